@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use crate::bound::loop_range;
+use crate::bytecode::{execute, Machine};
+use crate::solver::{factorial, floored_remainder, Expression};
+use crate::tokens::*;
+
+// intermediate results while evaluating a batch. constants stay scalars until they meet a column
+enum Column<'a>
+{
+    Scalar(f64),
+    Borrowed(&'a [f64]),
+    Owned(Vec<f64>),
+}
+
+impl Column<'_>
+{
+    fn values(&self) -> &[f64]
+    {
+        match self
+        {
+            Column::Scalar(val) => std::slice::from_ref(val),
+            Column::Borrowed(values) => values,
+            Column::Owned(values) => values,
+        }
+    }
+
+    fn at(&self, row: usize) -> f64
+    {
+        match self
+        {
+            Column::Scalar(val) => *val,
+            column => column.values()[row],
+        }
+    }
+}
+
+// the columns of a program's variables in slot order
+struct Columns<'a>
+{
+    columns: Vec<&'a [f64]>,
+    rows: usize,
+}
+
+impl Columns<'_>
+{
+    // the counter values of a loop in one row, or None when its bounds there aren't whole numbers
+    fn row_range(&self, (lower, upper): (&Column, &Column), row: usize) -> Option<(i64, i64)>
+    {
+        loop_range(lower.at(row), upper.at(row))
+    }
+}
+
+impl<'a> Machine for Columns<'a>
+{
+    type Value = Column<'a>;
+
+    fn constant(&self, val: f64) -> Column<'a>
+    {
+        Column::Scalar(val)
+    }
+
+    fn load(&self, slot: usize) -> Column<'a>
+    {
+        Column::Borrowed(self.columns[slot])
+    }
+
+    fn apply(&self, lhs: Column<'a>, op: Operation, rhs: Column<'a>) -> Column<'a>
+    {
+        apply_operation(lhs, op, rhs)
+    }
+
+    fn call(&self, func: Function, arg: Column<'a>) -> Column<'a>
+    {
+        apply_function(func, arg)
+    }
+
+    // every counter value some row needs. the other rows skip the terms outside their own bounds
+    fn range(&self, lower: &Column, upper: &Column) -> (i64, i64)
+    {
+        (0..self.rows)
+            .filter_map(|row| self.row_range((lower, upper), row))
+            .reduce(|(first1, last1), (first2, last2)| (first1.min(first2), last1.max(last2)))
+            .unwrap_or((1, 0))
+    }
+
+    fn accumulate(
+        &self,
+        op: Operation,
+        total: Column<'a>,
+        term: Column<'a>,
+        k: i64,
+        bounds: (&Column, &Column),
+    ) -> Column<'a>
+    {
+        if let (Column::Scalar(_), Column::Scalar(_)) = bounds
+        {
+            return apply_operation(total, op, term);
+        }
+
+        let totals = (0..self.rows).map(|row| match self.row_range(bounds, row)
+        {
+            Some((first, last)) if first <= k && k <= last =>
+            {
+                Expression::eval_binary_op(total.at(row), op, term.at(row))
+            },
+            _ => total.at(row),
+        });
+        Column::Owned(totals.collect())
+    }
+
+    // bounds that aren't whole numbers give NaN in their row
+    fn finish(&self, total: Column<'a>, bounds: (&Column, &Column)) -> Column<'a>
+    {
+        match bounds
+        {
+            (Column::Scalar(_), Column::Scalar(_)) => match self.row_range(bounds, 0)
+            {
+                Some(_) => total,
+                None => Column::Scalar(f64::NAN),
+            },
+            _ => Column::Owned(
+                (0..self.rows)
+                    .map(|row| match self.row_range(bounds, row)
+                    {
+                        Some(_) => total.at(row),
+                        None => f64::NAN,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Expression
+{
+    // evaluates the expression once per row of `columns`, one operation at a time over whole
+    // columns instead of one row at a time through the tree. fails with the name of any variable
+    // `columns` has no values for
+    pub fn eval_batch(&self, columns: &HashMap<String, Vec<f64>>) -> Result<Vec<f64>, String>
+    {
+        let mut lengths = columns.values().map(|column| column.len());
+        let rows = lengths.next().unwrap_or(1);
+        assert!(
+            lengths.all(|length| length == rows),
+            "columns have different lengths"
+        );
+
+        let program = self.compile();
+        let columns = Columns {
+            columns: program
+                .variables
+                .iter()
+                .map(|name| {
+                    columns
+                        .get(name)
+                        .map(|column| column.as_slice())
+                        .ok_or_else(|| name.clone())
+                })
+                .collect::<Result<_, _>>()?,
+            rows,
+        };
+
+        let mut stack: Vec<Column> = Vec::with_capacity(program.stack_size);
+        Ok(match execute(&program, &columns, &mut stack)
+        {
+            Column::Scalar(val) => vec![val; rows],
+            Column::Borrowed(values) => values.to_vec(),
+            Column::Owned(values) => values,
+        })
+    }
+}
+
+// matching on the operator once per column instead of once per value keeps the inner loops
+// simple enough for the compiler to vectorize
+fn apply_operation<'a>(lhs: Column<'a>, op: Operation, rhs: Column<'a>) -> Column<'a>
+{
+    match op
+    {
+        Operation::Addition => apply_elementwise(lhs, rhs, |val1, val2| val1 + val2),
+        Operation::Subtraction => apply_elementwise(lhs, rhs, |val1, val2| val1 - val2),
+        Operation::Multiplication | Operation::Dot =>
+        {
+            apply_elementwise(lhs, rhs, |val1, val2| val1 * val2)
+        },
+        Operation::Division => apply_elementwise(lhs, rhs, |val1, val2| val1 / val2),
+        Operation::IntegerDivision =>
+        {
+            apply_elementwise(lhs, rhs, |val1, val2| (val1 / val2).floor())
+        },
+        Operation::Remainder => apply_elementwise(lhs, rhs, floored_remainder),
+        Operation::Exponentiation => apply_elementwise(lhs, rhs, f64::powf),
+    }
+}
+
+fn apply_function(func: Function, arg: Column) -> Column
+{
+    match func
+    {
+        Function::Sqrt => map_elementwise(arg, f64::sqrt),
+        Function::Exp => map_elementwise(arg, f64::exp),
+        Function::Ln => map_elementwise(arg, f64::ln),
+        Function::Sin => map_elementwise(arg, f64::sin),
+        Function::Cos => map_elementwise(arg, f64::cos),
+        Function::Tan => map_elementwise(arg, f64::tan),
+        Function::Factorial => map_elementwise(arg, factorial),
+        Function::Negate => map_elementwise(arg, |val| -val),
+        Function::Det => arg,
+        Function::Inverse => map_elementwise(arg, |val| 1. / val),
+        // every value in the column is a separate list of one
+        Function::Aggregate(aggregate) if aggregate.min_len() > 1 =>
+        {
+            map_elementwise(arg, |_| f64::NAN)
+        },
+        Function::Aggregate(_) => arg,
+        Function::Sum | Function::Product => unreachable!("sums are loops in a program"),
+    }
+}
+
+fn map_elementwise(arg: Column, f: impl Fn(f64) -> f64) -> Column
+{
+    match arg
+    {
+        Column::Scalar(val) => Column::Scalar(f(val)),
+        Column::Borrowed(values) => Column::Owned(values.iter().map(|&val| f(val)).collect()),
+        Column::Owned(mut values) =>
+        {
+            values.iter_mut().for_each(|val| *val = f(*val));
+            Column::Owned(values)
+        },
+    }
+}
+
+fn apply_elementwise<'a>(
+    lhs: Column<'a>,
+    rhs: Column<'a>,
+    f: impl Fn(f64, f64) -> f64,
+) -> Column<'a>
+{
+    match (lhs, rhs)
+    {
+        (Column::Scalar(val1), Column::Scalar(val2)) => Column::Scalar(f(val1, val2)),
+
+        // reuse the buffer of an earlier result when there is one
+        (Column::Owned(mut values), Column::Scalar(val2)) =>
+        {
+            values.iter_mut().for_each(|val1| *val1 = f(*val1, val2));
+            Column::Owned(values)
+        },
+        (Column::Scalar(val1), Column::Owned(mut values)) =>
+        {
+            values.iter_mut().for_each(|val2| *val2 = f(val1, *val2));
+            Column::Owned(values)
+        },
+        (Column::Owned(mut values), rhs) =>
+        {
+            values
+                .iter_mut()
+                .zip(rhs.values())
+                .for_each(|(val1, &val2)| *val1 = f(*val1, val2));
+            Column::Owned(values)
+        },
+        (lhs, Column::Owned(mut values)) =>
+        {
+            values
+                .iter_mut()
+                .zip(lhs.values())
+                .for_each(|(val2, &val1)| *val2 = f(val1, *val2));
+            Column::Owned(values)
+        },
+
+        (Column::Scalar(val1), rhs) =>
+        {
+            Column::Owned(rhs.values().iter().map(|&val2| f(val1, val2)).collect())
+        },
+        (lhs, Column::Scalar(val2)) =>
+        {
+            Column::Owned(lhs.values().iter().map(|&val1| f(val1, val2)).collect())
+        },
+        (lhs, rhs) => Column::Owned(
+            lhs.values()
+                .iter()
+                .zip(rhs.values())
+                .map(|(&val1, &val2)| f(val1, val2))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_eval_batch()
+    {
+        let tree = Expression::parse("(4*x-(8-y))/(5^.4*x) + 5^(.2-y/3) - x*y + cos(x)/exp(y)");
+        let xs = vec![1., -3.5, 10., 0.25, 6.];
+        let ys = vec![2., 0., 7.25, -1., 6.];
+        let columns = HashMap::from([("x".to_string(), xs.clone()), ("y".to_string(), ys.clone())]);
+
+        let expected: Vec<f64> = xs
+            .iter()
+            .zip(&ys)
+            .map(|(&x, &y)| {
+                tree.eval_with(&HashMap::from([("x".to_string(), x), ("y".to_string(), y)]))
+            })
+            .collect();
+
+        assert_eq!(tree.eval_batch(&columns), Ok(expected));
+    }
+
+    #[test]
+    fn test_eval_batch_broadcast()
+    {
+        let columns = HashMap::from([("x".to_string(), vec![1., 2., 3.])]);
+
+        assert_eq!(
+            Expression::parse("2*3").eval_batch(&columns),
+            Ok(vec![6., 6., 6.])
+        );
+        assert_eq!(
+            Expression::parse("x").eval_batch(&columns),
+            Ok(vec![1., 2., 3.])
+        );
+        assert_eq!(
+            Expression::parse("2^x").eval_batch(&columns),
+            Ok(vec![2., 4., 8.])
+        );
+        assert_eq!(
+            Expression::parse("2*3").eval_batch(&HashMap::new()),
+            Ok(vec![6.])
+        );
+        assert_eq!(
+            Expression::parse("x + y").eval_batch(&columns),
+            Err("y".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_batch_loop()
+    {
+        // every row loops up to its own bound, and bounds that aren't whole numbers give NaN
+        let columns = HashMap::from([
+            ("x".to_string(), vec![2., 2., 3., 1.]),
+            ("n".to_string(), vec![3., 0., 2., 0.5]),
+        ]);
+        let result = Expression::parse("sum(k, 1, n, x^k) + sum(k, 1, 2, prod(j, k, n, j))")
+            .eval_batch(&columns)
+            .unwrap();
+
+        assert_eq!(result[..3], [14. + 12., 0. + 2., 12. + 4.]);
+        assert!(result[3].is_nan());
+        assert_eq!(
+            Expression::parse("sum(k, n, 2, k)")
+                .eval_batch(&columns)
+                .unwrap()[..3],
+            [0., 3., 2.]
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::numeric::{LiteralParser, Numeric};
+use crate::solver::Expression;
+use crate::tokens::*;
+
+// sums and products with at most this many terms are written out term by term in the steps of
+// simplify_wavefront. bigger ones are evaluated in a single step
+pub const EXPANDED_TERMS: usize = 10;
+
+// a node waiting to be visited, with the variables bound by the sums around it
+type ScopedNode = (Rc<RefCell<Expression>>, Rc<Vec<String>>);
+
+impl Expression
+{
+    pub fn has_bound_variables(&self) -> bool
+    {
+        self.to_postfix_tokens().iter().any(|token| {
+            token
+                .get_function()
+                .is_some_and(|func| func.binds_variable())
+        })
+    }
+
+    // variables that aren't bound by a sum or product around them, in the order they appear
+    pub fn free_variables(&self) -> Vec<String>
+    {
+        let mut free: Vec<String> = Vec::new();
+        let mut node_stack: Vec<ScopedNode> = Vec::new();
+
+        let mut visit =
+            |node: &Expression, bound: &Rc<Vec<String>>, node_stack: &mut Vec<_>| match &node
+                .operator
+            {
+                Token::Variable { name } =>
+                {
+                    if !bound.contains(name) && !free.contains(name)
+                    {
+                        free.push(name.clone());
+                    }
+                },
+                Token::Function { func } if func.binds_variable() =>
+                {
+                    let var = node.operands[0]
+                        .borrow()
+                        .operator
+                        .get_variable()
+                        .map(str::to_string);
+                    let mut inner = bound.as_ref().clone();
+                    inner.extend(var);
+
+                    node_stack.push((node.operands[3].clone(), Rc::new(inner)));
+                    node_stack.push((node.operands[2].clone(), bound.clone()));
+                    node_stack.push((node.operands[1].clone(), bound.clone()));
+                },
+                _ => node_stack.extend(
+                    node.operands
+                        .iter()
+                        .rev()
+                        .map(|operand| (operand.clone(), bound.clone())),
+                ),
+            };
+
+        visit(self, &Rc::new(Vec::new()), &mut node_stack);
+        while let Some((node, bound)) = node_stack.pop()
+        {
+            visit(&node.borrow(), &bound, &mut node_stack);
+        }
+
+        free
+    }
+
+    // writes every sum and product out as a chain of additions or multiplications, so code that
+    // only knows about plain operators can work with them. sums whose bounds still use other
+    // variables once any enclosing sums have been written out are left as they are
+    pub fn expand_bound_variables(&self) -> Expression
+    {
+        self.expand_ranges(usize::MAX)
+    }
+
+    // like expand_bound_variables, but sums and products with more than `max_terms` terms are
+    // replaced by their value instead, or left as they are when that still needs other variables
+    pub fn expand_ranges(&self, max_terms: usize) -> Expression
+    {
+        let mut stack: Vec<Expression> = Vec::new();
+
+        for token in self.to_postfix_tokens()
+        {
+            let operands = stack.split_off(stack.len() - token.arity());
+
+            let node = match token
+            {
+                Token::Function { func } if func.binds_variable() =>
+                {
+                    let [var, lower, upper, body]: [Expression; 4] =
+                        operands.try_into().ok().unwrap();
+
+                    match bounds(&lower, &upper).map(|(lower, upper)| loop_range(lower, upper))
+                    {
+                        Some(Some((lower, upper))) if range_len(lower, upper) > max_terms =>
+                        {
+                            // a body with other variables has no value yet, so it stays a sum
+                            let node = bound_node(func, var, lower, upper, body);
+                            match node.free_variables().is_empty()
+                            {
+                                true => Expression::number(node.eval()),
+                                false => node,
+                            }
+                        },
+                        Some(Some((lower, upper))) =>
+                        {
+                            let var = var.operator.get_variable().unwrap().to_string();
+                            expand_range(func, &var, lower, upper, &body, max_terms)
+                        },
+                        // numbers that aren't whole have no terms to write out, and the sum has
+                        // no value
+                        Some(None) => Expression::number(f64::NAN),
+                        // an inner sum whose bounds depend on an outer one is expanded once the
+                        // outer one fills them in
+                        None => Expression {
+                            operator: token,
+                            operands: [var, lower, upper, body]
+                                .into_iter()
+                                .map(|operand| Rc::new(RefCell::new(operand)))
+                                .collect(),
+                        },
+                    }
+                },
+                _ => Expression {
+                    operator: token,
+                    operands: operands
+                        .into_iter()
+                        .map(|operand| Rc::new(RefCell::new(operand)))
+                        .collect(),
+                },
+            };
+            stack.push(node);
+        }
+
+        stack.pop().expect("tried to pop value off empty stack")
+    }
+
+    // value of a sum or product, with the bound variable added to `vars` for the body only
+    pub(crate) fn eval_bound<N: Numeric>(
+        func: Function,
+        operands: &[Rc<RefCell<Expression>>],
+        vars: &HashMap<String, N>,
+        literal: LiteralParser<N>,
+    ) -> Result<N, N::Error>
+    {
+        let var = operands[0].borrow();
+        let var = var
+            .operator
+            .get_variable()
+            .unwrap_or_else(|| panic!("first argument of {} must be a variable", func.name()));
+        let lower = Self::eval_helper(&operands[1].borrow(), vars, literal)?;
+        let upper = Self::eval_helper(&operands[2].borrow(), vars, literal)?;
+        let (lower, upper) = match loop_range(lower.to_f64(), upper.to_f64())
+        {
+            Some(range) => range,
+            None =>
+            {
+                let description = format!("{}( {}, {}, {} )", func.name(), var, lower, upper);
+                return N::invalid_bounds(description);
+            },
+        };
+        let body = operands[3].borrow();
+
+        let mut scope = vars.clone();
+        let mut result = literal(&identity(func).to_string())?;
+        for k in lower..=upper
+        {
+            scope.insert(var.to_string(), literal(&k.to_string())?);
+            let term = Self::eval_helper(&body, &scope, literal)?;
+
+            result = match func
+            {
+                Function::Product => result.mul(&term)?,
+                _ => result.add(&term)?,
+            };
+        }
+
+        Ok(result)
+    }
+}
+
+pub(crate) fn identity(func: Function) -> f64
+{
+    match func
+    {
+        Function::Product => 1.,
+        _ => 0.,
+    }
+}
+
+// the operation that puts the terms of a sum or product together
+pub(crate) fn combining(func: Function) -> Operation
+{
+    match func
+    {
+        Function::Product => Operation::Multiplication,
+        _ => Operation::Addition,
+    }
+}
+
+// the first and last value of the counter of a loop, or None when a bound isn't a whole number
+pub(crate) fn loop_range(lower: f64, upper: f64) -> Option<(i64, i64)>
+{
+    match lower.fract() == 0. && upper.fract() == 0.
+    {
+        true => Some((lower as i64, upper as i64)),
+        false => None,
+    }
+}
+
+// the values of the bounds, or None while they still use variables
+fn bounds(lower: &Expression, upper: &Expression) -> Option<(f64, f64)>
+{
+    if lower
+        .to_postfix_tokens()
+        .iter()
+        .any(|token| token.get_variable().is_some())
+        || upper
+            .to_postfix_tokens()
+            .iter()
+            .any(|token| token.get_variable().is_some())
+    {
+        return None;
+    }
+
+    Some((lower.eval(), upper.eval()))
+}
+
+fn range_len(lower: i64, upper: i64) -> usize
+{
+    (upper - lower + 1).max(0) as usize
+}
+
+fn bound_node(
+    func: Function,
+    var: Expression,
+    lower: i64,
+    upper: i64,
+    body: Expression,
+) -> Expression
+{
+    Expression {
+        operator: Token::Function { func },
+        operands: [
+            var,
+            Expression::number(lower as f64),
+            Expression::number(upper as f64),
+            body,
+        ]
+        .into_iter()
+        .map(|operand| Rc::new(RefCell::new(operand)))
+        .collect(),
+    }
+}
+
+// body + body + ... with `var` replaced by each value in turn
+fn expand_range(
+    func: Function,
+    var: &str,
+    lower: i64,
+    upper: i64,
+    body: &Expression,
+    max_terms: usize,
+) -> Expression
+{
+    let op = combining(func);
+    let body_tokens = body.to_postfix_tokens();
+
+    let mut result: Option<Expression> = None;
+    for k in lower..=upper
+    {
+        let tokens = body_tokens
+            .iter()
+            .map(|token| match token.get_variable()
+            {
+                Some(name) if name == var => Token::Number { val: k.to_string() },
+                _ => token.clone(),
+            })
+            .collect();
+        let term = Expression::from_postfix_tokens(tokens).expand_ranges(max_terms);
+
+        result = Some(match result
+        {
+            Some(lhs) => Expression::binary(op, lhs, term),
+            None => term,
+        });
+    }
+
+    result.unwrap_or_else(|| Expression::number(identity(func)))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::interval::{Interval, IntervalError};
+    use crate::rational::{Irrational, RationalError};
+
+    #[test]
+    fn test_eval_bound()
+    {
+        assert_eq!(Expression::parse("sum(k, 1, 100, k^2)").eval(), 338350.);
+        assert_eq!(Expression::parse("prod(i, 1, 10, i)").eval(), 3628800.);
+        assert_eq!(
+            Expression::parse("sum(i, 1, 4, sum(j, 1, i, j))").eval(),
+            20.
+        );
+        assert_eq!(
+            Expression::parse("sum(k, 5, 4, k) + prod(k, 1, 0, k)").eval(),
+            1.
+        );
+
+        // the bound variable shadows x inside the sum only
+        let vars = HashMap::from([("x".to_string(), 10.), ("n".to_string(), 3.)]);
+        assert_eq!(
+            Expression::parse("x + sum(x, 1, n, x)").eval_with(&vars),
+            16.
+        );
+    }
+
+    #[test]
+    fn test_invalid_bounds()
+    {
+        let tree = Expression::parse("sum(k, 1, 2.5, k)");
+        assert!(tree.eval().is_nan());
+        assert!(tree.expand_bound_variables().eval().is_nan());
+        assert_eq!(
+            tree.eval_rational(Irrational::Error),
+            Err(RationalError::Undefined("sum( k, 1, 5/2 )".to_string()))
+        );
+
+        // an interval has no single value to count up to
+        let vars = HashMap::from([("n".to_string(), Interval::new(2., 3.))]);
+        assert_eq!(
+            Expression::parse("prod(k, 1, n, k)").eval_interval_with(&vars),
+            Err(IntervalError::Undefined(
+                "prod( k, [1, 1], [2, 3] )".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_expand_bound_variables()
+    {
+        let tree = Expression::parse("sum(k, 1, 3, k^2)");
+        assert_eq!(
+            tree.expand_bound_variables().expression_string(),
+            "( ( ( 1 ^ 2 ) + ( 2 ^ 2 ) ) + ( 3 ^ 2 ) ) "
+        );
+
+        let tree = Expression::parse("x * sum(i, 1, 2, prod(j, 1, i, j + x))");
+        assert_eq!(
+            tree.expand_bound_variables().expression_string(),
+            "( x * ( ( 1 + x ) + ( ( 1 + x ) * ( 2 + x ) ) ) ) "
+        );
+        assert_eq!(
+            Expression::parse("sum(k, 1, 100, k) + prod(k, 1, 2, k)")
+                .expand_ranges(EXPANDED_TERMS)
+                .expression_string(),
+            "( 5050 + ( 1 * 2 ) ) "
+        );
+        assert_eq!(
+            Expression::parse("sum(k, 1, 100, k * x)")
+                .expand_ranges(EXPANDED_TERMS)
+                .expression_string(),
+            "sum( k , 1 , 100 , ( k * x ) ) "
+        );
+    }
+
+    #[test]
+    fn test_free_variables()
+    {
+        let tree = Expression::parse("x * sum(k, 1, n, k * y) + prod(n, 1, 3, n) + k");
+
+        assert_eq!(tree.free_variables(), vec!["x", "n", "y", "k"]);
+        assert_eq!(
+            Expression::parse("2 * sum(k, 1, 3, k * y) + x")
+                .partial_eval()
+                .expression_string(),
+            "( ( 2 * sum( k , 1 , 3 , ( k * y ) ) ) + x ) "
+        );
+        assert_eq!(
+            Expression::parse("2 * sum(k, 1, 3, k^2) + x")
+                .partial_eval()
+                .expression_string(),
+            "( 28 + x ) "
+        );
+    }
+
+    #[test]
+    fn test_bound_strings()
+    {
+        let tree = Expression::parse("sum(k, 1, 100, k^2) / prod(i, 1, n, i)");
+
+        assert_eq!(
+            tree.expression_string(),
+            "( sum( k , 1 , 100 , ( k ^ 2 ) ) / prod( i , 1 , n , i ) ) "
+        );
+        assert_eq!(
+            tree.latex_string(),
+            "\\frac{\\sum_{k=1}^{100}\\left({k}^{2}\\right)}{\\prod_{i=1}^{n}\\left(i\\right)}"
+        );
+    }
+}
@@ -0,0 +1,548 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::bound::{combining, identity, loop_range};
+use crate::solver::Expression;
+use crate::tokens::*;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Instruction
+{
+    Push(f64),
+    Load(usize),
+    Apply(Operation),
+    Call(Function),
+    // the counter of the loop at this depth, counting from the outermost one
+    Counter(usize),
+    // pops the upper and then the lower bound, and runs the next `len` instructions once for every
+    // whole number between them, adding or multiplying the results together
+    Loop
+    {
+        func: Function,
+        len: usize,
+    },
+}
+
+// an expression flattened into postfix order, with every variable replaced by an index into the
+// slice of values passed to Vm::run
+#[derive(Debug, PartialEq)]
+pub struct Program
+{
+    pub instructions: Vec<Instruction>,
+    pub variables: Vec<String>,
+    pub stack_size: usize,
+}
+
+impl Program
+{
+    pub fn variable_slot(&self, name: &str) -> Option<usize>
+    {
+        self.variables.iter().position(|variable| variable == name)
+    }
+
+    // puts the values for each variable in the order Vm::run expects them. fails with the name of
+    // any variable `vars` has no value for
+    pub fn slots_from(&self, vars: &HashMap<String, f64>) -> Result<Vec<f64>, String>
+    {
+        self.variables
+            .iter()
+            .map(|name| vars.get(name).copied().ok_or_else(|| name.clone()))
+            .collect()
+    }
+}
+
+impl Expression
+{
+    pub fn compile(&self) -> Program
+    {
+        let mut instructions = Vec::new();
+        let mut variables: Vec<String> = Vec::new();
+        let mut stack_size = 0;
+        let mut depth = 0;
+
+        // sums and products become loops, so programs stay small however many terms they have
+        let tree = Expression::from_postfix_tokens(without_arrays(self.to_postfix_tokens()));
+
+        // the variables bound by the loops around the node being compiled, innermost last
+        let mut scope: Vec<String> = Vec::new();
+        // index of the Loop instruction of each of them
+        let mut loops: Vec<usize> = Vec::new();
+
+        let mut steps = vec![Step::Visit(Rc::new(RefCell::new(tree)))];
+        while let Some(step) = steps.pop()
+        {
+            let token = match step
+            {
+                Step::Visit(node) =>
+                {
+                    let node = node.borrow();
+                    match node.operator
+                    {
+                        Token::Function { func } if func.binds_variable() =>
+                        {
+                            let var = node.operands[0]
+                                .borrow()
+                                .operator
+                                .get_variable()
+                                .unwrap_or_else(|| {
+                                    panic!("first argument of {} must be a variable", func.name())
+                                })
+                                .to_string();
+
+                            steps.push(Step::EndLoop);
+                            steps.push(Step::Visit(node.operands[3].clone()));
+                            steps.push(Step::BeginLoop(func, var));
+                            steps.push(Step::Visit(node.operands[2].clone()));
+                            steps.push(Step::Visit(node.operands[1].clone()));
+                        },
+                        _ =>
+                        {
+                            steps.push(Step::Emit(node.operator.clone()));
+                            steps.extend(node.operands.iter().rev().cloned().map(Step::Visit));
+                        },
+                    }
+                    continue;
+                },
+                Step::BeginLoop(func, var) =>
+                {
+                    scope.push(var);
+                    loops.push(instructions.len());
+                    instructions.push(Instruction::Loop { func, len: 0 });
+                    depth -= 2;
+                    continue;
+                },
+                Step::EndLoop =>
+                {
+                    scope.pop();
+                    let start = loops.pop().expect("loop ended without starting");
+                    let body_len = instructions.len() - start - 1;
+                    if let Instruction::Loop { len, .. } = &mut instructions[start]
+                    {
+                        *len = body_len;
+                    }
+                    continue;
+                },
+                Step::Emit(token) => token,
+            };
+
+            let instruction = match token
+            {
+                Token::Number { .. } =>
+                {
+                    Instruction::Push(token.get_number().expect("error getting number from token"))
+                },
+                Token::Variable { name } if scope.contains(&name) =>
+                {
+                    Instruction::Counter(scope.iter().rposition(|var| *var == name).unwrap())
+                },
+                Token::Variable { name } =>
+                {
+                    let slot = match variables.iter().position(|variable| *variable == name)
+                    {
+                        Some(slot) => slot,
+                        None =>
+                        {
+                            variables.push(name);
+                            variables.len() - 1
+                        },
+                    };
+
+                    Instruction::Load(slot)
+                },
+                Token::Operator { op } => Instruction::Apply(op),
+                Token::Function { func } => Instruction::Call(func),
+                Token::Array { .. } => unreachable!("arrays are replaced before compiling"),
+                Token::LSep | Token::RSep | Token::LBracket | Token::RBracket | Token::Comma =>
+                {
+                    panic!("unexpected token for postfix expression: {:?}", token)
+                },
+            };
+
+            match instruction
+            {
+                Instruction::Push(_) | Instruction::Load(_) | Instruction::Counter(_) => depth += 1,
+                Instruction::Apply(_) => depth -= 1,
+                Instruction::Call(_) | Instruction::Loop { .. } => (),
+            }
+            stack_size = stack_size.max(depth);
+
+            instructions.push(instruction);
+        }
+
+        Program { instructions, variables, stack_size }
+    }
+}
+
+// work left while compiling, in the order it happens
+enum Step
+{
+    Visit(Rc<RefCell<Expression>>),
+    Emit(Token),
+    BeginLoop(Function, String),
+    EndLoop,
+}
+
+// programs only hold plain numbers, so every array is replaced by NaN like eval gives for it
+fn without_arrays(tokens: Vec<Token>) -> Vec<Token>
+{
+    let mut result: Vec<Token> = Vec::with_capacity(tokens.len());
+    // index in `result` of the first token of each subtree on the stack
+    let mut starts: Vec<usize> = Vec::new();
+
+    for token in tokens
+    {
+        let mut start = result.len();
+        for _ in 0..token.arity()
+        {
+            start = starts.pop().expect("tried to pop value off empty stack");
+        }
+
+        match token
+        {
+            Token::Array { .. } =>
+            {
+                result.truncate(start);
+                result.push(Token::Number { val: f64::NAN.to_string() });
+            },
+            _ => result.push(token),
+        }
+        starts.push(start);
+    }
+
+    result
+}
+
+// the kind of value a program is run on, like plain numbers in the vm or columns in eval_batch
+pub(crate) trait Machine
+{
+    type Value;
+
+    fn constant(&self, val: f64) -> Self::Value;
+    fn load(&self, slot: usize) -> Self::Value;
+    fn apply(&self, lhs: Self::Value, op: Operation, rhs: Self::Value) -> Self::Value;
+    fn call(&self, func: Function, arg: Self::Value) -> Self::Value;
+
+    // the first and last value of a loop counter. a range with no values skips the loop
+    fn range(&self, lower: &Self::Value, upper: &Self::Value) -> (i64, i64);
+    // puts the term for the counter value `k` into the total
+    fn accumulate(
+        &self,
+        op: Operation,
+        total: Self::Value,
+        term: Self::Value,
+        k: i64,
+        bounds: (&Self::Value, &Self::Value),
+    ) -> Self::Value;
+    // the value of a loop once every term is in
+    fn finish(&self, total: Self::Value, bounds: (&Self::Value, &Self::Value)) -> Self::Value;
+}
+
+// a loop that is running, with its total so far
+struct Frame<V>
+{
+    op: Operation,
+    start: usize,
+    end: usize,
+    counter: i64,
+    last: i64,
+    total: V,
+    lower: V,
+    upper: V,
+}
+
+// runs a program on any kind of value. loops keep their state in frames instead of recursing, so
+// going back to the start of a body is just a jump
+pub(crate) fn execute<M: Machine>(
+    program: &Program,
+    machine: &M,
+    stack: &mut Vec<M::Value>,
+) -> M::Value
+{
+    let instructions = &program.instructions;
+    let mut frames: Vec<Frame<M::Value>> = Vec::new();
+    let mut pc = 0;
+
+    while pc < instructions.len() || !frames.is_empty()
+    {
+        // the body of the innermost loop just left a term on the stack
+        if frames.last().is_some_and(|frame| frame.end == pc)
+        {
+            let mut frame = frames.pop().unwrap();
+            let term = stack.pop().expect("tried to pop value off empty stack");
+            let bounds = (&frame.lower, &frame.upper);
+            let total = machine.accumulate(frame.op, frame.total, term, frame.counter, bounds);
+
+            match frame.counter < frame.last
+            {
+                true =>
+                {
+                    frame.total = total;
+                    frame.counter += 1;
+                    pc = frame.start;
+                    frames.push(frame);
+                },
+                false => stack.push(machine.finish(total, (&frame.lower, &frame.upper))),
+            }
+            continue;
+        }
+
+        match instructions[pc]
+        {
+            Instruction::Push(val) => stack.push(machine.constant(val)),
+            Instruction::Load(slot) => stack.push(machine.load(slot)),
+            Instruction::Counter(depth) =>
+            {
+                stack.push(machine.constant(frames[depth].counter as f64))
+            },
+            Instruction::Apply(op) =>
+            {
+                let rhs = stack.pop().expect("tried to pop value off empty stack");
+                let lhs = stack.pop().expect("tried to pop value off empty stack");
+
+                stack.push(machine.apply(lhs, op, rhs));
+            },
+            Instruction::Call(func) =>
+            {
+                let arg = stack.pop().expect("tried to pop value off empty stack");
+
+                stack.push(machine.call(func, arg));
+            },
+            Instruction::Loop { func, len } =>
+            {
+                let upper = stack.pop().expect("tried to pop value off empty stack");
+                let lower = stack.pop().expect("tried to pop value off empty stack");
+                let (first, last) = machine.range(&lower, &upper);
+                let total = machine.constant(identity(func));
+
+                match first <= last
+                {
+                    true => frames.push(Frame {
+                        op: combining(func),
+                        start: pc + 1,
+                        end: pc + 1 + len,
+                        counter: first,
+                        last,
+                        total,
+                        lower,
+                        upper,
+                    }),
+                    false =>
+                    {
+                        stack.push(machine.finish(total, (&lower, &upper)));
+                        pc += len;
+                    },
+                }
+            },
+        }
+        pc += 1;
+    }
+
+    stack.pop().expect("program left no result on the stack")
+}
+
+// plain numbers, with the values of the variables in slot order
+struct Numbers<'a>(&'a [f64]);
+
+impl Machine for Numbers<'_>
+{
+    type Value = f64;
+
+    fn constant(&self, val: f64) -> f64
+    {
+        val
+    }
+
+    fn load(&self, slot: usize) -> f64
+    {
+        self.0[slot]
+    }
+
+    fn apply(&self, lhs: f64, op: Operation, rhs: f64) -> f64
+    {
+        Expression::eval_binary_op(lhs, op, rhs)
+    }
+
+    fn call(&self, func: Function, arg: f64) -> f64
+    {
+        Expression::eval_function(func, arg)
+    }
+
+    fn range(&self, lower: &f64, upper: &f64) -> (i64, i64)
+    {
+        loop_range(*lower, *upper).unwrap_or((1, 0))
+    }
+
+    fn accumulate(&self, op: Operation, total: f64, term: f64, _: i64, _: (&f64, &f64)) -> f64
+    {
+        Expression::eval_binary_op(total, op, term)
+    }
+
+    // bounds that aren't whole numbers give NaN, like everything else undefined does
+    fn finish(&self, total: f64, (lower, upper): (&f64, &f64)) -> f64
+    {
+        match loop_range(*lower, *upper)
+        {
+            Some(_) => total,
+            None => f64::NAN,
+        }
+    }
+}
+
+// keeps its stack between runs so evaluating a program doesn't allocate
+#[derive(Debug, Default)]
+pub struct Vm
+{
+    stack: Vec<f64>,
+}
+
+impl Vm
+{
+    pub fn new() -> Vm
+    {
+        Vm { stack: Vec::new() }
+    }
+
+    pub fn run(&mut self, program: &Program, variables: &[f64]) -> f64
+    {
+        assert_eq!(
+            variables.len(),
+            program.variables.len(),
+            "wrong number of variable values for program"
+        );
+
+        self.stack.clear();
+        self.stack.reserve(program.stack_size);
+
+        execute(program, &Numbers(variables), &mut self.stack)
+    }
+}
+
+// an expression compiled to a function of one variable, for code that evaluates it at lots of
+// points like root finding and integration
+pub struct SingleVariable
+{
+    program: Program,
+    vm: Vm,
+}
+
+impl SingleVariable
+{
+    // fails with the name of any other variable the expression uses
+    pub fn new(expression: &Expression, var: &str) -> Result<SingleVariable, String>
+    {
+        let program = expression.compile();
+        if let Some(other) = program.variables.iter().find(|name| *name != var)
+        {
+            return Err(other.clone());
+        }
+
+        Ok(SingleVariable { program, vm: Vm::new() })
+    }
+
+    pub fn eval(&mut self, x: f64) -> f64
+    {
+        let values = [x];
+        let values = &values[..self.program.variables.len()];
+
+        self.vm.run(&self.program, values)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_compile()
+    {
+        let program = Expression::parse("x^2 + 3*(y - x)").compile();
+
+        assert_eq!(
+            program.instructions,
+            vec![
+                Instruction::Load(0),
+                Instruction::Push(2.),
+                Instruction::Apply(Operation::Exponentiation),
+                Instruction::Push(3.),
+                Instruction::Load(1),
+                Instruction::Load(0),
+                Instruction::Apply(Operation::Subtraction),
+                Instruction::Apply(Operation::Multiplication),
+                Instruction::Apply(Operation::Addition),
+            ]
+        );
+        assert_eq!(program.variables, vec!["x", "y"]);
+        assert_eq!(program.stack_size, 4);
+    }
+
+    #[test]
+    fn test_vm()
+    {
+        let tree = Expression::parse("(4*x-(8-y))/(5^.4*x) + 5^(.2-y/3) + sqrt(exp(y)) * sin(x)");
+        let program = tree.compile();
+        let mut vm = Vm::new();
+
+        for (x, y) in [(1., 2.), (-3.5, 0.), (10., 7.25)]
+        {
+            let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+
+            assert_eq!(
+                vm.run(&program, &program.slots_from(&vars).unwrap()),
+                tree.eval_with(&vars)
+            );
+        }
+
+        assert_eq!(
+            Vm::new().run(&Expression::parse("2*4+6").compile(), &[]),
+            14.
+        );
+        assert_eq!(
+            program.slots_from(&HashMap::from([("x".to_string(), 1.)])),
+            Err("y".to_string())
+        );
+    }
+
+    #[test]
+    fn test_compile_loop()
+    {
+        // sums become loops, whether their bounds are numbers or wait for the values of variables
+        let tree = Expression::parse("sum(k, 1, n, x^k * sum(j, 1, 2, j))");
+        let program = tree.compile();
+
+        assert_eq!(
+            program.instructions,
+            vec![
+                Instruction::Push(1.),
+                Instruction::Load(0),
+                Instruction::Loop { func: Function::Sum, len: 8 },
+                Instruction::Load(1),
+                Instruction::Counter(0),
+                Instruction::Apply(Operation::Exponentiation),
+                Instruction::Push(1.),
+                Instruction::Push(2.),
+                Instruction::Loop { func: Function::Sum, len: 1 },
+                Instruction::Counter(1),
+                Instruction::Apply(Operation::Multiplication),
+            ]
+        );
+        assert_eq!(program.variables, vec!["n", "x"]);
+        assert_eq!(program.stack_size, 3);
+
+        let mut vm = Vm::new();
+        for (n, x) in [(3., 2.), (0., 5.), (1., -1.)]
+        {
+            let vars = HashMap::from([("n".to_string(), n), ("x".to_string(), x)]);
+            assert_eq!(vm.run(&program, &[n, x]), tree.eval_with(&vars));
+        }
+        assert!(vm.run(&program, &[f64::INFINITY, 2.]).is_nan());
+
+        let program = Expression::parse("prod(i, 1, n, sum(j, i, n, j))").compile();
+        assert_eq!(vm.run(&program, &[3.]), 6. * 5. * 3.);
+
+        let program = Expression::parse("sum(k, 1, 16000, k^2)").compile();
+        assert_eq!(program.instructions.len(), 6);
+        assert_eq!(vm.run(&program, &[]), 16000. * 16001. * 32001. / 6.);
+    }
+}
@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, PI};
+use std::fmt;
+
+use crate::numeric::{array_description, Numeric};
+use crate::solver::{factorial, floored_remainder, Expression};
+use crate::tokens::*;
+
+#[derive(Debug, PartialEq)]
+pub enum ComplexError
+{
+    DivisionByZero,
+    InvalidLiteral(String),
+    // the operation that only works on real numbers, like "2i // 2"
+    NotReal(String),
+    // the operation that has no result at all, like "ln( 0 )"
+    Undefined(String),
+    // a statistic that needs more than one value, like "stdev( 1 + 2i )"
+    NotEnoughValues(String),
+    // a vector or matrix, like "[ 1, i ]", which has no complex value
+    Unsupported(String),
+}
+
+// re + im i. powers and functions take their principal value, so the imaginary part of a logarithm
+// is in (-pi, pi] and square roots have a real part that isn't negative
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Complex
+{
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex
+{
+    pub fn new(re: f64, im: f64) -> Complex
+    {
+        Complex { re, im }
+    }
+
+    pub fn real(re: f64) -> Complex
+    {
+        Complex { re, im: 0. }
+    }
+
+    // reads real literals like "2.5", and imaginary ones like "2i", "-0.5i" or "i"
+    pub fn parse(literal: &str) -> Result<Complex, ComplexError>
+    {
+        let invalid = || ComplexError::InvalidLiteral(literal.to_string());
+
+        match literal.strip_suffix('i')
+        {
+            Some("") => Ok(Complex::new(0., 1.)),
+            Some("-") => Ok(Complex::new(0., -1.)),
+            Some(im) => Ok(Complex::new(0., im.parse().map_err(|_| invalid())?)),
+            None => Ok(Complex::real(literal.parse().map_err(|_| invalid())?)),
+        }
+    }
+
+    // the names complex expressions can use without giving them a value
+    pub fn constants() -> HashMap<String, Complex>
+    {
+        HashMap::from([
+            ("i".to_string(), Complex::new(0., 1.)),
+            ("e".to_string(), Complex::real(E)),
+            ("pi".to_string(), Complex::real(PI)),
+        ])
+    }
+
+    pub fn abs(&self) -> f64
+    {
+        self.re.hypot(self.im)
+    }
+
+    // the angle from the positive real axis, in (-pi, pi]
+    pub fn arg(&self) -> f64
+    {
+        self.im.atan2(self.re)
+    }
+
+    pub fn is_real(&self) -> bool
+    {
+        self.im == 0.
+    }
+
+    // "r e^(θi)", which reads back in as the same number
+    pub fn polar_string(&self) -> String
+    {
+        format!("{} e^({}i)", self.abs(), self.arg())
+    }
+
+    // "1+2i"
+    pub fn latex_string(&self) -> String
+    {
+        self.to_string().replace(' ', "")
+    }
+
+    // "re^{θi}"
+    pub fn polar_latex_string(&self) -> String
+    {
+        format!("{}e^{{{}i}}", self.abs(), self.arg())
+    }
+
+    // the parts as they're printed. adding zero turns -0 into 0
+    fn printed_parts(&self) -> (f64, f64)
+    {
+        (self.re + 0., self.im + 0.)
+    }
+
+    // wraps numbers with both parts in parentheses, for error messages
+    fn operand(&self) -> String
+    {
+        match self.printed_parts()
+        {
+            (re, im) if re != 0. && im != 0. => format!("( {} )", self),
+            _ => self.to_string(),
+        }
+    }
+
+    fn real_part(&self, description: impl FnOnce() -> String) -> Result<f64, ComplexError>
+    {
+        match self.is_real()
+        {
+            true => Ok(self.re),
+            false => Err(ComplexError::NotReal(description())),
+        }
+    }
+
+    fn exp(&self) -> Complex
+    {
+        let scale = self.re.exp();
+
+        match self.is_real()
+        {
+            true => Complex::real(scale),
+            false =>
+            {
+                let (sin, cos) = sin_cos(self.im);
+                Complex::new(scale * cos, scale * sin)
+            },
+        }
+    }
+
+    fn ln(&self) -> Complex
+    {
+        Complex::new(self.abs().ln(), self.arg())
+    }
+
+    fn sqrt(&self) -> Complex
+    {
+        if self.re == 0. && self.im == 0.
+        {
+            return Complex::real(0.);
+        }
+
+        // halves of the modulus plus or minus the real part, so nothing cancels out
+        let r = self.abs();
+        match self.re >= 0.
+        {
+            true =>
+            {
+                let t = ((r + self.re) / 2.).sqrt();
+                Complex::new(t, self.im / (2. * t))
+            },
+            false =>
+            {
+                let t = ((r - self.re) / 2.).sqrt();
+                Complex::new(self.im.abs() / (2. * t), t.copysign(self.im))
+            },
+        }
+    }
+
+    fn sin(&self) -> Complex
+    {
+        let (sin, cos) = sin_cos(self.re);
+        Complex::new(sin * self.im.cosh(), cos * self.im.sinh())
+    }
+
+    fn cos(&self) -> Complex
+    {
+        let (sin, cos) = sin_cos(self.re);
+        Complex::new(cos * self.im.cosh(), -sin * self.im.sinh())
+    }
+
+    // by squaring, so powers of i and of negative numbers stay exact
+    fn powi(&self, exponent: i64) -> Result<Complex, ComplexError>
+    {
+        let mut result = Complex::real(1.);
+        let mut base = *self;
+        let mut n = exponent.unsigned_abs();
+        while n > 0
+        {
+            if n % 2 == 1
+            {
+                result = result.mul(&base)?;
+            }
+            base = base.mul(&base)?;
+            n /= 2;
+        }
+
+        match exponent < 0
+        {
+            true => Complex::real(1.).div(&result),
+            false => Ok(result),
+        }
+    }
+}
+
+// the sine and cosine of an angle, with values that are within the angle's own rounding error of
+// zero made exactly zero, so the imaginary part of e^(i*pi) doesn't come out as 1.2e-16
+fn sin_cos(angle: f64) -> (f64, f64)
+{
+    let noise = f64::EPSILON * angle.abs();
+    let exact = |val: f64| match val.abs() <= noise
+    {
+        true => 0.,
+        false => val,
+    };
+
+    (exact(angle.sin()), exact(angle.cos()))
+}
+
+// the difference of two products, which is zero when they only differ by their rounding error,
+// like the real part of (e^(i*pi/4))^2
+fn difference(lhs: f64, rhs: f64) -> f64
+{
+    match (lhs - rhs).abs() <= f64::EPSILON * (lhs.abs() + rhs.abs())
+    {
+        true => 0.,
+        false => lhs - rhs,
+    }
+}
+
+// "a + bi", with parts that are zero left out
+impl fmt::Display for Complex
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let imaginary = |im: f64| match im
+        {
+            _ if im == 1. => "i".to_string(),
+            _ if im == -1. => "-i".to_string(),
+            _ => format!("{}i", im),
+        };
+
+        let (re, im) = self.printed_parts();
+        if im == 0.
+        {
+            write!(f, "{}", re)
+        }
+        else if re == 0.
+        {
+            write!(f, "{}", imaginary(im))
+        }
+        else if im < 0.
+        {
+            write!(f, "{} - {}", re, imaginary(-im))
+        }
+        else
+        {
+            write!(f, "{} + {}", re, imaginary(im))
+        }
+    }
+}
+
+impl Numeric for Complex
+{
+    type Error = ComplexError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        Complex::parse(literal)
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        match self.is_real()
+        {
+            true => self.re,
+            false => f64::NAN,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Complex::new(self.re + other.re, self.im + other.im))
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Complex::new(self.re - other.re, self.im - other.im))
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Complex::new(
+            difference(self.re * other.re, self.im * other.im),
+            difference(self.re * other.im, -self.im * other.re),
+        ))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        if other.re == 0. && other.im == 0.
+        {
+            return Err(ComplexError::DivisionByZero);
+        }
+
+        let scale = other.re * other.re + other.im * other.im;
+        Ok(Complex::new(
+            difference(self.re * other.re, -self.im * other.im) / scale,
+            difference(self.im * other.re, self.re * other.im) / scale,
+        ))
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{} // {}", self.operand(), other.operand());
+        let (a, b) = (self.real_part(description)?, other.real_part(description)?);
+        if b == 0.
+        {
+            return Err(ComplexError::DivisionByZero);
+        }
+
+        Ok(Complex::real((a / b).floor()))
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{} % {}", self.operand(), other.operand());
+        let (a, b) = (self.real_part(description)?, other.real_part(description)?);
+        if b == 0.
+        {
+            return Err(ComplexError::DivisionByZero);
+        }
+
+        Ok(Complex::real(floored_remainder(a, b)))
+    }
+
+    // the principal value exp(other * ln(self))
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        if self.re == 0. && self.im == 0.
+        {
+            return if other.re == 0. && other.im == 0.
+            {
+                Ok(Complex::real(1.))
+            }
+            else if other.re > 0.
+            {
+                Ok(Complex::real(0.))
+            }
+            else if other.is_real()
+            {
+                Err(ComplexError::DivisionByZero)
+            }
+            else
+            {
+                Err(ComplexError::Undefined(format!("0 ^ {}", other.operand())))
+            };
+        }
+
+        match other.is_real()
+        {
+            true if self.is_real() && self.re > 0. => Ok(Complex::real(self.re.powf(other.re))),
+            true if other.re.fract() == 0. && other.re.abs() <= 1024. => self.powi(other.re as i64),
+            _ => Ok(self.ln().mul(other)?.exp()),
+        }
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{}( {} )", func.name(), self);
+
+        match func
+        {
+            Function::Sqrt => Ok(self.sqrt()),
+            Function::Exp => Ok(self.exp()),
+            Function::Ln if self.re == 0. && self.im == 0. =>
+            {
+                Err(ComplexError::Undefined(description()))
+            },
+            Function::Ln => Ok(self.ln()),
+            Function::Sin => Ok(self.sin()),
+            Function::Cos => Ok(self.cos()),
+            Function::Tan => self.sin().div(&self.cos()),
+            Function::Factorial => match factorial(self.to_f64())
+            {
+                val if val.is_nan() => Err(ComplexError::Undefined(description())),
+                val => Ok(Complex::real(val)),
+            },
+            Function::Negate => Ok(Complex::new(-self.re, -self.im)),
+            Function::Det => Ok(*self),
+            Function::Inverse => Complex::real(1.).div(self),
+            Function::Aggregate(aggregate) if aggregate.min_len() > 1 =>
+            {
+                Err(ComplexError::NotEnoughValues(description()))
+            },
+            Function::Aggregate(_) => Ok(*self),
+            Function::Sum | Function::Product =>
+            {
+                unreachable!("sums are evaluated with their bound variable")
+            },
+        }
+    }
+
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Err(ComplexError::Unsupported(array_description(&elements)))
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(ComplexError::Undefined(description))
+    }
+}
+
+impl Expression
+{
+    pub fn eval_complex(&self) -> Result<Complex, ComplexError>
+    {
+        self.eval_complex_with(&HashMap::new())
+    }
+
+    // i, e and pi are the usual constants unless `vars` gives them another value
+    pub fn eval_complex_with(
+        &self,
+        vars: &HashMap<String, Complex>,
+    ) -> Result<Complex, ComplexError>
+    {
+        let mut scope = Complex::constants();
+        scope.extend(vars.iter().map(|(name, val)| (name.clone(), *val)));
+
+        self.eval_numeric_with(&scope)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn eval(expr: &str) -> Result<String, ComplexError>
+    {
+        Expression::parse(expr)
+            .eval_complex()
+            .map(|val| val.to_string())
+    }
+
+    #[test]
+    fn test_eval_complex()
+    {
+        assert_eq!(eval("sqrt(-4)"), Ok("2i".to_string()));
+        assert_eq!(eval("(1+2i)*(3-i)"), Ok("5 + 5i".to_string()));
+        assert_eq!(eval("e^(i*pi)"), Ok("-1".to_string()));
+        assert_eq!(eval("tan(pi) + e^(i*pi/2)"), Ok("i".to_string()));
+        assert_eq!(eval("(e^(i*pi/4))^2"), Ok("i".to_string()));
+        assert_eq!(eval("(1 - 2i) / (3 + 4i)"), Ok("-0.2 - 0.4i".to_string()));
+        assert_eq!(eval("i^2 + i^-1"), Ok("-1 - i".to_string()));
+        assert_eq!(
+            eval("sum(k, 1, 4, i^k) + sum(i, 1, 3, i)"),
+            Ok("6".to_string())
+        );
+        assert_eq!(eval("7 // 2 + (-7) % 3 + 4!"), Ok("29".to_string()));
+
+        let vars = HashMap::from([("z".to_string(), Complex::new(3., -4.))]);
+        let result = Expression::parse("sqrt(z) * z^0").eval_complex_with(&vars);
+        assert_eq!(result, Ok(Complex::new(2., -1.)));
+    }
+
+    #[test]
+    fn test_principal_branches()
+    {
+        let close = |expr: &str, re: f64, im: f64| {
+            let val = Expression::parse(expr).eval_complex().unwrap();
+            assert!(
+                (val.re - re).abs() < 1e-12 && (val.im - im).abs() < 1e-12,
+                "{} = {}",
+                expr,
+                val
+            );
+        };
+
+        close("ln(-1)", 0., PI);
+        close("i^i", (-PI / 2.).exp(), 0.);
+        close("(-8)^(1/3)", 1., 3f64.sqrt());
+        close("sqrt(-3 - 4i)", 1., -2.);
+        close("exp(ln(2 + 3i))", 2., 3.);
+        close("sin(i)", 0., 1f64.sinh());
+        close("cos(pi + i) + tan(i)", -1f64.cosh(), 1f64.tanh());
+    }
+
+    #[test]
+    fn test_complex_errors()
+    {
+        assert_eq!(eval("1 / (i - i)"), Err(ComplexError::DivisionByZero));
+        assert_eq!(eval("0^-2"), Err(ComplexError::DivisionByZero));
+        assert_eq!(
+            eval("(1 + 2i) // 2"),
+            Err(ComplexError::NotReal("( 1 + 2i ) // 2".to_string()))
+        );
+        assert_eq!(
+            eval("ln(0)"),
+            Err(ComplexError::Undefined("ln( 0 )".to_string()))
+        );
+        assert_eq!(
+            eval("(2i)!"),
+            Err(ComplexError::Undefined("factorial( 2i )".to_string()))
+        );
+        assert_eq!(
+            eval("stdev(1 + 2i)"),
+            Err(ComplexError::NotEnoughValues("stdev( 1 + 2i )".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_complex_strings()
+    {
+        let z = Expression::parse("1 + i").eval_complex().unwrap();
+        assert_eq!(
+            z.polar_string(),
+            "1.4142135623730951 e^(0.7853981633974483i)"
+        );
+        assert_eq!(
+            z.polar_latex_string(),
+            "1.4142135623730951e^{0.7853981633974483i}"
+        );
+        assert_eq!(Complex::new(-0.5, -2.).latex_string(), "-0.5-2i");
+        assert_eq!(Complex::new(0., -1.).to_string(), "-i");
+        assert_eq!(Complex::new(-0., 0.).to_string(), "0");
+        assert_eq!(
+            Complex::new(1e20, 1000.).to_string(),
+            "100000000000000000000 + 1000i"
+        );
+
+        // the polar form reads back in as the same number
+        let polar = Expression::parse(&Complex::new(-1., -1.).polar_string())
+            .eval_complex()
+            .unwrap();
+        assert!((polar.re + 1.).abs() < 1e-12 && (polar.im + 1.).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::solver::Expression;
+use crate::tokens::*;
+
+impl Expression
+{
+    // merges structurally identical subtrees into a single shared node, turning the tree into a
+    // DAG. returns the new expression and how many nodes were merged away
+    pub fn deduplicate(self) -> (Expression, usize)
+    {
+        // every distinct subtree gets an id, identified by its token and the ids of its children
+        let mut subtree_ids: HashMap<(Token, Vec<usize>), usize> = HashMap::new();
+        let mut nodes: Vec<Rc<RefCell<Expression>>> = Vec::new();
+        let mut id_stack: Vec<usize> = Vec::new();
+        let mut deduplicated = 0;
+
+        for token in self.to_postfix_tokens()
+        {
+            let children = id_stack.split_off(id_stack.len() - token.arity());
+            let key = (token, children);
+
+            let id = match subtree_ids.get(&key)
+            {
+                Some(&id) =>
+                {
+                    deduplicated += 1;
+                    id
+                },
+                None =>
+                {
+                    let operands = key.1.iter().map(|&child| nodes[child].clone()).collect();
+                    let node = Expression { operator: key.0.clone(), operands };
+                    nodes.push(Rc::new(RefCell::new(node)));
+                    subtree_ids.insert(key, nodes.len() - 1);
+
+                    nodes.len() - 1
+                },
+            };
+            id_stack.push(id);
+        }
+
+        let root = nodes[id_stack.pop().expect("tried to pop value off empty stack")].clone();
+        drop(nodes);
+        drop(subtree_ids);
+
+        let root = Rc::try_unwrap(root)
+            .expect("root of expression is shared")
+            .into_inner();
+
+        (root, deduplicated)
+    }
+
+    // like eval_with, but remembers the value of every node so nodes shared by deduplicate are
+    // only evaluated once
+    pub fn eval_deduplicated(&self, vars: &HashMap<String, f64>) -> f64
+    {
+        if binds_variable(self)
+        {
+            return self.eval_with(vars);
+        }
+
+        let mut values: HashMap<*const RefCell<Expression>, f64> = HashMap::new();
+        let mut node_stack: Vec<(Rc<RefCell<Expression>>, bool)> = self
+            .operands
+            .iter()
+            .rev()
+            .map(|node| (node.clone(), false))
+            .collect();
+
+        while let Some((node, expanded)) = node_stack.pop()
+        {
+            if values.contains_key(&Rc::as_ptr(&node))
+            {
+                continue;
+            }
+
+            let tree_node = node.borrow();
+            if binds_variable(&tree_node)
+            {
+                values.insert(Rc::as_ptr(&node), tree_node.eval_with(vars));
+            }
+            else if expanded || tree_node.operands.is_empty()
+            {
+                let operand_values: Vec<f64> = tree_node
+                    .operands
+                    .iter()
+                    .map(|operand| values[&Rc::as_ptr(operand)])
+                    .collect();
+
+                values.insert(
+                    Rc::as_ptr(&node),
+                    eval_node(&tree_node, &operand_values, vars),
+                );
+            }
+            else
+            {
+                node_stack.push((node.clone(), true));
+                node_stack.extend(
+                    tree_node
+                        .operands
+                        .iter()
+                        .rev()
+                        .map(|node| (node.clone(), false)),
+                );
+            }
+        }
+
+        let operand_values: Vec<f64> = self
+            .operands
+            .iter()
+            .map(|operand| values[&Rc::as_ptr(operand)])
+            .collect();
+
+        eval_node(self, &operand_values, vars)
+    }
+}
+
+// sums and products give their bound variable a new value for every term, so they're evaluated as
+// a whole instead of node by node
+fn binds_variable(node: &Expression) -> bool
+{
+    node.operator
+        .get_function()
+        .is_some_and(|func| func.binds_variable())
+}
+
+// value of a single node, given the values of its operands
+fn eval_node(node: &Expression, operand_values: &[f64], vars: &HashMap<String, f64>) -> f64
+{
+    match &node.operator
+    {
+        Token::Number { .. } => node
+            .operator
+            .get_number()
+            .expect("error getting number from token"),
+        Token::Variable { name } => *vars
+            .get(name)
+            .unwrap_or_else(|| panic!("no value given for variable: {}", name)),
+        Token::Operator { .. } | Token::Function { .. } | Token::Array { .. } =>
+        {
+            Expression::eval_operation(&node.operator, operand_values)
+        },
+        Token::LSep | Token::RSep | Token::LBracket | Token::RBracket | Token::Comma =>
+        {
+            panic!("attempted to eval invalid token: {:?}", node.operator)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_deduplicate()
+    {
+        let tree = Expression::parse("(a+b)*(a+b)/(a+b)");
+        let (dag, deduplicated) = tree.deduplicate();
+
+        // 11 nodes in the tree, but only a, b, +, * and / are different
+        assert_eq!(deduplicated, 6);
+        assert!(Rc::ptr_eq(
+            &dag.operands[1],
+            &dag.operands[0].borrow().operands[0]
+        ));
+        assert_eq!(
+            dag.expression_string(),
+            "( ( ( a + b ) * ( a + b ) ) / ( a + b ) ) "
+        );
+
+        let vars = HashMap::from([("a".to_string(), 2.), ("b".to_string(), 3.)]);
+        assert_eq!(dag.eval_deduplicated(&vars), 5.);
+        assert_eq!(dag.eval_with(&vars), 5.);
+
+        let (dag, deduplicated) = Expression::parse("2*3+4").deduplicate();
+        assert_eq!(deduplicated, 0);
+        assert_eq!(dag.simplify(), 10.);
+    }
+
+    #[test]
+    fn test_simplify_deduplicated()
+    {
+        let (dag, _) = Expression::parse("(2^3-1)*(2^3-1)+(2^3-1)").deduplicate();
+
+        assert_eq!(dag.eval_deduplicated(&HashMap::new()), 56.);
+        assert_eq!(dag.simplify(), 56.);
+    }
+
+    #[test]
+    fn test_eval_deduplicated_sum()
+    {
+        let (dag, _) = Expression::parse("sum(k, 1, n, k^2) + sum(k, 1, n, k^2) * k").deduplicate();
+        let vars = HashMap::from([("n".to_string(), 3.), ("k".to_string(), 2.)]);
+
+        assert_eq!(dag.eval_deduplicated(&vars), 42.);
+        assert_eq!(dag.eval_with(&vars), 42.);
+
+        let (dag, _) = Expression::parse("prod(k, 1, 4, k + 1)").deduplicate();
+        assert_eq!(dag.eval_deduplicated(&HashMap::new()), 120.);
+    }
+}
@@ -0,0 +1,983 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::f64::consts::{LN_2, LOG10_E, TAU};
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::numeric::{array_description, Numeric};
+use crate::rational::{exact_factorial, exact_root};
+use crate::solver::Expression;
+use crate::tokens::*;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Rounding
+{
+    // to the closest value, and halfway cases to the one with an even last digit
+    HalfEven,
+    // to the closest value, and halfway cases away from zero
+    HalfUp,
+    // to the closest value, and halfway cases towards zero
+    HalfDown,
+    // away from zero
+    Up,
+    // towards zero
+    Down,
+    // towards positive infinity
+    Ceiling,
+    // towards negative infinity
+    Floor,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DecimalContext
+{
+    // number of significant digits every result is rounded to
+    pub precision: u32,
+    pub rounding: Rounding,
+}
+
+impl Default for DecimalContext
+{
+    fn default() -> Self
+    {
+        DecimalContext { precision: 50, rounding: Rounding::HalfEven }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DecimalError
+{
+    DivisionByZero,
+    InvalidLiteral(String),
+    // the operation that has no real result, like "ln( -1 )"
+    Undefined(String),
+    // a statistic that needs more than the single value it was given, like "stdev( 2.5 )"
+    NotEnoughValues(String),
+    // a vector or matrix, like "[ 0.1, 0.2 ]". decimals are only ever plain numbers
+    Unsupported(String),
+    // a value whose exponent is further from zero than MAX_EXPONENT, like "10 ^ 100000" or
+    // "exp( -100000 )"
+    OutOfRange(String),
+}
+
+// every operation works on the exact value of its operands, with all the zeros written out, so
+// values are kept within this many powers of ten of one
+const MAX_EXPONENT: i64 = 10_000;
+
+// digits * 10^exponent. trailing zeros are always removed from digits, so every value has one
+// representation. results of operations are rounded to the context of the left operand
+#[derive(Debug, Clone)]
+pub struct Decimal
+{
+    digits: BigInt,
+    exponent: i64,
+    context: DecimalContext,
+}
+
+impl PartialEq for Decimal
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.digits == other.digits && self.exponent == other.exponent
+    }
+}
+
+impl Decimal
+{
+    // reads literals like "12", "0.25", ".5" or "1.5e-3", rounded to the context
+    pub fn parse(literal: &str, context: DecimalContext) -> Result<Decimal, DecimalError>
+    {
+        let invalid = || DecimalError::InvalidLiteral(literal.to_string());
+
+        let (mantissa, exponent) = match literal.split_once(['e', 'E'])
+        {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse().map_err(|_| invalid())?),
+            None => (literal, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if fraction.starts_with(['+', '-'])
+        {
+            return Err(invalid());
+        }
+        let digits: BigInt = format!("{}{}", whole, fraction)
+            .parse()
+            .map_err(|_| invalid())?;
+
+        let exponent = exponent - fraction.len() as i64;
+        let adjusted = exponent.saturating_add(digits.to_string().len() as i64 - 1);
+        if !digits.is_zero() && adjusted.abs() > MAX_EXPONENT
+        {
+            return Err(DecimalError::OutOfRange(literal.to_string()));
+        }
+
+        Ok(round(&to_rational(digits, exponent), context))
+    }
+
+    pub fn context(&self) -> DecimalContext
+    {
+        self.context
+    }
+
+    pub fn with_context(&self, context: DecimalContext) -> Decimal
+    {
+        round(&self.to_rational(), context)
+    }
+
+    pub fn to_rational(&self) -> BigRational
+    {
+        to_rational(self.digits.clone(), self.exponent)
+    }
+
+    fn is_zero(&self) -> bool
+    {
+        self.digits.is_zero()
+    }
+
+    // exponent of the first digit
+    fn adjusted_exponent(&self) -> i64
+    {
+        self.exponent + self.digits.abs().to_string().len() as i64 - 1
+    }
+
+    // roughly log10 of the absolute value, from its first 64 bits
+    fn log10(&self) -> f64
+    {
+        let digits = self.digits.abs();
+        let shift = digits.bits().saturating_sub(64);
+        let leading = ToPrimitive::to_f64(&(digits >> shift)).unwrap_or(f64::NAN);
+
+        leading.log10() + shift as f64 * 2f64.log10() + self.exponent as f64
+    }
+
+    // the value, unless it's out of the range of exponents decimals are kept in
+    fn checked(self, description: impl FnOnce() -> String) -> Result<Decimal, DecimalError>
+    {
+        match self.adjusted_exponent().abs() > MAX_EXPONENT
+        {
+            true => Err(DecimalError::OutOfRange(description())),
+            false => Ok(self),
+        }
+    }
+
+    fn power(&self, exponent: &Decimal) -> Result<Decimal, DecimalError>
+    {
+        let (base, power) = (self.to_rational(), exponent.to_rational());
+        let description = || format!("{} ^ {}", self, exponent);
+        let undefined = || DecimalError::Undefined(description());
+
+        if base.is_one()
+        {
+            return Ok(round(&base, self.context));
+        }
+        if base.is_zero()
+        {
+            return match power.is_positive()
+            {
+                true => Ok(round(&base, self.context)),
+                false => Err(DecimalError::DivisionByZero),
+            };
+        }
+        // checked before working anything out, since the powers of ten in between get as big
+        if (self.log10() * exponent.to_f64()).abs() > MAX_EXPONENT as f64
+        {
+            return Err(DecimalError::OutOfRange(description()));
+        }
+        // integer exponents, and roots that come out even like 0.25^0.5
+        let root = match power.denom().to_u32()
+        {
+            Some(1) => Some(base.clone()),
+            Some(degree) => exact_root(&base, degree),
+            None => None,
+        };
+        if let (Some(root), Some(times)) = (root, power.numer().to_i64())
+        {
+            return correctly_rounded(self.context, |digits| approx_int_pow(&root, times, digits))
+                .checked(description);
+        }
+
+        // a negative base only has a real power when the exponent is an integer
+        let negative = match base.is_negative()
+        {
+            true if power.is_integer() => power.numer().is_odd(),
+            true => return Err(undefined()),
+            false => false,
+        };
+        let result = correctly_rounded(self.context, |digits| {
+            approx_pow(&base.abs(), &power, digits)
+        });
+
+        match negative
+        {
+            true => result.negate(),
+            false => result,
+        }
+        .checked(description)
+    }
+
+    fn function(&self, func: Function) -> Result<Decimal, DecimalError>
+    {
+        let val = self.to_rational();
+        let description = || format!("{}( {} )", func.name(), self);
+        let undefined = || DecimalError::Undefined(description());
+        let exact = |val: BigRational| round(&val, self.context).checked(description);
+
+        match func
+        {
+            Function::Sqrt if val.is_negative() => Err(undefined()),
+            Function::Sqrt => match exact_root(&val, 2)
+            {
+                Some(root) => exact(root),
+                None => Ok(correctly_rounded(self.context, |digits| {
+                    approx_sqrt(&val, digits)
+                })),
+            },
+            Function::Ln if !val.is_positive() => Err(undefined()),
+            Function::Ln if val.is_one() => exact(BigRational::zero()),
+            Function::Exp | Function::Cos if val.is_zero() => exact(BigRational::one()),
+            Function::Exp if (self.to_f64() * LOG10_E).abs() > MAX_EXPONENT as f64 =>
+            {
+                Err(DecimalError::OutOfRange(description()))
+            },
+            Function::Sin | Function::Tan if val.is_zero() => exact(BigRational::zero()),
+            Function::Factorial => match exact_factorial(&val)
+            {
+                Ok(Some(val)) => exact(val),
+                Ok(None) => Err(undefined()),
+                Err(_) => Err(DecimalError::OutOfRange(description())),
+            },
+            Function::Negate => exact(-val),
+            Function::Det => exact(val),
+            Function::Inverse if val.is_zero() => Err(DecimalError::DivisionByZero),
+            Function::Inverse => exact(val.recip()),
+            Function::Aggregate(aggregate) if aggregate.min_len() > 1 =>
+            {
+                Err(DecimalError::NotEnoughValues(description()))
+            },
+            Function::Aggregate(_) => exact(val),
+            Function::Ln => Ok(correctly_rounded(self.context, |digits| {
+                approx_ln(&val, digits)
+            })),
+            Function::Exp => Ok(correctly_rounded(self.context, |digits| {
+                approx_exp(&val, digits)
+            })),
+            Function::Sin | Function::Cos | Function::Tan =>
+            {
+                Ok(correctly_rounded(self.context, |digits| {
+                    approx_trig(func, &val, digits)
+                }))
+            },
+            Function::Sum | Function::Product =>
+            {
+                unreachable!("sums are evaluated with their bound variable")
+            },
+        }
+    }
+
+    fn negate(self) -> Decimal
+    {
+        Decimal { digits: -self.digits, ..self }
+    }
+}
+
+impl Numeric for Decimal
+{
+    type Error = DecimalError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        Decimal::parse(literal, DecimalContext::default())
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        round(&(self.to_rational() + other.to_rational()), self.context)
+            .checked(|| format!("{} + {}", self, other))
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        round(&(self.to_rational() - other.to_rational()), self.context)
+            .checked(|| format!("{} - {}", self, other))
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        round(&(self.to_rational() * other.to_rational()), self.context)
+            .checked(|| format!("{} * {}", self, other))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        match other.is_zero()
+        {
+            true => Err(DecimalError::DivisionByZero),
+            false => round(&(self.to_rational() / other.to_rational()), self.context)
+                .checked(|| format!("{} / {}", self, other)),
+        }
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        match other.is_zero()
+        {
+            true => Err(DecimalError::DivisionByZero),
+            false => round(
+                &(self.to_rational() / other.to_rational()).floor(),
+                self.context,
+            )
+            .checked(|| format!("{} // {}", self, other)),
+        }
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let (val1, val2) = (self.to_rational(), other.to_rational());
+
+        match other.is_zero()
+        {
+            true => Err(DecimalError::DivisionByZero),
+            false => Ok(round(&(&val1 - &val2 * (&val1 / &val2).floor()), self.context)),
+        }
+    }
+
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.power(other)
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        self.function(func)
+    }
+
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Err(DecimalError::Unsupported(array_description(&elements)))
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(DecimalError::Undefined(description))
+    }
+}
+
+// plain notation like "0.3" or "1024", unless that would need zeros the precision doesn't have.
+// then scientific notation like "1.5e60"
+impl fmt::Display for Decimal
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let sign = if self.digits.is_negative() { "-" } else { "" };
+        let digits = self.digits.abs().to_string();
+        // exponent of the first digit
+        let adjusted = self.exponent + digits.len() as i64 - 1;
+
+        if self.exponent >= 0 && adjusted < self.context.precision as i64
+        {
+            write!(
+                f,
+                "{}{}{}",
+                sign,
+                digits,
+                "0".repeat(self.exponent as usize)
+            )
+        }
+        else if self.exponent < 0 && adjusted >= 0
+        {
+            let (whole, fraction) = digits.split_at(adjusted as usize + 1);
+            write!(f, "{}{}.{}", sign, whole, fraction)
+        }
+        else if self.exponent < 0 && adjusted >= -6
+        {
+            let zeros = "0".repeat((-adjusted - 1) as usize);
+            write!(f, "{}0.{}{}", sign, zeros, digits)
+        }
+        else
+        {
+            let (first, rest) = digits.split_at(1);
+            let point = if rest.is_empty() { "" } else { "." };
+            write!(f, "{}{}{}{}e{}", sign, first, point, rest, adjusted)
+        }
+    }
+}
+
+fn to_rational(digits: BigInt, exponent: i64) -> BigRational
+{
+    let scale = pow10(exponent.unsigned_abs());
+
+    match exponent >= 0
+    {
+        true => BigRational::from_integer(digits * scale),
+        false => BigRational::new(digits, scale),
+    }
+}
+
+fn pow10(exponent: u64) -> BigInt
+{
+    num_traits::pow(BigInt::from(10), exponent as usize)
+}
+
+// the decimal with the context's precision next to `val` in the direction of its rounding mode
+fn round(val: &BigRational, context: DecimalContext) -> Decimal
+{
+    if val.is_zero()
+    {
+        return Decimal { digits: BigInt::zero(), exponent: 0, context };
+    }
+
+    let precision = context.precision.max(1) as i64;
+    let (numer, denom) = (val.numer().abs(), val.denom().clone());
+    let (lowest, highest) = (pow10(precision as u64 - 1), pow10(precision as u64));
+
+    // guess the exponent from the lengths of numerator and denominator, which is off by at most
+    // one, then find the one that leaves exactly `precision` digits before the point
+    let digit_estimate = |val: &BigInt| (val.bits() as f64 * 2f64.log10()) as i64;
+    let mut exponent = digit_estimate(&numer) - digit_estimate(&denom) - precision;
+    let (quotient, remainder, divisor) = loop
+    {
+        let (numer, denom) = match exponent >= 0
+        {
+            true => (numer.clone(), &denom * pow10(exponent as u64)),
+            false => (&numer * pow10(exponent.unsigned_abs()), denom.clone()),
+        };
+        let (quotient, remainder) = numer.div_rem(&denom);
+
+        if quotient >= highest
+        {
+            exponent += 1;
+        }
+        else if quotient < lowest
+        {
+            exponent -= 1;
+        }
+        else
+        {
+            break (quotient, remainder, denom);
+        }
+    };
+
+    let negative = val.is_negative();
+    let inexact = !remainder.is_zero();
+    let half = (remainder * 2u32).cmp(&divisor);
+    let away_from_zero = match context.rounding
+    {
+        Rounding::HalfEven =>
+        {
+            half == Ordering::Greater || (half == Ordering::Equal && quotient.is_odd())
+        },
+        Rounding::HalfUp => half != Ordering::Less,
+        Rounding::HalfDown => half == Ordering::Greater,
+        Rounding::Up => inexact,
+        Rounding::Down => false,
+        Rounding::Ceiling => inexact && !negative,
+        Rounding::Floor => inexact && negative,
+    };
+
+    let mut digits = match away_from_zero
+    {
+        true => quotient + 1,
+        false => quotient,
+    };
+    while (&digits % 10u32).is_zero()
+    {
+        digits /= 10u32;
+        exponent += 1;
+    }
+
+    Decimal {
+        digits: if negative { -digits } else { digits },
+        exponent,
+        context,
+    }
+}
+
+// rounds an irrational result. `approximate` gives a value and a bound on its absolute error when
+// computed with that many digits after the point. digits get added until everything within the
+// error rounds to the same decimal, which then has to be the correctly rounded result
+fn correctly_rounded(
+    context: DecimalContext,
+    approximate: impl Fn(u64) -> (BigRational, BigRational),
+) -> Decimal
+{
+    let mut guard_digits = 10;
+
+    loop
+    {
+        let (val, error) = approximate(context.precision as u64 + guard_digits);
+        let lower = round(&(&val - &error), context);
+        if lower == round(&(&val + &error), context)
+        {
+            return lower;
+        }
+
+        guard_digits *= 2;
+    }
+}
+
+// the approximations below work on integers scaled by `one` = 10^digits and count their error in
+// units of the last digit
+
+fn to_fixed(val: &BigRational, one: &BigInt) -> BigInt
+{
+    val.numer() * one / val.denom()
+}
+
+fn from_fixed(val: BigInt, error: BigInt, one: &BigInt) -> (BigRational, BigRational)
+{
+    (
+        BigRational::new(val, one.clone()),
+        BigRational::new(error, one.clone()),
+    )
+}
+
+fn digit_count(val: i64) -> u64
+{
+    val.unsigned_abs().to_string().len() as u64
+}
+
+// atan(z) or atanh(z) from their series, for |z| well below 1
+fn arctan_series(z: &BigRational, one: &BigInt, hyperbolic: bool) -> (BigInt, BigInt)
+{
+    let z_squared = to_fixed(&(z * z), one);
+    let mut term = to_fixed(z, one);
+    let mut sum = term.clone();
+    let mut k = 1u32;
+
+    loop
+    {
+        term = &term * &z_squared / one;
+        if term.is_zero()
+        {
+            break;
+        }
+
+        let next = &term / (2 * k + 1);
+        match hyperbolic || k.is_multiple_of(2)
+        {
+            true => sum += next,
+            false => sum -= next,
+        }
+        k += 1;
+    }
+
+    (sum, BigInt::from(3 * (k + 1)))
+}
+
+fn ln2(one: &BigInt) -> (BigInt, BigInt)
+{
+    // ln(2) = 2 atanh(1/3)
+    let (val, error) = arctan_series(&BigRational::new(1.into(), 3.into()), one, true);
+    (val * 2, error * 2)
+}
+
+fn pi(one: &BigInt) -> (BigInt, BigInt)
+{
+    // machin's formula, pi = 16 atan(1/5) - 4 atan(1/239)
+    let (atan5, error5) = arctan_series(&BigRational::new(1.into(), 5.into()), one, false);
+    let (atan239, error239) = arctan_series(&BigRational::new(1.into(), 239.into()), one, false);
+
+    (atan5 * 16 - atan239 * 4, error5 * 16 + error239 * 4)
+}
+
+fn approx_sqrt(val: &BigRational, digits: u64) -> (BigRational, BigRational)
+{
+    let one = pow10(digits);
+    let root = to_fixed(val, &(&one * &one)).sqrt();
+
+    from_fixed(root, BigInt::from(2), &one)
+}
+
+fn approx_ln(val: &BigRational, digits: u64) -> (BigRational, BigRational)
+{
+    // val = m * 2^e with m between 2/3 and 4/3, where the series for ln(m) converges quickly
+    let mut e = val.numer().bits() as i64 - val.denom().bits() as i64;
+    let power =
+        BigRational::from_integer(num_traits::pow(BigInt::from(2), e.unsigned_abs() as usize));
+    let mut m = match e >= 0
+    {
+        true => val / power,
+        false => val * power,
+    };
+    while m > BigRational::new(4.into(), 3.into())
+    {
+        m /= BigInt::from(2);
+        e += 1;
+    }
+    while m < BigRational::new(2.into(), 3.into())
+    {
+        m *= BigInt::from(2);
+        e -= 1;
+    }
+
+    // ln(m) = 2 atanh((m - 1) / (m + 1))
+    let one = pow10(digits + digit_count(e) + 1);
+    let z = (&m - BigInt::one()) / (&m + BigInt::one());
+    let (ln_m, ln_m_error) = arctan_series(&z, &one, true);
+    let (ln2, ln2_error) = ln2(&one);
+
+    from_fixed(
+        ln_m * 2 + &ln2 * e,
+        ln_m_error * 2 + ln2_error * e.abs() + 1,
+        &one,
+    )
+}
+
+fn approx_exp(val: &BigRational, digits: u64) -> (BigRational, BigRational)
+{
+    // val = n ln(2) + r with |r| <= ln(2) / 2, so exp(val) = 2^n exp(r)
+    let n = (ToPrimitive::to_f64(val).unwrap_or(0.) / LN_2).round() as i64;
+    let one = pow10(digits + digit_count(n) + 2);
+    let (ln2, ln2_error) = ln2(&one);
+    let r = to_fixed(val, &one) - &ln2 * n;
+    let r_error = ln2_error * n.abs() + 1;
+
+    let mut term = one.clone();
+    let mut sum = one.clone();
+    let mut k = 1u32;
+    loop
+    {
+        term = &term * &r / (&one * k);
+        if term.is_zero()
+        {
+            break;
+        }
+
+        sum += &term;
+        k += 1;
+    }
+    // exp(r) is below 2, so being off by x in r is off by at most 2x in the result
+    let error = BigInt::from(2 * (k + 1)) + r_error * 2;
+
+    let scale =
+        BigRational::from_integer(num_traits::pow(BigInt::from(2), n.unsigned_abs() as usize));
+    let (val, error) = from_fixed(sum, error, &one);
+    match n >= 0
+    {
+        true => (val * &scale, error * &scale),
+        false => (val / &scale, error / &scale),
+    }
+}
+
+// base^power by repeated squaring, with every step rounded to `digits` significant digits so the
+// numbers stay small however big the exact result would be
+fn approx_int_pow(base: &BigRational, power: i64, digits: u64) -> (BigRational, BigRational)
+{
+    let context = DecimalContext { precision: digits as u32, rounding: Rounding::HalfEven };
+    // each rounding is off by at most a relative 10^(1 - digits) / 2, which gets multiplied by
+    // the number of times the rounded value goes into the result. this adds those up
+    let mut weight = 0u64;
+    let mut rounded = |val: BigRational, times: u64| {
+        let result = round(&val, context).to_rational();
+        if result != val
+        {
+            weight = weight.saturating_add(times);
+        }
+        result
+    };
+
+    let mut result = BigRational::one();
+    let mut square = base.clone();
+    let mut remaining = power.unsigned_abs();
+    while remaining > 0
+    {
+        if remaining % 2 == 1
+        {
+            result = rounded(&result * &square, 1);
+        }
+        remaining /= 2;
+        if remaining > 0
+        {
+            square = rounded(&square * &square, remaining);
+        }
+    }
+    if power < 0
+    {
+        result = rounded(result.recip(), 1);
+    }
+
+    // while the relative errors add up to at most a quarter, the result is off by less than four
+    // times their sum. an exact result has no error at all, so it doesn't look like it's on the
+    // edge of a rounding step
+    let (bound, scale) = (BigInt::from(weight) * 2u32, pow10(digits - 1));
+    let error = match bound <= scale
+    {
+        true => result.abs() * bound / scale,
+        false => result.abs() + BigInt::one(),
+    };
+    (result, error)
+}
+
+// base^exponent = exp(exponent ln(base)) for positive bases
+fn approx_pow(base: &BigRational, exponent: &BigRational, digits: u64)
+    -> (BigRational, BigRational)
+{
+    let extra = digit_count(exponent.to_integer().to_i64().unwrap_or(i64::MAX)) + 2;
+    let (ln, ln_error) = approx_ln(base, digits + extra);
+    let argument_error = ln_error * exponent.abs();
+
+    let (val, error) = approx_exp(&(ln * exponent), digits + extra);
+    // the argument being off by x scales the result by up to exp(x), which is below 1 + 2x
+    let propagated = val.abs() * argument_error * BigInt::from(2);
+
+    (val, error + propagated)
+}
+
+fn approx_trig(func: Function, val: &BigRational, digits: u64) -> (BigRational, BigRational)
+{
+    if func == Function::Tan
+    {
+        let (sin, sin_error) = approx_trig(Function::Sin, val, digits + 2);
+        let (cos, cos_error) = approx_trig(Function::Cos, val, digits + 2);
+        if cos.abs() <= cos_error
+        {
+            // too close to a pole to say anything yet
+            return (
+                BigRational::zero(),
+                BigRational::from_integer(pow10(digits)),
+            );
+        }
+
+        let tan = &sin / &cos;
+        let error = (sin_error + tan.abs() * &cos_error) / (cos.abs() - cos_error);
+        return (tan, error);
+    }
+
+    // val = 2 pi k + r with |r| <= pi
+    let k = (ToPrimitive::to_f64(val).unwrap_or(0.) / TAU).round() as i64;
+    let one = pow10(digits + digit_count(k) + 2);
+    let (pi, pi_error) = pi(&one);
+    let r = to_fixed(val, &one) - &pi * (2 * k);
+    let r_error = pi_error * (2 * k).abs() + 1;
+
+    let r_squared = &r * &r / &one;
+    let (mut term, mut i) = match func
+    {
+        Function::Sin => (r, 1u32),
+        _ => (one.clone(), 0u32),
+    };
+    let mut sum = term.clone();
+    let mut count = 0u32;
+    loop
+    {
+        term = -(&term * &r_squared) / (&one * (i + 1) * (i + 2));
+        if term.is_zero()
+        {
+            break;
+        }
+
+        sum += &term;
+        i += 2;
+        count += 1;
+    }
+    // the terms add up to at most cosh(pi) < 12 times the error in r
+    let error = BigInt::from(16 * (count + 1)) + r_error * 24;
+
+    from_fixed(sum, error, &one)
+}
+
+impl Expression
+{
+    pub fn eval_decimal(&self, context: DecimalContext) -> Result<Decimal, DecimalError>
+    {
+        self.eval_decimal_with(&HashMap::new(), context)
+    }
+
+    // variables are rounded to the context first
+    pub fn eval_decimal_with(
+        &self,
+        vars: &HashMap<String, Decimal>,
+        context: DecimalContext,
+    ) -> Result<Decimal, DecimalError>
+    {
+        let vars = vars
+            .iter()
+            .map(|(name, val)| (name.clone(), val.with_context(context)))
+            .collect();
+
+        Self::eval_helper(self, &vars, &|literal| Decimal::parse(literal, context))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn eval(expr: &str) -> String
+    {
+        Expression::parse(expr)
+            .eval_decimal(DecimalContext::default())
+            .unwrap()
+            .to_string()
+    }
+
+    fn eval_rounded(expr: &str, precision: u32, rounding: Rounding) -> String
+    {
+        Expression::parse(expr)
+            .eval_decimal(DecimalContext { precision, rounding })
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_eval_decimal()
+    {
+        assert_eq!(eval("0.1 + 0.2"), "0.3");
+        assert_eq!(
+            Expression::parse("0.1 + 0.2").eval_decimal(DecimalContext::default()),
+            Decimal::parse("0.3", DecimalContext::default())
+        );
+        assert_eq!(eval("1/3"), format!("0.{}", "3".repeat(50)));
+        assert_eq!(eval("1.1^10 * 2^(0-3)"), "0.3242178075125");
+        assert_eq!(eval("2^10 - 1000.5"), "23.5");
+        assert_eq!(
+            eval("2^200"),
+            "1.6069380442589902755419620923411626025222029937828e60"
+        );
+        assert_eq!(eval("0.25^0.5 + sum(k, 1, 4, 0.1)"), "0.9");
+        assert_eq!(eval("1/8000000"), "1.25e-7");
+
+        let vars = HashMap::from([(
+            "x".to_string(),
+            Decimal::parse("0.1", DecimalContext::default()).unwrap(),
+        )]);
+        let result =
+            Expression::parse("3x - 0.3").eval_decimal_with(&vars, DecimalContext::default());
+        assert_eq!(result.unwrap().to_string(), "0");
+    }
+
+    #[test]
+    fn test_rounding()
+    {
+        assert_eq!(eval_rounded("2/3", 3, Rounding::HalfEven), "0.667");
+        assert_eq!(eval_rounded("2/3", 3, Rounding::Down), "0.666");
+        assert_eq!(eval_rounded("(0-2)/3", 3, Rounding::Floor), "-0.667");
+        assert_eq!(eval_rounded("(0-2)/3", 3, Rounding::Ceiling), "-0.666");
+        assert_eq!(eval_rounded("0.1/3", 2, Rounding::Up), "0.034");
+
+        assert_eq!(eval_rounded("0.125", 2, Rounding::HalfEven), "0.12");
+        assert_eq!(eval_rounded("0.135", 2, Rounding::HalfEven), "0.14");
+        assert_eq!(eval_rounded("0.125", 2, Rounding::HalfUp), "0.13");
+        assert_eq!(eval_rounded("0.125", 2, Rounding::HalfDown), "0.12");
+
+        // rounding up can carry into a new digit
+        assert_eq!(eval_rounded("99.96", 3, Rounding::HalfEven), "100");
+        assert_eq!(eval_rounded("123456", 3, Rounding::HalfEven), "1.23e5");
+    }
+
+    #[test]
+    fn test_correctly_rounded()
+    {
+        assert_eq!(
+            eval("sqrt(2)"),
+            "1.4142135623730950488016887242096980785696718753769"
+        );
+        assert_eq!(
+            eval("2^0.5"),
+            "1.4142135623730950488016887242096980785696718753769"
+        );
+        assert_eq!(
+            eval("exp(1)"),
+            "2.7182818284590452353602874713526624977572470937"
+        );
+        assert_eq!(
+            eval("exp(0-50)"),
+            "1.9287498479639177830173428165270125747528326512303e-22"
+        );
+        assert_eq!(
+            eval("ln(2)"),
+            "0.69314718055994530941723212145817656807550013436026"
+        );
+        assert_eq!(
+            eval("ln(10^(0-30))"),
+            "-69.077552789821370520539743640530926228033044658863"
+        );
+        assert_eq!(
+            eval("1.5^2.5"),
+            "2.7556759606310753604719445840441278159616909157388"
+        );
+        assert_eq!(
+            eval("sin(1)"),
+            "0.84147098480789650665250232163029899962256306079837"
+        );
+        assert_eq!(
+            eval("cos(100)"),
+            "0.86231887228768393410193851395084253551008400853551"
+        );
+        assert_eq!(
+            eval("tan(1.5)"),
+            "14.101419947171719387646083651987756445659543577236"
+        );
+
+        // whole number powers are rounded while squaring, however many digits the exact result has
+        assert_eq!(
+            eval("1.0001^10000000"),
+            "1.8739958581859057607500043655943586107816144187442e434"
+        );
+        assert_eq!(
+            eval("0.999^(0-123456)"),
+            "4.3962823099107213515354771955056355832878069275015e53"
+        );
+        assert_eq!(
+            eval_rounded("(0-1.5)^41", 50, Rounding::Down),
+            "-16585998.48141001821340978494845330715179443359375"
+        );
+
+        assert_eq!(eval_rounded("sqrt(2)", 5, Rounding::Up), "1.4143");
+        assert_eq!(eval_rounded("exp(1)", 5, Rounding::Down), "2.7182");
+        assert_eq!(
+            eval_rounded("(0-2)^3 - 0.5^(0-1)", 5, Rounding::HalfEven),
+            "-10"
+        );
+    }
+
+    #[test]
+    fn test_decimal_errors()
+    {
+        let eval = |expr| Expression::parse(expr).eval_decimal(DecimalContext::default());
+
+        assert_eq!(eval("1/(0.5 - 0.5)"), Err(DecimalError::DivisionByZero));
+        assert_eq!(eval("0^(0-1)"), Err(DecimalError::DivisionByZero));
+        assert_eq!(
+            eval("ln(0-1)"),
+            Err(DecimalError::Undefined("ln( -1 )".to_string()))
+        );
+        assert_eq!(
+            eval("sqrt(0-4)"),
+            Err(DecimalError::Undefined("sqrt( -4 )".to_string()))
+        );
+        assert_eq!(
+            eval("(0-8)^0.5"),
+            Err(DecimalError::Undefined("-8 ^ 0.5".to_string()))
+        );
+        assert_eq!(
+            Decimal::parse("1.2.3", DecimalContext::default()),
+            Err(DecimalError::InvalidLiteral("1.2.3".to_string()))
+        );
+
+        // values far from one are given up on instead of writing out all their zeros
+        let out_of_range =
+            |description: &str| Err(DecimalError::OutOfRange(description.to_string()));
+        assert_eq!(eval("10^1000000"), out_of_range("10 ^ 1000000"));
+        assert_eq!(eval("0.1^100000"), out_of_range("0.1 ^ 100000"));
+        assert_eq!(eval("exp(100000000)"), out_of_range("exp( 100000000 )"));
+        assert_eq!(eval("10^9000 * 10^9000"), out_of_range("1e9000 * 1e9000"));
+        assert_eq!(
+            Decimal::parse("1e1000000", DecimalContext::default()),
+            out_of_range("1e1000000")
+        );
+        assert_eq!(
+            eval("10^9999 / 10^9999"),
+            Decimal::parse("1", DecimalContext::default())
+        );
+    }
+}
@@ -0,0 +1,538 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::solver::Expression;
+use crate::tokens::*;
+
+impl Expression
+{
+    // derivative with respect to `var`, simplified so that "0*x + 1*3" comes out as "3"
+    pub fn derivative(&self, var: &str) -> Expression
+    {
+        self.derivative_unsimplified(var).simplify_symbolic().0
+    }
+
+    // builds the derivative of every subtree bottom up, keeping each subtree alongside its
+    // derivative since the product, quotient and chain rules need both
+    pub fn derivative_unsimplified(&self, var: &str) -> Expression
+    {
+        let mut stack: Vec<(Expression, Expression)> = Vec::new();
+
+        // sums are differentiated term by term, or as a whole when their bounds aren't numbers
+        let tokens = match self.has_bound_variables()
+        {
+            true => self.expand_bound_variables().to_postfix_tokens(),
+            false => self.to_postfix_tokens(),
+        };
+
+        for token in tokens
+        {
+            let node = match token
+            {
+                Token::Number { .. } => (
+                    Expression { operator: token, operands: Vec::new() },
+                    Expression::number(0.),
+                ),
+                Token::Variable { ref name } =>
+                {
+                    let derivative = Expression::number(if name == var { 1. } else { 0. });
+
+                    (
+                        Expression { operator: token, operands: Vec::new() },
+                        derivative,
+                    )
+                },
+                Token::Operator { op } =>
+                {
+                    let (g, dg) = stack.pop().expect("tried to pop value off empty stack");
+                    let (f, df) = stack.pop().expect("tried to pop value off empty stack");
+                    let derivative = binary_derivative(op, &f, df, &g, dg, var);
+
+                    (Expression::binary(op, f, g), derivative)
+                },
+                Token::Function { func } if func.binds_variable() =>
+                {
+                    let operands = stack.split_off(stack.len() - 4);
+                    let [(k, _), (lower, _), (upper, _), (f, df)]: [(Expression, Expression); 4] =
+                        operands.try_into().ok().unwrap();
+                    let derivative = bound_derivative(func, [&k, &lower, &upper, &f], df, var);
+
+                    (bound(func, k, lower, upper, f), derivative)
+                },
+                Token::Function { func } =>
+                {
+                    let (f, df) = stack.pop().expect("tried to pop value off empty stack");
+                    let derivative = function_derivative(func, &f, df);
+
+                    (Expression::function(func, f), derivative)
+                },
+                // differentiated element by element
+                Token::Array { len } =>
+                {
+                    let (elements, derivatives) =
+                        stack.split_off(stack.len() - len).into_iter().unzip();
+
+                    (Expression::array(elements), Expression::array(derivatives))
+                },
+                Token::LSep | Token::RSep | Token::LBracket | Token::RBracket | Token::Comma =>
+                {
+                    panic!("unexpected token for postfix expression: {:?}", token)
+                },
+            };
+            stack.push(node);
+        }
+
+        assert!(stack.len() == 1);
+        stack.pop().unwrap().1
+    }
+}
+
+fn add(lhs: Expression, rhs: Expression) -> Expression
+{
+    Expression::binary(Operation::Addition, lhs, rhs)
+}
+
+fn sub(lhs: Expression, rhs: Expression) -> Expression
+{
+    Expression::binary(Operation::Subtraction, lhs, rhs)
+}
+
+fn mul(lhs: Expression, rhs: Expression) -> Expression
+{
+    Expression::binary(Operation::Multiplication, lhs, rhs)
+}
+
+fn div(lhs: Expression, rhs: Expression) -> Expression
+{
+    Expression::binary(Operation::Division, lhs, rhs)
+}
+
+fn pow(lhs: Expression, rhs: Expression) -> Expression
+{
+    Expression::binary(Operation::Exponentiation, lhs, rhs)
+}
+
+fn ln(arg: Expression) -> Expression
+{
+    Expression::function(Function::Ln, arg)
+}
+
+fn bound(
+    func: Function,
+    var: Expression,
+    lower: Expression,
+    upper: Expression,
+    body: Expression,
+) -> Expression
+{
+    Expression {
+        operator: Token::Function { func },
+        operands: [var, lower, upper, body]
+            .into_iter()
+            .map(|operand| Rc::new(RefCell::new(operand)))
+            .collect(),
+    }
+}
+
+// a sum or product whose bounds depend on other variables. `df` is the derivative of the body
+fn bound_derivative(
+    func: Function,
+    [k, lower, upper, f]: [&Expression; 4],
+    df: Expression,
+    var: &str,
+) -> Expression
+{
+    let depends_on_var = |bound: &Expression| bound.free_variables().iter().any(|name| name == var);
+
+    // the bounds only take whole numbers, so like factorials they have no slope
+    if depends_on_var(lower) || depends_on_var(upper)
+    {
+        return Expression::number(f64::NAN);
+    }
+    // and inside the sum, `var` is the bound variable rather than the one being differentiated
+    if k.operator.get_variable() == Some(var)
+    {
+        return Expression::number(0.);
+    }
+
+    let body = match func
+    {
+        // the product rule for every factor at once: each term is the factor's derivative times
+        // all the factors before and after it
+        Function::Product =>
+        {
+            let name = k.operator.get_variable().unwrap();
+            let (j, f_j) = renamed(f, name, [lower, upper]);
+            let factors = |from, to| bound(func, j.deep_clone(), from, to, f_j.deep_clone());
+
+            let before = factors(
+                lower.deep_clone(),
+                sub(k.deep_clone(), Expression::number(1.)),
+            );
+            let after = factors(
+                add(k.deep_clone(), Expression::number(1.)),
+                upper.deep_clone(),
+            );
+            mul(mul(before, df), after)
+        },
+        _ => df,
+    };
+
+    bound(
+        Function::Sum,
+        k.deep_clone(),
+        lower.deep_clone(),
+        upper.deep_clone(),
+        body,
+    )
+}
+
+// `f` with the variable `name` replaced by one that appears nowhere in it or in `avoid`
+fn renamed(f: &Expression, name: &str, avoid: [&Expression; 2]) -> (Expression, Expression)
+{
+    let tokens = f.to_postfix_tokens();
+    let used: Vec<Token> = [f, avoid[0], avoid[1]]
+        .iter()
+        .flat_map(|expr| expr.to_postfix_tokens())
+        .collect();
+
+    let mut fresh = name.repeat(2);
+    while used
+        .iter()
+        .any(|token| token.get_variable() == Some(fresh.as_str()))
+    {
+        fresh.push_str(name);
+    }
+
+    let tokens = tokens
+        .into_iter()
+        .map(|token| match token.get_variable()
+        {
+            Some(var) if var == name => Token::Variable { name: fresh.clone() },
+            _ => token,
+        })
+        .collect();
+
+    (
+        Expression::variable(&fresh),
+        Expression::from_postfix_tokens(tokens),
+    )
+}
+
+fn binary_derivative(
+    op: Operation,
+    f: &Expression,
+    df: Expression,
+    g: &Expression,
+    dg: Expression,
+    var: &str,
+) -> Expression
+{
+    match op
+    {
+        Operation::Addition => add(df, dg),
+        Operation::Subtraction => sub(df, dg),
+        // (fg)' = f'g + fg', and the same for the dot product of vectors
+        Operation::Multiplication | Operation::Dot => add(
+            Expression::binary(op, df, g.deep_clone()),
+            Expression::binary(op, f.deep_clone(), dg),
+        ),
+        // (f/g)' = (f'g - fg') / g^2
+        Operation::Division => div(
+            sub(mul(df, g.deep_clone()), mul(f.deep_clone(), dg)),
+            pow(g.deep_clone(), Expression::number(2.)),
+        ),
+        // f // g only jumps between whole numbers, so it's flat everywhere else
+        Operation::IntegerDivision => Expression::number(0.),
+        // f % g = f - g (f // g), where f // g is flat
+        Operation::Remainder => sub(
+            df,
+            mul(
+                dg,
+                Expression::binary(Operation::IntegerDivision, f.deep_clone(), g.deep_clone()),
+            ),
+        ),
+        Operation::Exponentiation =>
+        {
+            if !g.contains_variable(var)
+            {
+                // (f^c)' = c f^(c-1) f'
+                let exponent = sub(g.deep_clone(), Expression::number(1.));
+
+                mul(mul(g.deep_clone(), pow(f.deep_clone(), exponent)), df)
+            }
+            else if !f.contains_variable(var)
+            {
+                // (c^g)' = c^g ln(c) g'
+                mul(
+                    mul(pow(f.deep_clone(), g.deep_clone()), ln(f.deep_clone())),
+                    dg,
+                )
+            }
+            else
+            {
+                // (f^g)' = f^g (g' ln(f) + g f' / f)
+                let inner = add(
+                    mul(dg, ln(f.deep_clone())),
+                    div(mul(g.deep_clone(), df), f.deep_clone()),
+                );
+
+                mul(pow(f.deep_clone(), g.deep_clone()), inner)
+            }
+        },
+    }
+}
+
+// chain rule, f(g)' = f'(g) g'
+fn function_derivative(func: Function, f: &Expression, df: Expression) -> Expression
+{
+    // an argument that doesn't depend on var stays constant even where the function has no slope,
+    // like 3! or sqrt(0)
+    if df.number_value() == Some(0.)
+    {
+        return df;
+    }
+    let takes_arrays = matches!(
+        func,
+        Function::Det | Function::Inverse | Function::Aggregate(_)
+    );
+    if takes_arrays && f.has_arrays()
+    {
+        return array_derivative(func, f, df);
+    }
+
+    let outer = match func
+    {
+        Function::Sqrt => div(
+            Expression::number(1.),
+            mul(
+                Expression::number(2.),
+                Expression::function(Function::Sqrt, f.deep_clone()),
+            ),
+        ),
+        Function::Exp => Expression::function(Function::Exp, f.deep_clone()),
+        Function::Ln => div(Expression::number(1.), f.deep_clone()),
+        Function::Sin => Expression::function(Function::Cos, f.deep_clone()),
+        Function::Cos => sub(
+            Expression::number(0.),
+            Expression::function(Function::Sin, f.deep_clone()),
+        ),
+        Function::Tan => div(
+            Expression::number(1.),
+            pow(
+                Expression::function(Function::Cos, f.deep_clone()),
+                Expression::number(2.),
+            ),
+        ),
+        // only defined for whole numbers, so there's no slope
+        Function::Factorial => Expression::number(f64::NAN),
+        Function::Negate => Expression::number(-1.),
+        // a plain number is its own determinant
+        Function::Det => Expression::number(1.),
+        Function::Inverse => div(
+            Expression::number(-1.),
+            pow(f.deep_clone(), Expression::number(2.)),
+        ),
+        // not defined for a single value, like eval gives
+        Function::Aggregate(aggregate) if aggregate.min_len() > 1 => Expression::number(f64::NAN),
+        // and a list of one
+        Function::Aggregate(_) => Expression::number(1.),
+        Function::Sum | Function::Product =>
+        {
+            unreachable!("sums are differentiated by bound_derivative")
+        },
+    };
+
+    mul(outer, df)
+}
+
+// derivative of a function of a vector or matrix, or NaN where there's no rule for it in terms
+// of expressions
+fn array_derivative(func: Function, f: &Expression, df: Expression) -> Expression
+{
+    match func
+    {
+        // the determinant is linear in each row, so its derivative adds up the determinants with
+        // one row differentiated at a time
+        Function::Det => match (rows(f), rows(&df))
+        {
+            (Some(rows), Some(derivatives)) if rows.len() == derivatives.len() => (0..rows.len())
+                .map(|i| {
+                    let mut rows: Vec<Expression> =
+                        rows.iter().map(|row| row.deep_clone()).collect();
+                    rows[i] = derivatives[i].deep_clone();
+
+                    Expression::function(Function::Det, Expression::array(rows))
+                })
+                .reduce(add)
+                .unwrap_or_else(|| Expression::number(0.)),
+            _ => Expression::number(f64::NAN),
+        },
+        // (F^-1)' = -F^-1 F' F^-1
+        Function::Inverse =>
+        {
+            let inverse = || Expression::function(Function::Inverse, f.deep_clone());
+
+            Expression::function(Function::Negate, mul(mul(inverse(), df), inverse()))
+        },
+        // taking the sum or mean commutes with differentiating each entry
+        Function::Aggregate(Aggregate::Sum | Aggregate::Mean) => Expression::function(func, df),
+        // s' = s ((f - mean(f)) . f') / ((f - mean(f)) . (f - mean(f))), since (n - 1) s^2 is
+        // the sum of the squared deviations
+        Function::Aggregate(Aggregate::Stdev) =>
+        {
+            let deviations = || {
+                sub(
+                    f.deep_clone(),
+                    Expression::function(Function::Aggregate(Aggregate::Mean), f.deep_clone()),
+                )
+            };
+            let dot = |lhs, rhs| Expression::binary(Operation::Dot, lhs, rhs);
+
+            div(
+                mul(
+                    Expression::function(func, f.deep_clone()),
+                    dot(deviations(), df),
+                ),
+                dot(deviations(), deviations()),
+            )
+        },
+        // which entry the median, min or max picks depends on the values
+        _ => Expression::number(f64::NAN),
+    }
+}
+
+// the rows of a matrix written out like "[[1, x], [2, 3]]"
+fn rows(matrix: &Expression) -> Option<Vec<Expression>>
+{
+    let rows: Vec<Expression> = match matrix.operator
+    {
+        Token::Array { .. } => matrix
+            .operands
+            .iter()
+            .map(|row| row.borrow().deep_clone())
+            .collect(),
+        _ => return None,
+    };
+
+    rows.iter()
+        .all(|row| matches!(row.operator, Token::Array { .. }))
+        .then_some(rows)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::matrix::Array;
+
+    fn derivative(expr: &str) -> String
+    {
+        Expression::parse(expr).derivative("x").expression_string()
+    }
+
+    #[test]
+    fn test_derivative()
+    {
+        assert_eq!(derivative("3*x"), "3 ");
+        assert_eq!(derivative("x^2"), "( 2 * x ) ");
+        assert_eq!(derivative("x^3 + 2*x - y"), "( ( 3 * ( x ^ 2 ) ) + 2 ) ");
+        assert_eq!(derivative("y*x + y"), "y ");
+        assert_eq!(derivative("sin(x^2)"), "( cos( ( x ^ 2 ) ) * ( 2 * x ) ) ");
+        assert_eq!(derivative("ln(x)"), "( 1 / x ) ");
+        assert_eq!(derivative("2^x"), "( 0.6931471805599453 * ( 2 ^ x ) ) ");
+        assert_eq!(derivative("x % 3 + x // 2"), "1 ");
+        assert_eq!(derivative("x!"), "NaN ");
+        assert_eq!(derivative("stdev(x)"), "NaN ");
+        assert_eq!(
+            derivative("[x^2, 3] . [1, x]"),
+            "( ( [ ( 2 * x ) , 0 ] . [ 1 , x ] ) + ( [ ( x ^ 2 ) , 3 ] . [ 0 , 1 ] ) ) "
+        );
+        assert_eq!(derivative("3! * x + sqrt(0) * x"), "6 ");
+
+        // sums whose bounds aren't numbers are differentiated inside the sum
+        assert_eq!(
+            derivative("sum(k, 1, n, x^k) + sum(x, 1, n, x)"),
+            "sum( k , 1 , n , ( k * ( x ^ ( k - 1 ) ) ) ) "
+        );
+        assert_eq!(
+            derivative("prod(k, 1, n, x + k)"),
+            "sum( k , 1 , n , ( prod( kk , 1 , ( k - 1 ) , ( x + kk ) ) * prod( kk , ( k + 1 ) , \
+             n , ( x + kk ) ) ) ) "
+        );
+        assert_eq!(derivative("sum(k, 1, x, k)"), "NaN ");
+        assert_eq!(
+            Expression::parse("x^2").derivative("x").latex_string(),
+            "{2}\\cdot{x}"
+        );
+    }
+
+    #[test]
+    fn test_derivative_numerically()
+    {
+        let tree = Expression::parse("x^x / (1 + x*y) + sqrt(x)*exp(y) - tan(x)*cos(y*x)");
+        let derivative = tree.derivative("x");
+
+        for (x, y) in [(1., 2.), (0.5, -0.25), (2.5, 3.)]
+        {
+            let at = |x: f64| {
+                tree.eval_with(&HashMap::from([("x".to_string(), x), ("y".to_string(), y)]))
+            };
+            let h = 1e-6;
+            let expected = (at(x + h) - at(x - h)) / (2. * h);
+            let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+
+            assert!((derivative.eval_with(&vars) - expected).abs() < 1e-6);
+        }
+
+        // the product rule still works when one of the factors is zero
+        let tree = Expression::parse("prod(k, 1, n, x - k) + sum(k, 0, n, x^k / k!)");
+        let derivative = tree.derivative("x");
+        for (x, n) in [(1., 3.), (2.5, 4.), (0.5, 0.)]
+        {
+            let at = |x: f64| {
+                tree.eval_with(&HashMap::from([("x".to_string(), x), ("n".to_string(), n)]))
+            };
+            let h = 1e-6;
+            let expected = (at(x + h) - at(x - h)) / (2. * h);
+            let vars = HashMap::from([("x".to_string(), x), ("n".to_string(), n)]);
+
+            assert!((derivative.eval_with(&vars) - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_matrix_derivative()
+    {
+        let at = |expr: &str, x: f64| {
+            let vars = HashMap::from([("x".to_string(), Array::Scalar(x))]);
+            Expression::parse(expr)
+                .derivative("x")
+                .eval_matrix_with(&vars)
+                .unwrap()
+        };
+
+        assert_eq!(at("det([[x, 0], [0, x]])", 3.), Array::Scalar(6.));
+        assert_eq!(at("det([[x, 2], [x^2, 1]])", 3.), Array::Scalar(-11.));
+        assert_eq!(
+            at("inv([[x, 0], [1, 1]])", 2.),
+            Array::Matrix(vec![vec![-0.25, 0.], vec![0.25, 0.]])
+        );
+        assert_eq!(at("inv(x^2)", 2.), Array::Scalar(-0.25));
+
+        assert_eq!(at("mean([x, 2x])", 3.), Array::Scalar(1.5));
+        assert_eq!(
+            at("sum([x, x]) + sum([[x^2, 1], [x, 0]])", 3.),
+            Array::Scalar(9.)
+        );
+        // stdev([x, 2x, 3x]) = x
+        assert_eq!(at("stdev([x, 2x, 3x])", 3.), Array::Scalar(1.));
+        assert_eq!(derivative("max([x, 2x])"), "NaN ");
+        assert_eq!(
+            derivative("det([[x, 1], [0, 1]] * [[x, 0], [0, 1]])"),
+            "NaN "
+        );
+    }
+}
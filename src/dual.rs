@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use crate::bound::loop_range;
+use crate::bytecode::{execute, Machine};
+use crate::solver::Expression;
+use crate::tokens::*;
+
+// a value together with its partial derivative with respect to every variable of the expression,
+// in the order of Program::variables
+#[derive(Debug, PartialEq, Clone)]
+pub struct Dual
+{
+    pub val: f64,
+    pub grad: Vec<f64>,
+}
+
+impl Dual
+{
+    pub fn constant(val: f64, variables: usize) -> Dual
+    {
+        Dual { val, grad: vec![0.; variables] }
+    }
+
+    pub fn variable(val: f64, slot: usize, variables: usize) -> Dual
+    {
+        let mut grad = vec![0.; variables];
+        grad[slot] = 1.;
+
+        Dual { val, grad }
+    }
+
+    // combines the gradients of two operands, given the partial derivatives of the operation with
+    // respect to each of them
+    fn chain2(self, other: &Dual, val: f64, d_self: f64, d_other: f64) -> Dual
+    {
+        let mut grad = self.grad;
+        grad.iter_mut()
+            .zip(&other.grad)
+            .for_each(|(d1, &d2)| *d1 = scaled(d_self, *d1) + scaled(d_other, d2));
+
+        Dual { val, grad }
+    }
+
+    fn chain(mut self, val: f64, derivative: f64) -> Dual
+    {
+        self.grad.iter_mut().for_each(|d| *d = scaled(derivative, *d));
+        self.val = val;
+
+        self
+    }
+
+    pub fn apply(self, op: Operation, other: Dual) -> Dual
+    {
+        let (val1, val2) = (self.val, other.val);
+        let val = Expression::eval_binary_op(val1, op, val2);
+
+        match op
+        {
+            Operation::Addition => self.chain2(&other, val, 1., 1.),
+            Operation::Subtraction => self.chain2(&other, val, 1., -1.),
+            Operation::Multiplication | Operation::Dot => self.chain2(&other, val, val2, val1),
+            Operation::Division => self.chain2(&other, val, 1. / val2, -val1 / (val2 * val2)),
+            Operation::IntegerDivision => self.chain2(&other, val, 0., 0.),
+            Operation::Remainder => self.chain2(&other, val, 1., -(val1 / val2).floor()),
+            Operation::Exponentiation =>
+            {
+                // each variable is handled separately so that a constant exponent doesn't need
+                // ln of the base, which would give NaN for negative bases
+                let mut grad = self.grad;
+                for (d1, &d2) in grad.iter_mut().zip(&other.grad)
+                {
+                    *d1 = if *d1 == 0. && d2 == 0.
+                    {
+                        0.
+                    }
+                    else if d2 == 0.
+                    {
+                        val2 * val1.powf(val2 - 1.) * *d1
+                    }
+                    else if *d1 == 0.
+                    {
+                        val * val1.ln() * d2
+                    }
+                    else
+                    {
+                        val * (d2 * val1.ln() + val2 * *d1 / val1)
+                    };
+                }
+
+                Dual { val, grad }
+            },
+        }
+    }
+
+    pub fn call(self, func: Function) -> Dual
+    {
+        let arg = self.val;
+        let val = Expression::eval_function(func, arg);
+
+        let derivative = match func
+        {
+            Function::Sqrt => 1. / (2. * val),
+            Function::Exp => val,
+            Function::Ln => 1. / arg,
+            Function::Sin => arg.cos(),
+            Function::Cos => -arg.sin(),
+            Function::Tan => 1. / (arg.cos() * arg.cos()),
+            // only defined for whole numbers, so there's no slope
+            Function::Factorial => f64::NAN,
+            Function::Negate => -1.,
+            Function::Det => 1.,
+            Function::Inverse => -val * val,
+            Function::Aggregate(aggregate) if aggregate.min_len() > 1 => f64::NAN,
+            Function::Aggregate(_) => 1.,
+            Function::Sum | Function::Product => unreachable!("sums are loops in a program"),
+        };
+
+        self.chain(val, derivative)
+    }
+}
+
+// derivative * d, where a variable the operand doesn't depend on stays at zero even if the
+// operation has no slope there, like 5! or sqrt(0)
+fn scaled(derivative: f64, d: f64) -> f64
+{
+    match d == 0.
+    {
+        true => 0.,
+        false => derivative * d,
+    }
+}
+
+// dual numbers for a program, with the values of its variables in slot order
+struct Duals
+{
+    values: Vec<f64>,
+}
+
+impl Machine for Duals
+{
+    type Value = Dual;
+
+    fn constant(&self, val: f64) -> Dual
+    {
+        Dual::constant(val, self.values.len())
+    }
+
+    fn load(&self, slot: usize) -> Dual
+    {
+        Dual::variable(self.values[slot], slot, self.values.len())
+    }
+
+    fn apply(&self, lhs: Dual, op: Operation, rhs: Dual) -> Dual
+    {
+        lhs.apply(op, rhs)
+    }
+
+    fn call(&self, func: Function, arg: Dual) -> Dual
+    {
+        arg.call(func)
+    }
+
+    fn range(&self, lower: &Dual, upper: &Dual) -> (i64, i64)
+    {
+        loop_range(lower.val, upper.val).unwrap_or((1, 0))
+    }
+
+    fn accumulate(&self, op: Operation, total: Dual, term: Dual, _: i64, _: (&Dual, &Dual))
+        -> Dual
+    {
+        total.apply(op, term)
+    }
+
+    // the bounds only take whole numbers, so like factorials they have no slope
+    fn finish(&self, total: Dual, (lower, upper): (&Dual, &Dual)) -> Dual
+    {
+        match loop_range(lower.val, upper.val)
+        {
+            Some(_) =>
+            {
+                let val = total.val;
+                total
+                    .chain2(lower, val, 1., f64::NAN)
+                    .chain2(upper, val, 1., f64::NAN)
+            },
+            None => Dual { val: f64::NAN, grad: vec![f64::NAN; total.grad.len()] },
+        }
+    }
+}
+
+impl Expression
+{
+    // value of the expression and its gradient with respect to every variable in it, computed in
+    // a single forward pass over dual numbers. fails with the name of any variable `vars` has no
+    // value for
+    pub fn eval_gradient(
+        &self,
+        vars: &HashMap<String, f64>,
+    ) -> Result<(f64, HashMap<String, f64>), String>
+    {
+        let program = self.compile();
+        let duals = Duals { values: program.slots_from(vars)? };
+
+        let mut stack: Vec<Dual> = Vec::with_capacity(program.stack_size);
+        let result = execute(&program, &duals, &mut stack);
+        let gradient = program.variables.into_iter().zip(result.grad).collect();
+
+        Ok((result.val, gradient))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn gradient_of(expr: &str, vars: &HashMap<String, f64>) -> (f64, HashMap<String, f64>)
+    {
+        Expression::parse(expr).eval_gradient(vars).unwrap()
+    }
+
+    #[test]
+    fn test_eval_gradient()
+    {
+        let vars = HashMap::from([("x".to_string(), 3.), ("y".to_string(), -2.)]);
+        let (val, gradient) = gradient_of("x^2*y + y^3 - 4", &vars);
+
+        assert_eq!(val, -30.);
+        assert_eq!(
+            gradient,
+            HashMap::from([("x".to_string(), -12.), ("y".to_string(), 21.)])
+        );
+
+        let (val, gradient) = gradient_of("2*3", &HashMap::new());
+        assert_eq!(val, 6.);
+        assert!(gradient.is_empty());
+
+        // y^0.5 and sqrt(0) have no slope at zero, but don't depend on x either
+        let vars = HashMap::from([("x".to_string(), 2.), ("y".to_string(), 0.)]);
+        let (val, gradient) = gradient_of("y^0.5 + x + sqrt(0) * x", &vars);
+        assert_eq!(val, 2.);
+        assert_eq!(gradient["x"], 1.);
+
+        let (val, gradient) = gradient_of("3! * x", &vars);
+        assert_eq!(val, 12.);
+        assert_eq!(gradient["x"], 6.);
+
+        // sums loop up to n when they run, and have no slope in n
+        let vars = HashMap::from([("x".to_string(), 2.), ("n".to_string(), 3.)]);
+        let (val, gradient) = gradient_of("sum(k, 1, n, x^k)", &vars);
+        assert_eq!(val, 14.);
+        assert_eq!(gradient["x"], 17.);
+        assert!(gradient["n"].is_nan());
+
+        let vars = HashMap::from([("x".to_string(), 2.), ("n".to_string(), 1.5)]);
+        let (val, gradient) = gradient_of("prod(k, 1, n, x)", &vars);
+        assert!(val.is_nan() && gradient["x"].is_nan());
+
+        assert_eq!(
+            Expression::parse("x * y").eval_gradient(&vars),
+            Err("y".to_string())
+        );
+    }
+
+    #[test]
+    fn test_gradient_matches_derivative()
+    {
+        let tree =
+            Expression::parse("x^y / (1 + x*y) + sqrt(x)*exp(y) - ln(x)*tan(y) + sin(x)/cos(y)");
+
+        for (x, y) in [(1., 2.), (0.5, -0.25), (2.5, 1.)]
+        {
+            let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+            let (val, gradient) = tree.eval_gradient(&vars).unwrap();
+
+            assert_eq!(val, tree.eval_with(&vars));
+            for name in ["x", "y"]
+            {
+                let expected = tree.derivative(name).eval_with(&vars);
+                assert!((gradient[name] - expected).abs() < 1e-9);
+            }
+        }
+    }
+}
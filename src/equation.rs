@@ -0,0 +1,776 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::solver::Expression;
+use crate::tokens::*;
+
+#[derive(Debug, PartialEq)]
+pub enum EquationError
+{
+    // the variable can't be isolated by inverting operations, and the equation isn't a polynomial
+    CannotIsolate,
+    // only linear and quadratic polynomials are solved
+    DegreeTooHigh(usize),
+    // sin, cos and tan have no inverse here
+    NoInverse(Function),
+    NoSolution,
+    // the equation holds for every value of the variable
+    InfiniteSolutions,
+    // the text given to parse doesn't have exactly one '='
+    NotAnEquation(String),
+}
+
+pub struct Equation
+{
+    pub lhs: Expression,
+    pub rhs: Expression,
+}
+
+// exact solutions for the variable, along with every equation on the way there in the same
+// format as the steps of simplify
+pub struct Solution
+{
+    pub solutions: Vec<Expression>,
+    pub steps: Vec<String>,
+}
+
+impl Equation
+{
+    pub fn parse(equation: &str) -> Result<Equation, EquationError>
+    {
+        match equation.split_once('=')
+        {
+            Some((lhs, rhs)) if !rhs.contains('=') =>
+            {
+                Ok(Equation { lhs: Expression::parse(lhs), rhs: Expression::parse(rhs) })
+            },
+            _ => Err(EquationError::NotAnEquation(equation.to_string())),
+        }
+    }
+
+    pub fn equation_string(&self) -> String
+    {
+        format!(
+            "{}= {}",
+            self.lhs.expression_string(),
+            self.rhs.expression_string()
+        )
+    }
+
+    // solves linear and quadratic equations exactly. when the variable shows up only once it is
+    // isolated by undoing the operations around it one at a time, otherwise the equation is
+    // collected into a polynomial first
+    pub fn solve(&self, var: &str) -> Result<Solution, EquationError>
+    {
+        let difference = Expression::binary(
+            Operation::Subtraction,
+            self.lhs.deep_clone(),
+            self.rhs.deep_clone(),
+        )
+        .expand_bound_variables();
+        let occurrences = occurrences(&difference, var);
+
+        let Some(mut coefficients) = polynomial(&difference, var)
+        else
+        {
+            return match occurrences
+            {
+                1 => self.isolate(var),
+                _ => Err(EquationError::CannotIsolate),
+            };
+        };
+        while coefficients.last() == Some(&0.)
+        {
+            coefficients.pop();
+        }
+
+        match coefficients.len()
+        {
+            0 => Err(EquationError::InfiniteSolutions),
+            1 => Err(EquationError::NoSolution),
+            2 if occurrences == 1 => self.isolate(var),
+            2 =>
+            {
+                let standard = Equation {
+                    lhs: polynomial_expression(&coefficients, var),
+                    rhs: Expression::number(0.),
+                };
+                let mut solution = standard.isolate(var)?;
+                solution.steps.insert(0, standard.equation_string());
+
+                Ok(solution)
+            },
+            3 => Ok(solve_quadratic(&coefficients, var)),
+            len => Err(EquationError::DegreeTooHigh(len - 1)),
+        }
+    }
+
+    // undoes the outermost operation on the side holding the variable until only the variable
+    // is left. undoing an even power gives two values for what's under it, and there are none
+    // when it would have to be negative
+    fn isolate(&self, var: &str) -> Result<Solution, EquationError>
+    {
+        let (mut lhs, rhs) = if occurrences(&self.lhs, var) == 1
+        {
+            (self.lhs.deep_clone(), self.rhs.deep_clone())
+        }
+        else
+        {
+            (self.rhs.deep_clone(), self.lhs.deep_clone())
+        };
+        let mut values = vec![exact_fold(&rhs)];
+        let mut steps = Vec::new();
+
+        while lhs.operator.get_variable() != Some(var)
+        {
+            let operands: Vec<Expression> = lhs
+                .operands
+                .iter()
+                .map(|operand| operand.borrow().deep_clone())
+                .collect();
+
+            let mut inner = None;
+            let mut inverted = Vec::new();
+            for rhs in values
+            {
+                let (next, rhs) = match (&lhs.operator, operands.as_slice())
+                {
+                    (Token::Operator { op }, [f, g]) => invert_binary(*op, f, g, rhs, var)?,
+                    (Token::Function { func }, [f]) =>
+                    {
+                        (f.deep_clone(), invert_function(*func, rhs)?)
+                    },
+                    _ => return Err(EquationError::CannotIsolate),
+                };
+
+                inner = Some(next);
+                inverted.extend(rhs.iter().map(exact_fold));
+            }
+
+            lhs = inner.expect("isolating with no values left");
+            values = inverted;
+            if values.is_empty()
+            {
+                return Err(EquationError::NoSolution);
+            }
+            for rhs in &values
+            {
+                steps.push(format!(
+                    "{}= {}",
+                    lhs.expression_string(),
+                    rhs.expression_string()
+                ));
+            }
+        }
+
+        Ok(Solution { solutions: values, steps })
+    }
+}
+
+// undoes `func(f) = rhs`, giving every value f can have
+fn invert_function(func: Function, rhs: Expression) -> Result<Vec<Expression>, EquationError>
+{
+    let rhs = match func
+    {
+        // square roots are never negative
+        Function::Sqrt if numeric_value(&rhs).is_some_and(|val| val < 0.) => return Ok(Vec::new()),
+        Function::Sqrt => pow(rhs, Expression::number(2.)),
+        Function::Exp => Expression::function(Function::Ln, rhs),
+        Function::Ln => Expression::function(Function::Exp, rhs),
+        Function::Negate => Expression::function(Function::Negate, rhs),
+        Function::Det => rhs,
+        Function::Aggregate(aggregate) if aggregate.min_len() > 1 =>
+        {
+            return Err(EquationError::NoInverse(func))
+        },
+        Function::Aggregate(_) => rhs,
+        Function::Inverse => Expression::binary(Operation::Division, Expression::number(1.), rhs),
+        Function::Sin | Function::Cos | Function::Tan | Function::Factorial =>
+        {
+            return Err(EquationError::NoInverse(func))
+        },
+        Function::Sum | Function::Product => return Err(EquationError::CannotIsolate),
+    };
+
+    Ok(vec![rhs])
+}
+
+// undoes `f op g = rhs` for whichever of f and g holds the variable, giving every value it can have
+fn invert_binary(
+    op: Operation,
+    f: &Expression,
+    g: &Expression,
+    rhs: Expression,
+    var: &str,
+) -> Result<(Expression, Vec<Expression>), EquationError>
+{
+    let binary = Expression::binary;
+
+    if occurrences(f, var) == 1
+    {
+        let g = g.deep_clone();
+        let rhs = match op
+        {
+            Operation::Addition => binary(Operation::Subtraction, rhs, g),
+            Operation::Subtraction => binary(Operation::Addition, rhs, g),
+            Operation::Multiplication | Operation::Dot => binary(Operation::Division, rhs, g),
+            Operation::Division => binary(Operation::Multiplication, rhs, g),
+            Operation::Exponentiation =>
+            {
+                let value = numeric_value(&rhs);
+                let (even_power, even_root) = (is_even_power(&g), is_even_root(&g));
+                let root = pow(rhs, binary(Operation::Division, Expression::number(1.), g));
+
+                let values = match value
+                {
+                    // even powers and even roots are never negative
+                    Some(val) if val < 0. && (even_power || even_root) => Vec::new(),
+                    // and f^2 = 4 has f = 2 and f = -2
+                    _ if even_power && value != Some(0.) => vec![
+                        root.deep_clone(),
+                        Expression::function(Function::Negate, root),
+                    ],
+                    _ => vec![root],
+                };
+                return Ok((f.deep_clone(), values));
+            },
+            // rounding loses what would be needed to undo it
+            Operation::IntegerDivision | Operation::Remainder =>
+            {
+                return Err(EquationError::CannotIsolate)
+            },
+        };
+
+        Ok((f.deep_clone(), vec![rhs]))
+    }
+    else
+    {
+        let f = f.deep_clone();
+        let rhs = match op
+        {
+            Operation::Addition => binary(Operation::Subtraction, rhs, f),
+            Operation::Subtraction => binary(Operation::Subtraction, f, rhs),
+            Operation::Multiplication | Operation::Dot => binary(Operation::Division, rhs, f),
+            Operation::Division => binary(Operation::Division, f, rhs),
+            Operation::Exponentiation => binary(
+                Operation::Division,
+                Expression::function(Function::Ln, rhs),
+                Expression::function(Function::Ln, f),
+            ),
+            Operation::IntegerDivision | Operation::Remainder =>
+            {
+                return Err(EquationError::CannotIsolate)
+            },
+        };
+
+        Ok((g.deep_clone(), vec![rhs]))
+    }
+}
+
+fn solve_quadratic(coefficients: &[f64], var: &str) -> Solution
+{
+    let (c, b, a) = (coefficients[0], coefficients[1], coefficients[2]);
+    let discriminant = b * b - 4. * a * c;
+
+    let standard = Equation {
+        lhs: polynomial_expression(coefficients, var),
+        rhs: Expression::number(0.),
+    };
+    let mut steps = vec![standard.equation_string()];
+    let mut solutions = Vec::new();
+
+    // a repeated root only shows up once, and there are no real roots below zero
+    let signs: &[Operation] = match discriminant
+    {
+        d if d > 0. => &[Operation::Addition, Operation::Subtraction],
+        0. => &[Operation::Addition],
+        _ => &[],
+    };
+
+    for &sign in signs
+    {
+        // (-b ± sqrt(b^2 - 4ac)) / 2a
+        let formula = Expression::binary(
+            Operation::Division,
+            Expression::binary(
+                sign,
+                Expression::number(0. - b),
+                Expression::function(Function::Sqrt, Expression::number(discriminant)),
+            ),
+            Expression::number(2. * a),
+        );
+        let solution = exact_fold(&formula);
+
+        steps.push(format!(
+            "{}= {}",
+            var_string(var),
+            formula.expression_string()
+        ));
+        steps.push(format!(
+            "{}= {}",
+            var_string(var),
+            solution.expression_string()
+        ));
+        solutions.push(solution);
+    }
+
+    Solution { solutions, steps }
+}
+
+fn var_string(var: &str) -> String
+{
+    Expression::variable(var).expression_string()
+}
+
+fn pow(lhs: Expression, rhs: Expression) -> Expression
+{
+    Expression::binary(Operation::Exponentiation, lhs, rhs)
+}
+
+fn occurrences(expression: &Expression, var: &str) -> usize
+{
+    expression
+        .to_postfix_tokens()
+        .iter()
+        .filter(|token| token.get_variable() == Some(var))
+        .count()
+}
+
+// coefficients of the expression as a polynomial in `var`, lowest degree first. None if it has
+// other variables, or if the variable shows up in a way a polynomial can't have
+fn polynomial(expression: &Expression, var: &str) -> Option<Vec<f64>>
+{
+    let mut stack: Vec<Vec<f64>> = Vec::new();
+
+    for token in expression.to_postfix_tokens()
+    {
+        let coefficients = match token
+        {
+            Token::Number { .. } => vec![token.get_number()?],
+            Token::Variable { ref name } if name == var => vec![0., 1.],
+            Token::Variable { .. } => return None,
+            Token::Operator { op } =>
+            {
+                let p2 = stack.pop()?;
+                let p1 = stack.pop()?;
+
+                match op
+                {
+                    Operation::Addition => add_polynomials(p1, &p2, 1.),
+                    Operation::Subtraction => add_polynomials(p1, &p2, -1.),
+                    Operation::Multiplication | Operation::Dot => multiply_polynomials(&p1, &p2),
+                    Operation::Division if p2.len() == 1 && p2[0] != 0. =>
+                    {
+                        p1.iter().map(|coefficient| coefficient / p2[0]).collect()
+                    },
+                    Operation::Exponentiation
+                        if p2.len() == 1 && p2[0] >= 0. && p2[0].fract() == 0. =>
+                    {
+                        let mut result = vec![1.];
+                        for _ in 0..p2[0] as usize
+                        {
+                            result = multiply_polynomials(&result, &p1);
+                        }
+                        result
+                    },
+                    _ if p1.len() == 1 && p2.len() == 1 =>
+                    {
+                        vec![Expression::eval_binary_op(p1[0], op, p2[0])]
+                    },
+                    _ => return None,
+                }
+            },
+            Token::Function { func: Function::Negate } =>
+            {
+                stack.pop()?.iter().map(|coefficient| -coefficient).collect()
+            },
+            Token::Function { func } =>
+            {
+                let p = stack.pop()?;
+                if p.len() != 1
+                {
+                    return None;
+                }
+
+                vec![Expression::eval_function(func, p[0])]
+            },
+            Token::Array { .. } => return None,
+            Token::LSep | Token::RSep | Token::LBracket | Token::RBracket | Token::Comma =>
+            {
+                return None
+            },
+        };
+        stack.push(coefficients);
+    }
+
+    stack.pop()
+}
+
+fn add_polynomials(mut p1: Vec<f64>, p2: &[f64], sign: f64) -> Vec<f64>
+{
+    if p1.len() < p2.len()
+    {
+        p1.resize(p2.len(), 0.);
+    }
+    p1.iter_mut()
+        .zip(p2)
+        .for_each(|(coefficient1, coefficient2)| *coefficient1 += sign * coefficient2);
+
+    p1
+}
+
+fn multiply_polynomials(p1: &[f64], p2: &[f64]) -> Vec<f64>
+{
+    let mut result = vec![0.; p1.len() + p2.len() - 1];
+    for (i, coefficient1) in p1.iter().enumerate()
+    {
+        for (j, coefficient2) in p2.iter().enumerate()
+        {
+            result[i + j] += coefficient1 * coefficient2;
+        }
+    }
+
+    result
+}
+
+// the polynomial written out highest degree first, like "2x^2 - 5x + 6"
+fn polynomial_expression(coefficients: &[f64], var: &str) -> Expression
+{
+    let mut result: Option<Expression> = None;
+
+    for (degree, &coefficient) in coefficients.iter().enumerate().rev()
+    {
+        if coefficient == 0.
+        {
+            continue;
+        }
+
+        let magnitude = if result.is_some()
+        {
+            coefficient.abs()
+        }
+        else
+        {
+            coefficient
+        };
+        let term = match degree
+        {
+            0 => Expression::number(magnitude),
+            _ =>
+            {
+                let power = match degree
+                {
+                    1 => Expression::variable(var),
+                    _ => pow(Expression::variable(var), Expression::number(degree as f64)),
+                };
+
+                match magnitude
+                {
+                    1. => power,
+                    _ => Expression::binary(
+                        Operation::Multiplication,
+                        Expression::number(magnitude),
+                        power,
+                    ),
+                }
+            },
+        };
+
+        result = Some(match result
+        {
+            None => term,
+            Some(lhs) if coefficient < 0. => Expression::binary(Operation::Subtraction, lhs, term),
+            Some(lhs) => Expression::binary(Operation::Addition, lhs, term),
+        });
+    }
+
+    result.unwrap_or_else(|| Expression::number(0.))
+}
+
+// folds numbers together only where no precision is lost, so square roots and logarithms that
+// aren't whole numbers stay as they are and integer arithmetic is done on reduced fractions
+pub fn exact_fold(expression: &Expression) -> Expression
+{
+    let mut stack: Vec<Expression> = Vec::new();
+
+    for token in expression.to_postfix_tokens()
+    {
+        let operands = stack.split_off(stack.len() - token.arity());
+        let fractions: Option<Vec<(f64, f64)>> = operands.iter().map(as_fraction).collect();
+
+        let folded = match (&token, fractions)
+        {
+            (Token::Operator { op }, Some(fractions)) =>
+            {
+                fold_fractions(fractions[0], *op, fractions[1])
+            },
+            (Token::Function { func }, Some(fractions)) if fractions[0].1 == 1. =>
+            {
+                let val = Expression::eval_function(*func, fractions[0].0);
+                (val.fract() == 0.).then(|| Expression::number(val))
+            },
+            _ => None,
+        };
+
+        stack.push(folded.unwrap_or_else(|| {
+            let operands = operands
+                .into_iter()
+                .map(|operand| Rc::new(RefCell::new(operand)))
+                .collect();
+
+            Expression { operator: token, operands }
+        }));
+    }
+
+    stack.pop().expect("tried to pop value off empty stack")
+}
+
+// a number, or an integer divided by an integer, as (numerator, denominator)
+fn as_fraction(expression: &Expression) -> Option<(f64, f64)>
+{
+    if let Some(val) = expression.number_value()
+    {
+        return Some((val, 1.));
+    }
+
+    match expression.operator
+    {
+        Token::Operator { op: Operation::Division } =>
+        {
+            let num = expression.operands[0].borrow().number_value()?;
+            let den = expression.operands[1].borrow().number_value()?;
+
+            (num.fract() == 0. && den.fract() == 0.).then_some((num, den))
+        },
+        _ => None,
+    }
+}
+
+// the value of a side that folded down to a number or a fraction
+fn numeric_value(expression: &Expression) -> Option<f64>
+{
+    as_fraction(expression).map(|(num, den)| num / den)
+}
+
+// an even whole number exponent, like in x^2
+fn is_even_power(exponent: &Expression) -> bool
+{
+    exponent
+        .number_value()
+        .is_some_and(|val| val != 0. && val % 2. == 0.)
+}
+
+// an exponent whose denominator is even in lowest terms, like in x^(1/2) or x^0.75
+fn is_even_root(exponent: &Expression) -> bool
+{
+    let (mut num, mut den) = match as_fraction(exponent)
+    {
+        Some(fraction) => fraction,
+        None => return false,
+    };
+
+    // decimals are read as a fraction over a power of ten
+    while num.fract() != 0. && den < 1e15
+    {
+        num *= 10.;
+        den *= 10.;
+    }
+
+    num.fract() == 0.
+        && fraction(num, den)
+            .and_then(|reduced| as_fraction(&reduced))
+            .is_some_and(|(_, den)| den % 2. == 0.)
+}
+
+fn fold_fractions(
+    (num1, den1): (f64, f64),
+    op: Operation,
+    (num2, den2): (f64, f64),
+) -> Option<Expression>
+{
+    let integers = [num1, den1, num2, den2].iter().all(|val| val.fract() == 0.);
+    if !integers
+    {
+        let val = Expression::eval_binary_op(num1 / den1, op, num2 / den2);
+        return Some(Expression::number(val));
+    }
+
+    match op
+    {
+        Operation::Addition => fraction(num1 * den2 + num2 * den1, den1 * den2),
+        Operation::Subtraction => fraction(num1 * den2 - num2 * den1, den1 * den2),
+        Operation::Multiplication | Operation::Dot => fraction(num1 * num2, den1 * den2),
+        Operation::Division => fraction(num1 * den2, den1 * num2),
+        Operation::IntegerDivision | Operation::Remainder if num2 == 0. => None,
+        Operation::IntegerDivision => fraction((num1 * den2 / (den1 * num2)).floor(), 1.),
+        // f % g = f - g (f // g)
+        Operation::Remainder =>
+        {
+            let quotient = (num1 * den2 / (den1 * num2)).floor();
+            fraction(num1 * den2 - quotient * num2 * den1, den1 * den2)
+        },
+        Operation::Exponentiation if den2 == 1. && num2 >= 0. =>
+        {
+            fraction(num1.powf(num2), den1.powf(num2))
+        },
+        Operation::Exponentiation if den2 == 1. => fraction(den1.powf(-num2), num1.powf(-num2)),
+        // roots are only folded when they come out even, like 8^(1/3)
+        Operation::Exponentiation =>
+        {
+            let val = Expression::eval_binary_op(num1 / den1, op, num2 / den2);
+            (val.fract() == 0.).then(|| Expression::number(val))
+        },
+    }
+}
+
+// num / den in lowest terms, as a plain number when it divides evenly
+fn fraction(num: f64, den: f64) -> Option<Expression>
+{
+    if den == 0.
+    {
+        return None;
+    }
+
+    let (mut a, mut b) = (num.abs(), den.abs());
+    while b != 0.
+    {
+        (a, b) = (b, a % b);
+    }
+    let divisor = if a == 0. { 1. } else { a };
+    let (num, den) = (
+        num.abs() / divisor * num.signum() * den.signum(),
+        den.abs() / divisor,
+    );
+
+    Some(match den
+    {
+        1. => Expression::number(num),
+        _ => Expression::binary(
+            Operation::Division,
+            Expression::number(num),
+            Expression::number(den),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn solve(equation: &str) -> (Vec<String>, Vec<String>)
+    {
+        let solution = Equation::parse(equation).unwrap().solve("x").unwrap();
+        let solutions = solution
+            .solutions
+            .iter()
+            .map(|solution| solution.expression_string())
+            .collect();
+
+        (solutions, solution.steps)
+    }
+
+    #[test]
+    fn test_linear()
+    {
+        assert_eq!(
+            solve("2x + 3 = 7"),
+            (
+                vec!["2 ".to_string()],
+                vec!["( 2 * x ) = 4 ".to_string(), "x = 2 ".to_string()]
+            )
+        );
+        assert_eq!(solve("7 = 3(x - 1)/2").0, vec!["( 17 / 3 ) "]);
+        assert_eq!(
+            solve("5x - 2 = 3x + 1"),
+            (
+                vec!["( 3 / 2 ) ".to_string()],
+                vec![
+                    "( ( 2 * x ) - 3 ) = 0 ".to_string(),
+                    "( 2 * x ) = 3 ".to_string(),
+                    "x = ( 3 / 2 ) ".to_string(),
+                ]
+            )
+        );
+        assert_eq!(solve("a*x + b = c").0, vec!["( ( c - b ) / a ) "]);
+    }
+
+    #[test]
+    fn test_quadratic()
+    {
+        assert_eq!(
+            solve("x^2 - 5x + 6 = 0"),
+            (
+                vec!["3 ".to_string(), "2 ".to_string()],
+                vec![
+                    "( ( ( x ^ 2 ) - ( 5 * x ) ) + 6 ) = 0 ".to_string(),
+                    "x = ( ( 5 + sqrt( 1 ) ) / 2 ) ".to_string(),
+                    "x = 3 ".to_string(),
+                    "x = ( ( 5 - sqrt( 1 ) ) / 2 ) ".to_string(),
+                    "x = 2 ".to_string(),
+                ]
+            )
+        );
+        assert_eq!(
+            solve("x^2 = 3x + 1").0,
+            vec!["( ( 3 + sqrt( 13 ) ) / 2 ) ", "( ( 3 - sqrt( 13 ) ) / 2 ) "]
+        );
+        assert_eq!(solve("(x - 1)^2 = 0").0, vec!["1 "]);
+        assert_eq!(
+            solve("x^2 = 2").0,
+            vec!["( ( 0 + sqrt( 8 ) ) / 2 ) ", "( ( 0 - sqrt( 8 ) ) / 2 ) "]
+        );
+        assert_eq!(solve("x^2 + 1 = 0").0, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_isolate_functions()
+    {
+        assert_eq!(solve("sqrt(x + 1) = 3").0, vec!["8 "]);
+        assert_eq!(solve("exp(2x) = 5").0, vec!["( ln( 5 ) / 2 ) "]);
+        assert_eq!(solve("2^x = 8").0, vec!["( ln( 8 ) / ln( 2 ) ) "]);
+        assert_eq!(solve("x^(1/3) = 2").0, vec!["8 "]);
+    }
+
+    #[test]
+    fn test_isolate_even_powers()
+    {
+        assert_eq!(
+            solve("x^2 = a").0,
+            vec!["( a ^ ( 1 / 2 ) ) ", "( - ( a ^ ( 1 / 2 ) ) ) "]
+        );
+        assert_eq!(solve("(x + a)^2 = 4").0, vec!["( 2 - a ) ", "( -2 - a ) "]);
+        assert_eq!(solve("(x + a)^2 = 0").0, vec!["( 0 - a ) "]);
+        assert_eq!(solve("x^(1/2) = a").0, vec!["( a ^ 2 ) "]);
+
+        let solve = |equation| Equation::parse(equation).unwrap().solve("x").err();
+        assert_eq!(solve("sqrt(x) = -3"), Some(EquationError::NoSolution));
+        assert_eq!(solve("x^(1/2) = 1 - 4"), Some(EquationError::NoSolution));
+        assert_eq!(solve("x^0.5 + 1 = -1"), Some(EquationError::NoSolution));
+        assert_eq!(solve("(x + a)^2 = -4"), Some(EquationError::NoSolution));
+    }
+
+    #[test]
+    fn test_solve_errors()
+    {
+        let solve = |equation| Equation::parse(equation).unwrap().solve("x").err();
+
+        assert_eq!(solve("x^3 = 8"), Some(EquationError::DegreeTooHigh(3)));
+        assert_eq!(solve("x + 1 = x"), Some(EquationError::NoSolution));
+        assert_eq!(solve("2x = x + x"), Some(EquationError::InfiniteSolutions));
+        assert_eq!(
+            solve("sin(x) = 1"),
+            Some(EquationError::NoInverse(Function::Sin))
+        );
+        assert_eq!(solve("x*exp(x) = 1"), Some(EquationError::CannotIsolate));
+
+        assert_eq!(
+            Equation::parse("x^2 - 1").err(),
+            Some(EquationError::NotAnEquation("x^2 - 1".to_string()))
+        );
+        assert_eq!(
+            Equation::parse("x = 1 = 2").err(),
+            Some(EquationError::NotAnEquation("x = 1 = 2".to_string()))
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::solver::Expression;
+use crate::tokens::*;
+
+impl Expression
+{
+    // folds every subtree that only contains numbers and removes operations that can't change
+    // the result, leaving the parts that depend on variables alone. "2*3*x + (4-4)*y" becomes
+    // "6*x"
+    //
+    // variables are assumed to be finite, so x*0 folds to 0 even though it would be NaN if x was
+    // infinite. a side that already holds an infinite or NaN number, like the one in "(1/0)*x*0",
+    // is never multiplied away
+    pub fn partial_eval(&self) -> Expression
+    {
+        let mut val_stack: Vec<Expression> = Vec::new();
+
+        for token in self.to_postfix_tokens()
+        {
+            match token
+            {
+                Token::Number { .. } | Token::Variable { .. } =>
+                {
+                    val_stack.push(Expression { operator: token, operands: Vec::new() })
+                },
+                Token::Operator { op } =>
+                {
+                    let rhs = val_stack.pop().expect("tried to pop value off empty stack");
+                    let lhs = val_stack.pop().expect("tried to pop value off empty stack");
+
+                    val_stack.push(fold_binary_op(lhs, op, rhs));
+                },
+                Token::Function { func } if func.binds_variable() =>
+                {
+                    let operands = val_stack.split_off(val_stack.len() - token.arity());
+                    let node = Expression {
+                        operator: token,
+                        operands: operands
+                            .into_iter()
+                            .map(|operand| Rc::new(RefCell::new(operand)))
+                            .collect(),
+                    };
+
+                    // only the bound variable is left, so the whole sum is a number
+                    match node.free_variables().is_empty()
+                    {
+                        true => val_stack.push(Expression::number(node.eval())),
+                        false => val_stack.push(node),
+                    }
+                },
+                Token::Function { func } =>
+                {
+                    let arg = val_stack.pop().expect("tried to pop value off empty stack");
+
+                    match arg.number_value()
+                    {
+                        Some(val) =>
+                        {
+                            val_stack.push(Expression::number(Expression::eval_function(func, val)))
+                        },
+                        None => val_stack.push(Expression::function(func, arg)),
+                    }
+                },
+                // each element is folded on its own, but the array stays
+                Token::Array { .. } =>
+                {
+                    let elements = val_stack.split_off(val_stack.len() - token.arity());
+                    val_stack.push(Expression {
+                        operator: token,
+                        operands: elements
+                            .into_iter()
+                            .map(|element| Rc::new(RefCell::new(element)))
+                            .collect(),
+                    });
+                },
+                Token::LSep | Token::RSep | Token::LBracket | Token::RBracket | Token::Comma =>
+                {
+                    panic!("unexpected token for postfix expression: {:?}", token)
+                },
+            }
+        }
+
+        assert!(val_stack.len() == 1);
+        val_stack.pop().unwrap()
+    }
+}
+
+fn fold_binary_op(lhs: Expression, op: Operation, rhs: Expression) -> Expression
+{
+    match (lhs.number_value(), op, rhs.number_value())
+    {
+        (Some(val1), _, Some(val2)) =>
+        {
+            Expression::number(Expression::eval_binary_op(val1, op, val2))
+        },
+
+        (Some(0.), Operation::Addition, _) => rhs,
+        (_, Operation::Addition | Operation::Subtraction, Some(0.)) => lhs,
+
+        (Some(1.), Operation::Multiplication, _) => rhs,
+        (_, Operation::Multiplication | Operation::Division, Some(1.)) => lhs,
+        // only 0 when the other side is finite, which unknowns are taken to be
+        (Some(0.), Operation::Multiplication, _) if has_finite_numbers(&rhs) =>
+        {
+            Expression::number(0.)
+        },
+        (_, Operation::Multiplication, Some(0.)) if has_finite_numbers(&lhs) =>
+        {
+            Expression::number(0.)
+        },
+
+        (_, Operation::Exponentiation, Some(1.)) => lhs,
+
+        _ => Expression::binary(op, lhs, rhs),
+    }
+}
+
+fn has_finite_numbers(expression: &Expression) -> bool
+{
+    expression
+        .to_postfix_tokens()
+        .iter()
+        .all(|token| token.get_number().is_none_or(f64::is_finite))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn fold(expr: &str) -> String
+    {
+        Expression::parse(expr).partial_eval().expression_string()
+    }
+
+    #[test]
+    fn test_partial_eval()
+    {
+        assert_eq!(fold("2*3*x + (4-4)*y"), "( 6 * x ) ");
+        assert_eq!(fold("(2^3 - 1) * 4"), "28 ");
+        assert_eq!(fold("x^(3-2) / (5-4) - 0"), "x ");
+        assert_eq!(fold("0 + 1 * (y + 2*3)"), "( y + 6 ) ");
+        assert_eq!(fold("x - y * (1 - 1)"), "x ");
+        assert_eq!(fold("2 ^ x * 1 + z"), "( ( 2 ^ x ) + z ) ");
+        assert_eq!(fold("sqrt(x * 1) + ln(exp(2))"), "( sqrt( x ) + 2 ) ");
+        assert_eq!(
+            fold("(1/0)*x*0 + 0*(0/0)"),
+            "( ( ( inf * x ) * 0 ) + NaN ) "
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::numeric::{array_description, Numeric};
+use crate::rational::{exact_factorial, exact_pow, Irrational, RationalError, Value};
+use crate::solver::Expression;
+use crate::tokens::*;
+
+// what `/` does when the division doesn't come out even
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Division
+{
+    Error,
+    // carry on with the exact fraction
+    Promote,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IntegerError
+{
+    DivisionByZero,
+    // literals have to be whole numbers
+    InvalidLiteral(String),
+    // the operation whose result isn't an integer, like "7 / 2", or not even rational once
+    // divisions are promoted, like "sqrt( 2 )"
+    Inexact(String),
+    // the operation that has no result at all, like "factorial( -1 )"
+    Undefined(String),
+    // a statistic of a single integer that needs more, like "stdev( 4 )"
+    NotEnoughValues(String),
+    // a vector or matrix literal like "[ 1, 2 ]"
+    Unsupported(String),
+    // a power or factorial too big to work out, like "2 ^ 10000000" or "factorial( 1000000 )"
+    TooLarge(String),
+}
+
+// an integer of any size, or a fraction once a division has been promoted
+#[derive(Debug, Clone)]
+pub struct Integer
+{
+    val: BigRational,
+    division: Division,
+}
+
+impl PartialEq for Integer
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.val == other.val
+    }
+}
+
+impl From<BigInt> for Integer
+{
+    fn from(val: BigInt) -> Self
+    {
+        Integer {
+            val: BigRational::from_integer(val),
+            division: Division::Error,
+        }
+    }
+}
+
+impl Integer
+{
+    pub fn parse(literal: &str, division: Division) -> Result<Integer, IntegerError>
+    {
+        let val: BigInt = literal
+            .parse()
+            .map_err(|_| IntegerError::InvalidLiteral(literal.to_string()))?;
+
+        Ok(Integer { val: BigRational::from_integer(val), division })
+    }
+
+    // the value if it's a whole number
+    pub fn to_bigint(&self) -> Option<BigInt>
+    {
+        self.val.is_integer().then(|| self.val.to_integer())
+    }
+
+    pub fn to_rational(&self) -> &BigRational
+    {
+        &self.val
+    }
+
+    // keeps `val` if it's a whole number or divisions are being promoted
+    fn checked(
+        &self,
+        val: BigRational,
+        description: impl FnOnce() -> String,
+    ) -> Result<Integer, IntegerError>
+    {
+        match val.is_integer() || self.division == Division::Promote
+        {
+            true => Ok(Integer { val, division: self.division }),
+            false => Err(IntegerError::Inexact(description())),
+        }
+    }
+
+    fn nonzero(other: &Integer) -> Result<(), IntegerError>
+    {
+        match other.val.is_zero()
+        {
+            true => Err(IntegerError::DivisionByZero),
+            false => Ok(()),
+        }
+    }
+}
+
+// integers on their own, and promoted fractions like "7/2"
+impl fmt::Display for Integer
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.val.is_integer()
+        {
+            true => write!(f, "{}", self.val.numer()),
+            false => write!(f, "{}/{}", self.val.numer(), self.val.denom()),
+        }
+    }
+}
+
+impl Numeric for Integer
+{
+    type Error = IntegerError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        Integer::parse(literal, Division::Error)
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        ToPrimitive::to_f64(&self.val).unwrap_or(f64::NAN)
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Integer { val: &self.val + &other.val, division: self.division })
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Integer { val: &self.val - &other.val, division: self.division })
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Integer { val: &self.val * &other.val, division: self.division })
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Self::nonzero(other)?;
+        self.checked(&self.val / &other.val, || format!("{} / {}", self, other))
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Self::nonzero(other)?;
+        Ok(Integer {
+            val: (&self.val / &other.val).floor(),
+            division: self.division,
+        })
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Self::nonzero(other)?;
+        let quotient = (&self.val / &other.val).floor();
+
+        Ok(Integer {
+            val: &self.val - &other.val * quotient,
+            division: self.division,
+        })
+    }
+
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{} ^ {}", self, other);
+
+        match exact_pow(&self.val, &other.val)
+        {
+            Ok(Some(val)) => self.checked(val, description),
+            Ok(None) => Err(IntegerError::Inexact(description())),
+            Err(RationalError::TooLarge(_)) => Err(IntegerError::TooLarge(description())),
+            Err(_) => Err(IntegerError::DivisionByZero),
+        }
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{}( {} )", func.name(), self);
+
+        if func == Function::Factorial
+        {
+            return match exact_factorial(&self.val)
+            {
+                Ok(Some(val)) => Ok(Integer { val, division: self.division }),
+                Ok(None) if self.val.is_integer() && self.val.is_negative() =>
+                {
+                    Err(IntegerError::Undefined(description()))
+                },
+                Ok(None) => Err(IntegerError::Inexact(description())),
+                Err(_) => Err(IntegerError::TooLarge(description())),
+            };
+        }
+
+        match Value::Exact(self.val.clone()).call(func, Irrational::Error)
+        {
+            Ok(Value::Exact(val)) => self.checked(val, description),
+            Ok(Value::Float(_)) | Err(RationalError::Irrational(_)) =>
+            {
+                Err(IntegerError::Inexact(description()))
+            },
+            Err(RationalError::DivisionByZero) => Err(IntegerError::DivisionByZero),
+            Err(RationalError::InvalidLiteral(literal)) =>
+            {
+                Err(IntegerError::InvalidLiteral(literal))
+            },
+            Err(RationalError::NotEnoughValues(_)) =>
+            {
+                Err(IntegerError::NotEnoughValues(description()))
+            },
+            Err(RationalError::Unsupported(array)) => Err(IntegerError::Unsupported(array)),
+            Err(RationalError::TooLarge(_)) => Err(IntegerError::TooLarge(description())),
+            Err(RationalError::Undefined(_)) => Err(IntegerError::Undefined(description())),
+        }
+    }
+
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Err(IntegerError::Unsupported(array_description(&elements)))
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(IntegerError::Undefined(description))
+    }
+}
+
+impl Expression
+{
+    pub fn eval_integer(&self, division: Division) -> Result<Integer, IntegerError>
+    {
+        self.eval_integer_with(&HashMap::new(), division)
+    }
+
+    pub fn eval_integer_with(
+        &self,
+        vars: &HashMap<String, Integer>,
+        division: Division,
+    ) -> Result<Integer, IntegerError>
+    {
+        let vars = vars
+            .iter()
+            .map(|(name, val)| (name.clone(), Integer { val: val.val.clone(), division }))
+            .collect();
+
+        Self::eval_helper(self, &vars, &|literal| Integer::parse(literal, division))
+    }
+
+    // like simplify_wavefront, with every intermediate integer written out in full
+    pub fn simplify_integer(
+        self,
+        division: Division,
+    ) -> Result<(Integer, Vec<String>), IntegerError>
+    {
+        self.simplify_with(&|literal| Integer::parse(literal, division))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn eval(expr: &str) -> Result<String, IntegerError>
+    {
+        Expression::parse(expr)
+            .eval_integer(Division::Error)
+            .map(|val| val.to_string())
+    }
+
+    #[test]
+    fn test_eval_integer()
+    {
+        assert_eq!(
+            eval("2^200"),
+            Ok("1606938044258990275541962092341162602522202993782792835301376".to_string())
+        );
+        assert_eq!(
+            eval("30!"),
+            Ok("265252859812191058636308480000000".to_string())
+        );
+        assert_eq!(eval("30! / 28! - 2^3!"), Ok("806".to_string()));
+        assert_eq!(
+            eval("3^10000000"),
+            Err(IntegerError::TooLarge("3 ^ 10000000".to_string()))
+        );
+        assert_eq!(
+            eval("1000000!"),
+            Err(IntegerError::TooLarge("factorial( 1000000 )".to_string()))
+        );
+        assert_eq!(eval("20000! / 19998!"), Ok("399980000".to_string()));
+        assert_eq!(
+            eval("7 // 2 + 7 % 3 + (0-7) // 2 + (0-7) % 3"),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            eval("sqrt(10^40) + prod(k, 1, 20, k) % 1000007"),
+            Ok("100000000000000794133".to_string())
+        );
+
+        let vars = HashMap::from([("n".to_string(), Integer::from(BigInt::from(10)))]);
+        let result = Expression::parse("n^n - 1").eval_integer_with(&vars, Division::Error);
+        assert_eq!(result.unwrap().to_string(), "9999999999");
+    }
+
+    #[test]
+    fn test_integer_division()
+    {
+        assert_eq!(eval("12 / 4"), Ok("3".to_string()));
+        assert_eq!(
+            eval("7 / 2"),
+            Err(IntegerError::Inexact("7 / 2".to_string()))
+        );
+        assert_eq!(
+            eval("2^(0-1)"),
+            Err(IntegerError::Inexact("2 ^ -1".to_string()))
+        );
+        assert_eq!(
+            eval("sqrt(8)"),
+            Err(IntegerError::Inexact("sqrt( 8 )".to_string()))
+        );
+        assert_eq!(
+            eval("(0-1)!"),
+            Err(IntegerError::Undefined("factorial( -1 )".to_string()))
+        );
+        assert_eq!(eval("5 % (3 - 3)"), Err(IntegerError::DivisionByZero));
+        assert_eq!(
+            eval("1.5 * 2"),
+            Err(IntegerError::InvalidLiteral("1.5".to_string()))
+        );
+
+        let promote = |expr| {
+            Expression::parse(expr)
+                .eval_integer(Division::Promote)
+                .map(|val| val.to_string())
+        };
+        assert_eq!(promote("7 / 2"), Ok("7/2".to_string()));
+        assert_eq!(
+            promote("(1 / 3 + 1 / 6) * 4 + 2^(0-1)"),
+            Ok("5/2".to_string())
+        );
+        assert_eq!(promote("(7 / 2) // 1"), Ok("3".to_string()));
+        assert_eq!(
+            promote("sqrt(2)"),
+            Err(IntegerError::Inexact("sqrt( 2 )".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_simplify_integer()
+    {
+        let (result, steps) = Expression::parse("2^200 + 30!")
+            .simplify_integer(Division::Error)
+            .unwrap();
+
+        assert_eq!(
+            result.to_bigint(),
+            Some(
+                "1606938044258990275541962092606415462334394052419101315301376"
+                    .parse()
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            steps,
+            vec![
+                "( 1606938044258990275541962092341162602522202993782792835301376 + \
+                 265252859812191058636308480000000 ) ",
+                "1606938044258990275541962092606415462334394052419101315301376 ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use crate::bytecode::SingleVariable;
+use crate::solver::Expression;
+
+#[derive(Debug, PartialEq)]
+pub enum IntegrationError
+{
+    // the expression uses a variable other than the one being integrated over
+    UnboundVariable(String),
+    // the expression isn't a finite number here, like 1/x at 0
+    Singularity(f64),
+    // the error estimate was still above the tolerance after max_evaluations
+    DidNotConverge
+    {
+        value: f64,
+        error: f64,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Quadrature
+{
+    AdaptiveSimpson,
+    GaussKronrod,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Integral
+{
+    pub value: f64,
+    // estimate of the absolute error of value
+    pub error: f64,
+    pub evaluations: usize,
+}
+
+pub struct Integrator
+{
+    pub method: Quadrature,
+    pub tolerance: f64,
+    pub max_evaluations: usize,
+}
+
+impl Default for Integrator
+{
+    fn default() -> Self
+    {
+        Integrator {
+            method: Quadrature::GaussKronrod,
+            tolerance: 1e-10,
+            max_evaluations: 100_000,
+        }
+    }
+}
+
+// counts evaluations and turns values that aren't finite into errors
+struct Integrand
+{
+    function: SingleVariable,
+    evaluations: usize,
+}
+
+impl Integrand
+{
+    fn eval(&mut self, x: f64) -> Result<f64, IntegrationError>
+    {
+        self.evaluations += 1;
+
+        let val = self.function.eval(x);
+        match val.is_finite()
+        {
+            true => Ok(val),
+            false => Err(IntegrationError::Singularity(x)),
+        }
+    }
+}
+
+// nodes of the 15 point kronrod rule on [-1, 1] from the middle outwards, with the weights of the
+// kronrod rule and of the 7 point gauss rule that uses every other node
+const KRONROD_NODES: [f64; 8] = [
+    0.,
+    0.20778495500789848,
+    0.4058451513773972,
+    0.5860872354676911,
+    0.7415311855993945,
+    0.8648644233597691,
+    0.9491079123427585,
+    0.9914553711208126,
+];
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.20948214108472782,
+    0.20443294007529889,
+    0.19035057806478542,
+    0.1690047266392679,
+    0.14065325971552592,
+    0.10479001032225019,
+    0.06309209262997856,
+    0.022935322010529224,
+];
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.4179591836734694,
+    0.3818300505051189,
+    0.27970539148927664,
+    0.1294849661688697,
+];
+
+impl Integrator
+{
+    pub fn new() -> Integrator
+    {
+        Self::default()
+    }
+
+    pub fn integrate(
+        &self,
+        expression: &Expression,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<Integral, IntegrationError>
+    {
+        let function =
+            SingleVariable::new(expression, var).map_err(IntegrationError::UnboundVariable)?;
+        let mut f = Integrand { function, evaluations: 0 };
+
+        if lower == upper
+        {
+            return Ok(Integral { value: 0., error: 0., evaluations: 0 });
+        }
+        // integrating backwards is the same as integrating forwards and flipping the sign
+        let (a, b, sign) = match lower < upper
+        {
+            true => (lower, upper, 1.),
+            false => (upper, lower, -1.),
+        };
+
+        let (value, error) = match self.method
+        {
+            Quadrature::AdaptiveSimpson => self.adaptive_simpson(&mut f, a, b)?,
+            Quadrature::GaussKronrod => self.gauss_kronrod(&mut f, a, b)?,
+        };
+
+        Ok(Integral { value: sign * value, error, evaluations: f.evaluations })
+    }
+
+    // splits intervals in half until simpson's rule on the halves agrees with simpson's rule on
+    // the whole interval
+    fn adaptive_simpson(
+        &self,
+        f: &mut Integrand,
+        a: f64,
+        b: f64,
+    ) -> Result<(f64, f64), IntegrationError>
+    {
+        struct Interval
+        {
+            a: f64,
+            b: f64,
+            fa: f64,
+            fm: f64,
+            fb: f64,
+            whole: f64,
+            tolerance: f64,
+        }
+
+        let simpson =
+            |a: f64, b: f64, fa: f64, fm: f64, fb: f64| (b - a) / 6. * (fa + 4. * fm + fb);
+
+        let (fa, fm, fb) = (f.eval(a)?, f.eval((a + b) / 2.)?, f.eval(b)?);
+        let mut intervals = vec![Interval {
+            a,
+            b,
+            fa,
+            fm,
+            fb,
+            whole: simpson(a, b, fa, fm, fb),
+            tolerance: self.tolerance,
+        }];
+        let (mut value, mut error) = (0., 0.);
+
+        while let Some(interval) = intervals.pop()
+        {
+            let Interval { a, b, fa, fm, fb, whole, tolerance } = interval;
+            let m = (a + b) / 2.;
+            let (lm, rm) = ((a + m) / 2., (m + b) / 2.);
+            let (flm, frm) = (f.eval(lm)?, f.eval(rm)?);
+
+            let left = simpson(a, m, fa, flm, fm);
+            let right = simpson(m, b, fm, frm, fb);
+            let difference = left + right - whole;
+
+            // the halves can't be split any further once their midpoints run into the endpoints
+            let exhausted = lm <= a || rm >= b;
+            if difference.abs() <= 15. * tolerance || exhausted
+            {
+                // richardson extrapolation of the two estimates
+                value += left + right + difference / 15.;
+                error += difference.abs() / 15.;
+            }
+            else if f.evaluations >= self.max_evaluations
+            {
+                value += left + right;
+                error += difference.abs();
+                intervals
+                    .iter()
+                    .for_each(|interval| error += interval.whole.abs());
+
+                return Err(IntegrationError::DidNotConverge { value, error });
+            }
+            else
+            {
+                let tolerance = tolerance / 2.;
+                intervals.push(Interval { a: m, b, fa: fm, fm: frm, fb, whole: right, tolerance });
+                intervals.push(Interval { a, b: m, fa, fm: flm, fb: fm, whole: left, tolerance });
+            }
+        }
+
+        Ok((value, error))
+    }
+
+    // 15 point gauss-kronrod on every interval, splitting whichever one has the largest error
+    // estimate until the total error is below the tolerance
+    fn gauss_kronrod(
+        &self,
+        f: &mut Integrand,
+        a: f64,
+        b: f64,
+    ) -> Result<(f64, f64), IntegrationError>
+    {
+        // (a, b, value, error)
+        let mut intervals = vec![gauss_kronrod_interval(f, a, b)?];
+
+        loop
+        {
+            let value: f64 = intervals.iter().map(|interval| interval.2).sum();
+            let error: f64 = intervals.iter().map(|interval| interval.3).sum();
+
+            if error <= self.tolerance
+            {
+                return Ok((value, error));
+            }
+
+            let (worst, &(a, b, _, _)) = intervals
+                .iter()
+                .enumerate()
+                .max_by(|(_, interval1), (_, interval2)| interval1.3.total_cmp(&interval2.3))
+                .unwrap();
+            let m = (a + b) / 2.;
+
+            if f.evaluations >= self.max_evaluations || m <= a || m >= b
+            {
+                return Err(IntegrationError::DidNotConverge { value, error });
+            }
+
+            intervals.swap_remove(worst);
+            intervals.push(gauss_kronrod_interval(f, a, m)?);
+            intervals.push(gauss_kronrod_interval(f, m, b)?);
+        }
+    }
+}
+
+fn gauss_kronrod_interval(
+    f: &mut Integrand,
+    a: f64,
+    b: f64,
+) -> Result<(f64, f64, f64, f64), IntegrationError>
+{
+    let center = (a + b) / 2.;
+    let half_width = (b - a) / 2.;
+
+    let f_center = f.eval(center)?;
+    let mut kronrod = KRONROD_WEIGHTS[0] * f_center;
+    let mut gauss = GAUSS_WEIGHTS[0] * f_center;
+
+    for i in 1..KRONROD_NODES.len()
+    {
+        let offset = half_width * KRONROD_NODES[i];
+        let pair = f.eval(center - offset)? + f.eval(center + offset)?;
+
+        kronrod += KRONROD_WEIGHTS[i] * pair;
+        if i % 2 == 0
+        {
+            gauss += GAUSS_WEIGHTS[i / 2] * pair;
+        }
+    }
+
+    let value = kronrod * half_width;
+    let error = ((kronrod - gauss) * half_width).abs();
+
+    Ok((a, b, value, error))
+}
+
+// integral of the expression over `var` from lower to upper with the default settings
+pub fn integrate(
+    expression: &str,
+    var: &str,
+    lower: f64,
+    upper: f64,
+) -> Result<Integral, IntegrationError>
+{
+    Integrator::new().integrate(&Expression::parse(expression), var, lower, upper)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_integrate()
+    {
+        for method in [Quadrature::AdaptiveSimpson, Quadrature::GaussKronrod]
+        {
+            let integrator = Integrator { method, ..Integrator::new() };
+            let integrate = |expr, lower, upper| {
+                integrator
+                    .integrate(&Expression::parse(expr), "x", lower, upper)
+                    .unwrap()
+            };
+
+            let integral = integrate("x^2", 0., 3.);
+            assert!((integral.value - 9.).abs() < 1e-10);
+            assert!(integral.error <= 1e-10);
+
+            let integral = integrate("sin(x)", 0., std::f64::consts::PI);
+            assert!((integral.value - 2.).abs() < 1e-9);
+
+            let integral = integrate("exp(0 - x^2)", 2., -2.);
+            assert!((integral.value + 1.7641627815248198).abs() < 1e-9);
+
+            assert_eq!(integrate("3", 1., 1.).value, 0.);
+        }
+
+        // gauss-kronrod never evaluates the endpoints, so singularities there are fine as long as
+        // the integral exists
+        let integral = integrate("1/sqrt(x)", "x", 0., 1.).unwrap();
+        assert!((integral.value - 2.).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_integration_errors()
+    {
+        assert_eq!(
+            integrate("x*y", "x", 0., 1.),
+            Err(IntegrationError::UnboundVariable("y".to_string()))
+        );
+
+        let simpson = Integrator { method: Quadrature::AdaptiveSimpson, ..Integrator::new() };
+        assert_eq!(
+            simpson.integrate(&Expression::parse("1/x"), "x", 0., 1.),
+            Err(IntegrationError::Singularity(0.))
+        );
+        assert_eq!(
+            simpson.integrate(&Expression::parse("1/(x - 0.5)"), "x", 0., 1.),
+            Err(IntegrationError::Singularity(0.5))
+        );
+
+        let limited = Integrator { max_evaluations: 100, ..Integrator::new() };
+        let result = limited.integrate(&Expression::parse("sin(1/x)"), "x", 0.001, 1.);
+        assert!(matches!(
+            result,
+            Err(IntegrationError::DidNotConverge { .. })
+        ));
+    }
+}
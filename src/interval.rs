@@ -0,0 +1,658 @@
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::fmt;
+
+use num_rational::BigRational;
+
+use crate::numeric::{array_description, Numeric};
+use crate::rational::parse_exact;
+use crate::solver::Expression;
+use crate::tokens::*;
+
+#[derive(Debug, PartialEq)]
+pub enum IntervalError
+{
+    // dividing by exactly zero. intervals that only contain zero give an unbounded result instead
+    DivisionByZero,
+    InvalidLiteral(String),
+    // the operation with no value anywhere in its interval, like "ln( [-2, -1] )"
+    Undefined(String),
+    // a statistic of one interval that needs several, like "stdev( [1, 2] )"
+    NotEnoughValues(String),
+    // a vector or matrix literal. intervals are written with brackets too, so it's described
+    // with spaces like "[ 1, 2 ]"
+    Unsupported(String),
+}
+
+// every real number from lo to hi. bounds are rounded outwards, so the interval always contains
+// the exact result of what it was computed from
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Interval
+{
+    lo: f64,
+    hi: f64,
+}
+
+#[derive(Clone, Copy)]
+enum Direction
+{
+    Down,
+    Up,
+}
+
+impl Interval
+{
+    pub fn new(lo: f64, hi: f64) -> Interval
+    {
+        assert!(lo <= hi, "empty interval: [{}, {}]", lo, hi);
+
+        // adding zero turns -0 into 0
+        Interval { lo: lo + 0., hi: hi + 0. }
+    }
+
+    pub fn point(val: f64) -> Interval
+    {
+        Interval::new(val, val)
+    }
+
+    pub fn entire() -> Interval
+    {
+        Interval::new(f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    // reads number literals, widened to the neighbouring floats unless they're exact
+    pub fn parse(literal: &str) -> Result<Interval, IntervalError>
+    {
+        let val: f64 = literal
+            .parse()
+            .map_err(|_| IntervalError::InvalidLiteral(literal.to_string()))?;
+
+        match parse_exact(literal).ok() == BigRational::from_float(val)
+        {
+            true => Ok(Interval::point(val)),
+            false => Ok(Interval::new(val.next_down(), val.next_up())),
+        }
+    }
+
+    pub fn lo(&self) -> f64
+    {
+        self.lo
+    }
+
+    pub fn hi(&self) -> f64
+    {
+        self.hi
+    }
+
+    pub fn width(&self) -> f64
+    {
+        rounded(self.hi - self.lo, f64::NAN, Direction::Up)
+    }
+
+    pub fn contains(&self, val: f64) -> bool
+    {
+        self.lo <= val && val <= self.hi
+    }
+
+    fn is_point(&self) -> bool
+    {
+        self.lo == self.hi
+    }
+
+    // the smallest interval holding every bound in `los` and `his`
+    fn hull(los: impl IntoIterator<Item = f64>, his: impl IntoIterator<Item = f64>) -> Interval
+    {
+        Interval::new(
+            los.into_iter().fold(f64::INFINITY, f64::min),
+            his.into_iter().fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+
+    // f of every pair of bounds, for operations that are monotonic in each operand
+    fn corners(&self, other: &Interval, f: impl Fn(f64, f64, Direction) -> f64) -> Interval
+    {
+        let pairs = [
+            (self.lo, other.lo),
+            (self.lo, other.hi),
+            (self.hi, other.lo),
+            (self.hi, other.hi),
+        ];
+
+        Interval::hull(
+            pairs.map(|(a, b)| f(a, b, Direction::Down)),
+            pairs.map(|(a, b)| f(a, b, Direction::Up)),
+        )
+    }
+
+    // sin and cos, which reach their maximum at `peak` + 2 pi k and their minimum half a turn later
+    fn periodic(&self, f: fn(f64) -> f64, peak: f64) -> Interval
+    {
+        if self.width() >= TAU
+        {
+            return Interval::new(-1., 1.);
+        }
+
+        let reaches = |offset: f64| {
+            let k = ((self.lo - offset) / TAU).ceil();
+            offset + k * TAU <= self.hi
+        };
+        let lo = match reaches(peak + PI)
+        {
+            true => -1.,
+            false => outward(f(self.lo), Direction::Down).min(outward(f(self.hi), Direction::Down)),
+        };
+        let hi = match reaches(peak)
+        {
+            true => 1.,
+            false => outward(f(self.lo), Direction::Up).max(outward(f(self.hi), Direction::Up)),
+        };
+
+        Interval::new(lo.max(-1.), hi.min(1.))
+    }
+
+    fn tan(&self) -> Interval
+    {
+        let k = ((self.lo - FRAC_PI_2) / PI).ceil();
+        let (lo, hi) = (self.lo.tan(), self.hi.tan());
+
+        // a pole that rounding hides still shows up as the tangent wrapping around
+        match self.width() >= PI || FRAC_PI_2 + k * PI <= self.hi || lo > hi
+        {
+            true => Interval::entire(),
+            false => Interval::new(outward(lo, Direction::Down), outward(hi, Direction::Up)),
+        }
+    }
+
+    fn factorial(&self, description: impl FnOnce() -> String) -> Result<Interval, IntervalError>
+    {
+        let (lo, hi) = (self.lo.max(0.).ceil(), self.hi.floor());
+        if lo > hi
+        {
+            return Err(IntervalError::Undefined(description()));
+        }
+
+        // the product is rounded at every step, so it's worked out once in each direction
+        let product = |n: f64, direction| {
+            let mut result = 1.;
+            let mut i = 2.;
+            while i <= n && result < f64::INFINITY
+            {
+                result = mul_rounded(result, i, direction);
+                i += 1.;
+            }
+            result
+        };
+
+        Ok(Interval::new(
+            product(lo, Direction::Down),
+            product(hi, Direction::Up),
+        ))
+    }
+
+    // self ^ n for a whole number n
+    fn powi(&self, n: f64) -> Result<Interval, IntervalError>
+    {
+        if n < 0.
+        {
+            return Interval::point(1.).div(&self.powi(-n)?);
+        }
+
+        let bound = |val: f64, direction| pow_rounded(val, n, direction);
+        let power = if n % 2. == 1. || self.lo >= 0.
+        {
+            Interval::new(
+                bound(self.lo, Direction::Down),
+                bound(self.hi, Direction::Up),
+            )
+        }
+        else if self.hi <= 0.
+        {
+            Interval::new(
+                bound(self.hi, Direction::Down),
+                bound(self.lo, Direction::Up),
+            )
+        }
+        else
+        {
+            let hi = bound(self.lo, Direction::Up).max(bound(self.hi, Direction::Up));
+            Interval::new(0., hi)
+        };
+
+        Ok(power)
+    }
+}
+
+// "[lo, hi]"
+impl fmt::Display for Interval
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+// `val` rounded in `direction`, given the error of rounding it to nearest. an error of NaN means
+// the rounding isn't known, so the result moves a float over either way
+fn rounded(val: f64, error: f64, direction: Direction) -> f64
+{
+    match direction
+    {
+        Direction::Down if error < 0. || error.is_nan() => val.next_down(),
+        Direction::Up if error > 0. || error.is_nan() => val.next_up(),
+        _ => val,
+    }
+}
+
+// for library functions like exp and sin, which are within a float of the exact result but aren't
+// always rounded to nearest
+fn outward(val: f64, direction: Direction) -> f64
+{
+    rounded(rounded(val, f64::NAN, direction), f64::NAN, direction)
+}
+
+// the exact error of each operation comes from an error-free transformation like TwoSum or an fma
+fn add_rounded(a: f64, b: f64, direction: Direction) -> f64
+{
+    let sum = a + b;
+    let b_part = sum - a;
+    let error = (a - (sum - b_part)) + (b - b_part);
+
+    rounded(sum, error, direction)
+}
+
+fn mul_rounded(a: f64, b: f64, direction: Direction) -> f64
+{
+    // zero times an unbounded end is still zero
+    if a == 0. || b == 0.
+    {
+        return 0.;
+    }
+
+    let product = a * b;
+    rounded(product, a.mul_add(b, -product), direction)
+}
+
+fn div_rounded(a: f64, b: f64, direction: Direction) -> f64
+{
+    let quotient = a / b;
+    let remainder = (-quotient).mul_add(b, a);
+
+    rounded(quotient, remainder * b.signum(), direction)
+}
+
+// val^n for a whole number n, by squaring with every product rounded the same way
+fn pow_rounded(val: f64, n: f64, direction: Direction) -> f64
+{
+    // past 2^53 the power overflows or underflows anyway, unless it's of 1 or 0
+    if n > 2f64.powi(53)
+    {
+        return outward(val.powf(n), direction);
+    }
+
+    // the magnitude has to be rounded the other way when the power comes out negative
+    let negative = val < 0. && n % 2. == 1.;
+    let magnitude_direction = match (negative, direction)
+    {
+        (true, Direction::Down) => Direction::Up,
+        (true, Direction::Up) => Direction::Down,
+        (false, direction) => direction,
+    };
+
+    let mut result = 1.;
+    let mut base = val.abs();
+    let mut n = n as u64;
+    while n > 0
+    {
+        if n % 2 == 1
+        {
+            result = mul_rounded(result, base, magnitude_direction);
+        }
+        base = mul_rounded(base, base, magnitude_direction);
+        n /= 2;
+    }
+
+    match negative
+    {
+        true => -result,
+        false => result,
+    }
+}
+
+fn sqrt_rounded(a: f64, direction: Direction) -> f64
+{
+    let root = a.sqrt();
+
+    rounded(root, (-root).mul_add(root, a), direction)
+}
+
+impl Numeric for Interval
+{
+    type Error = IntervalError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        Interval::parse(literal)
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        match self.is_point()
+        {
+            true => self.lo,
+            false => f64::NAN,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Interval::new(
+            add_rounded(self.lo, other.lo, Direction::Down),
+            add_rounded(self.hi, other.hi, Direction::Up),
+        ))
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Interval::new(
+            add_rounded(self.lo, -other.hi, Direction::Down),
+            add_rounded(self.hi, -other.lo, Direction::Up),
+        ))
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self.corners(other, mul_rounded))
+    }
+
+    // dividing by an interval that contains zero leaves out the zero, and gives an unbounded
+    // result. one that has zero inside it would split the result in two, so it gives every number
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        if other.lo == 0. && other.hi == 0.
+        {
+            return Err(IntervalError::DivisionByZero);
+        }
+
+        let (lo, hi) = (f64::NEG_INFINITY, f64::INFINITY);
+        let quotient = if other.lo > 0. || other.hi < 0.
+        {
+            self.corners(other, div_rounded)
+        }
+        else if self.contains(0.) || (other.lo < 0. && other.hi > 0.)
+        {
+            Interval::entire()
+        }
+        else if other.lo == 0.
+        {
+            match self.lo > 0.
+            {
+                true => Interval::new(div_rounded(self.lo, other.hi, Direction::Down), hi),
+                false => Interval::new(lo, div_rounded(self.hi, other.hi, Direction::Up)),
+            }
+        }
+        else
+        {
+            match self.lo > 0.
+            {
+                true => Interval::new(lo, div_rounded(self.lo, other.lo, Direction::Up)),
+                false => Interval::new(div_rounded(self.hi, other.lo, Direction::Down), hi),
+            }
+        };
+
+        Ok(quotient)
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let quotient = self.div(other)?;
+
+        Ok(Interval::new(quotient.lo.floor(), quotient.hi.floor()))
+    }
+
+    // the remainder takes the sign of the divisor and is smaller than it, unless the whole
+    // interval has the same quotient and the remainder can be worked out from it
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let quotient = self.int_div(other)?;
+        let bounds = Interval::new(other.lo.min(0.), other.hi.max(0.));
+
+        match quotient.is_point() && quotient.lo.is_finite()
+        {
+            true =>
+            {
+                let remainder = self.sub(&other.mul(&quotient)?)?;
+                Ok(Interval::new(
+                    remainder.lo.max(bounds.lo),
+                    remainder.hi.min(bounds.hi),
+                ))
+            },
+            false => Ok(bounds),
+        }
+    }
+
+    // bases are cut down to the numbers powers are defined for, so only whole powers take
+    // negative bases
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        if other.is_point() && other.lo.fract() == 0.
+        {
+            return self.powi(other.lo);
+        }
+        if self.hi < 0.
+        {
+            return Err(IntervalError::Undefined(format!("{} ^ {}", self, other)));
+        }
+
+        // x^y is monotonic in x and in y once x isn't negative
+        let base = Interval::new(self.lo.max(0.), self.hi);
+        let power = base.corners(other, |a, b, direction| match a == 0. || b == 0.
+        {
+            true => a.powf(b),
+            false => outward(a.powf(b), direction),
+        });
+
+        Ok(power)
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{}( {} )", func.name(), self);
+
+        match func
+        {
+            Function::Sqrt if self.hi < 0. => Err(IntervalError::Undefined(description())),
+            Function::Sqrt => Ok(Interval::new(
+                sqrt_rounded(self.lo.max(0.), Direction::Down),
+                sqrt_rounded(self.hi, Direction::Up),
+            )),
+            Function::Exp => Ok(Interval::new(
+                outward(self.lo.exp(), Direction::Down).max(0.),
+                outward(self.hi.exp(), Direction::Up),
+            )),
+            Function::Ln if self.hi <= 0. => Err(IntervalError::Undefined(description())),
+            Function::Ln => Ok(Interval::new(
+                match self.lo > 0.
+                {
+                    true => outward(self.lo.ln(), Direction::Down),
+                    false => f64::NEG_INFINITY,
+                },
+                outward(self.hi.ln(), Direction::Up),
+            )),
+            Function::Sin => Ok(self.periodic(f64::sin, FRAC_PI_2)),
+            Function::Cos => Ok(self.periodic(f64::cos, 0.)),
+            Function::Tan => Ok(self.tan()),
+            Function::Factorial => self.factorial(description),
+            Function::Negate => Ok(Interval::new(-self.hi, -self.lo)),
+            Function::Det => Ok(*self),
+            Function::Inverse => Interval::point(1.).div(self),
+            Function::Aggregate(aggregate) if aggregate.min_len() > 1 =>
+            {
+                Err(IntervalError::NotEnoughValues(description()))
+            },
+            Function::Aggregate(_) => Ok(*self),
+            Function::Sum | Function::Product =>
+            {
+                unreachable!("sums are evaluated with their bound variable")
+            },
+        }
+    }
+
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Err(IntervalError::Unsupported(array_description(&elements)))
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(IntervalError::Undefined(description))
+    }
+}
+
+impl Expression
+{
+    pub fn eval_interval(&self) -> Result<Interval, IntervalError>
+    {
+        self.eval_numeric()
+    }
+
+    // an interval that contains the value of the expression for every choice of variables from
+    // their intervals
+    pub fn eval_interval_with(
+        &self,
+        vars: &HashMap<String, Interval>,
+    ) -> Result<Interval, IntervalError>
+    {
+        self.eval_numeric_with(vars)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn eval(expr: &str, x: Interval) -> Result<Interval, IntervalError>
+    {
+        Expression::parse(expr).eval_interval_with(&HashMap::from([("x".to_string(), x)]))
+    }
+
+    #[test]
+    fn test_eval_interval()
+    {
+        let x = Interval::new(1., 2.);
+        assert_eq!(eval("x^2 - 2x", x), Ok(Interval::new(-3., 2.)));
+        assert_eq!(eval("x // 0.5 + x % 0.75", x), Ok(Interval::new(2., 4.75)));
+        assert_eq!(eval("(x + 3) % 3", x), Ok(Interval::new(1., 2.)));
+        assert_eq!(eval("(x + 1) % 3", x), Ok(Interval::new(0., 3.)));
+        assert_eq!(
+            eval("sqrt(x - 1) + (x + 3)!", x),
+            Ok(Interval::new(24., 121.))
+        );
+
+        // 0.1 and 0.2 can't be written exactly, but the interval still holds 0.3
+        let result = Expression::parse("0.1 + 0.2").eval_interval().unwrap();
+        let exact = BigRational::new(3.into(), 10.into());
+        assert!(BigRational::from_float(result.lo()).unwrap() < exact);
+        assert!(BigRational::from_float(result.hi()).unwrap() > exact);
+        assert!(result.width() < 2e-16);
+    }
+
+    #[test]
+    fn test_interval_division()
+    {
+        let div = |lo, hi| eval("1 / x", Interval::new(lo, hi)).unwrap().to_string();
+
+        assert_eq!(div(2., 4.), "[0.25, 0.5]");
+        assert_eq!(div(0., 2.), "[0.5, inf]");
+        assert_eq!(div(-4., 0.), "[-inf, -0.25]");
+        assert_eq!(div(-1., 2.), "[-inf, inf]");
+        assert_eq!(
+            eval("(x - 1) / x", Interval::new(0., 1.)),
+            Ok(Interval::entire())
+        );
+        assert_eq!(
+            eval("1 / (x - x)", Interval::point(3.)),
+            Err(IntervalError::DivisionByZero)
+        );
+        assert_eq!(
+            eval("x // (x - 3)", Interval::new(1., 2.)),
+            Ok(Interval::new(-2., -1.))
+        );
+
+        let third = Expression::parse("1 / 3").eval_interval().unwrap();
+        let exact = BigRational::new(1.into(), 3.into());
+        assert!(BigRational::from_float(third.lo()).unwrap() < exact);
+        assert!(BigRational::from_float(third.hi()).unwrap() > exact);
+        assert_eq!(third.hi(), third.lo().next_up());
+    }
+
+    #[test]
+    fn test_interval_powers()
+    {
+        let y = Interval::new(-1., 2.);
+        let vars = HashMap::from([
+            ("x".to_string(), Interval::new(2., 4.)),
+            ("y".to_string(), y),
+        ]);
+        let result = Expression::parse("x^y").eval_interval_with(&vars).unwrap();
+        assert!(result.contains(0.25) && result.contains(16.) && result.width() < 15.75 + 1e-12);
+
+        let x = Interval::new(-3., 2.);
+        assert_eq!(eval("x^2", x).map(|val| val.lo()), Ok(0.));
+        assert!(eval("x^3", x).unwrap().contains(-27.));
+        assert_eq!(eval("x^-1", x), Ok(Interval::entire()));
+        assert_eq!(eval("x^0.5", x).map(|val| val.lo()), Ok(0.));
+        assert_eq!(
+            eval("(x - 3)^0.5", x),
+            Err(IntervalError::Undefined(
+                "[-6, -1] ^ [0.5, 0.5]".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_interval_functions()
+    {
+        let x = Interval::new(0., 3.);
+        assert_eq!(eval("sin(x)", x).map(|val| val.hi()), Ok(1.));
+        assert_eq!(eval("cos(x + 1)", x).map(|val| val.lo()), Ok(-1.));
+        assert_eq!(eval("sin(x * 10)", x), Ok(Interval::new(-1., 1.)));
+        assert_eq!(eval("tan(x)", x), Ok(Interval::entire()));
+        assert_eq!(
+            eval("ln(x - 1)", x).map(|val| val.lo()),
+            Ok(f64::NEG_INFINITY)
+        );
+        assert_eq!(
+            eval("ln(x - 3)", x),
+            Err(IntervalError::Undefined("ln( [-3, 0] )".to_string()))
+        );
+        assert_eq!(
+            eval("((x + 0.5) / 8)!", x),
+            Err(IntervalError::Undefined(
+                "factorial( [0.0625, 0.4375] )".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_interval_encloses_points()
+    {
+        let tree = Expression::parse("sin(x) * exp(y) - x^y / (1 + y) + tan(x / 2) * ln(y)");
+        let (x, y) = (Interval::new(0.5, 2.), Interval::new(0.1, 1.5));
+        let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+        let result = tree.eval_interval_with(&vars).unwrap();
+
+        for i in 0..=20
+        {
+            for j in 0..=20
+            {
+                let point = HashMap::from([
+                    ("x".to_string(), 0.5 + 1.5 * i as f64 / 20.),
+                    ("y".to_string(), 0.1 + 1.4 * j as f64 / 20.),
+                ]);
+                let val = tree.eval_with(&point);
+                assert!(result.contains(val), "{} isn't in {}", val, result);
+            }
+        }
+    }
+}
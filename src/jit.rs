@@ -0,0 +1,440 @@
+#[cfg(not(feature = "jit"))]
+use std::cell::RefCell;
+
+#[cfg(not(feature = "jit"))]
+use crate::bytecode::{Program, Vm};
+use crate::solver::Expression;
+
+// an expression compiled to native code with cranelift when the `jit` feature is enabled. without
+// the feature it falls back to running the bytecode program, so callers don't have to care which
+// one they got
+pub struct JitFunction
+{
+    variables: Vec<String>,
+
+    #[cfg(feature = "jit")]
+    module: Option<cranelift_jit::JITModule>,
+    #[cfg(feature = "jit")]
+    function: extern "C" fn(*const f64) -> f64,
+
+    #[cfg(not(feature = "jit"))]
+    program: Program,
+    #[cfg(not(feature = "jit"))]
+    vm: RefCell<Vm>,
+}
+
+impl JitFunction
+{
+    // names of the variables in the order `call` expects their values
+    pub fn variables(&self) -> &[String]
+    {
+        &self.variables
+    }
+
+    pub fn call(&self, variables: &[f64]) -> f64
+    {
+        assert_eq!(
+            variables.len(),
+            self.variables.len(),
+            "wrong number of variable values for jit function"
+        );
+
+        #[cfg(feature = "jit")]
+        return (self.function)(variables.as_ptr());
+
+        #[cfg(not(feature = "jit"))]
+        return self.vm.borrow_mut().run(&self.program, variables);
+    }
+}
+
+impl Expression
+{
+    #[cfg(not(feature = "jit"))]
+    pub fn jit(&self) -> JitFunction
+    {
+        let program = self.compile();
+
+        JitFunction {
+            variables: program.variables.clone(),
+            program,
+            vm: RefCell::new(Vm::new()),
+        }
+    }
+
+    #[cfg(feature = "jit")]
+    pub fn jit(&self) -> JitFunction
+    {
+        native::compile(self)
+    }
+}
+
+#[cfg(feature = "jit")]
+impl Drop for JitFunction
+{
+    fn drop(&mut self)
+    {
+        if let Some(module) = self.module.take()
+        {
+            // SAFETY: `function` points into this module and can't be called after the drop
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+#[cfg(feature = "jit")]
+mod native
+{
+    use cranelift_codegen::ir::condcodes::FloatCC;
+    use cranelift_codegen::ir::{
+        types, AbiParam, Block, InstBuilder, MemFlagsData, UserFuncName, Value,
+    };
+    use cranelift_codegen::settings::{self, Configurable};
+    use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+    use cranelift_jit::{JITBuilder, JITModule};
+    use cranelift_module::{default_libcall_names, Linkage, Module};
+
+    use super::JitFunction;
+    use crate::bound::{combining, identity};
+    use crate::bytecode::Instruction;
+    use crate::solver::Expression;
+    use crate::tokens::{Aggregate, Function, Operation};
+
+    // cranelift has no instruction for pow or remainders, so the generated code calls back into
+    // rust for them
+    extern "C" fn powf(val1: f64, val2: f64) -> f64
+    {
+        Expression::eval_binary_op(val1, Operation::Exponentiation, val2)
+    }
+
+    extern "C" fn remainder(val1: f64, val2: f64) -> f64
+    {
+        Expression::eval_binary_op(val1, Operation::Remainder, val2)
+    }
+
+    // same for everything but sqrt and negation. the function is passed as its index in FUNCTIONS
+    const FUNCTIONS: [Function; 15] = [
+        Function::Sqrt,
+        Function::Exp,
+        Function::Ln,
+        Function::Sin,
+        Function::Cos,
+        Function::Tan,
+        Function::Factorial,
+        Function::Det,
+        Function::Inverse,
+        Function::Aggregate(Aggregate::Sum),
+        Function::Aggregate(Aggregate::Mean),
+        Function::Aggregate(Aggregate::Median),
+        Function::Aggregate(Aggregate::Stdev),
+        Function::Aggregate(Aggregate::Min),
+        Function::Aggregate(Aggregate::Max),
+    ];
+
+    extern "C" fn call_function(index: i64, val: f64) -> f64
+    {
+        Expression::eval_function(FUNCTIONS[index as usize], val)
+    }
+
+    // a loop whose body is being generated
+    struct Loop
+    {
+        func: Function,
+        // index of the instruction after the body
+        end: usize,
+        header: Block,
+        exit: Block,
+        counter: Value,
+        total: Value,
+        valid: Value,
+    }
+
+    // whether a bound is a whole number, which rules out infinities and NaN too
+    fn is_whole(builder: &mut FunctionBuilder, val: Value) -> Value
+    {
+        let floor = builder.ins().floor(val);
+        let fract = builder.ins().fsub(val, floor);
+        let zero = builder.ins().f64const(0.);
+        builder.ins().fcmp(FloatCC::Equal, fract, zero)
+    }
+
+    // jumps into a loop that counts from `lower` to `upper`, and leaves the builder in its body
+    fn start_loop(
+        builder: &mut FunctionBuilder,
+        func: Function,
+        lower: Value,
+        upper: Value,
+        end: usize,
+    ) -> Loop
+    {
+        let lower_whole = is_whole(builder, lower);
+        let upper_whole = is_whole(builder, upper);
+        let valid = builder.ins().band(lower_whole, upper_whole);
+        let identity = builder.ins().f64const(identity(func));
+
+        let header = builder.create_block();
+        let counter = builder.append_block_param(header, types::F64);
+        let total = builder.append_block_param(header, types::F64);
+        let body = builder.create_block();
+        let exit = builder.create_block();
+        builder.append_block_param(exit, types::F64);
+
+        builder.ins().jump(header, &[lower.into(), identity.into()]);
+
+        builder.switch_to_block(header);
+        let in_range = builder.ins().fcmp(FloatCC::LessThanOrEqual, counter, upper);
+        let running = builder.ins().band(in_range, valid);
+        builder
+            .ins()
+            .brif(running, body, &[], exit, &[total.into()]);
+
+        builder.switch_to_block(body);
+
+        Loop { func, end, header, exit, counter, total, valid }
+    }
+
+    // adds the term to the total and goes around again, then continues after the loop with its
+    // value. bounds that aren't whole numbers give NaN
+    fn finish_loop(builder: &mut FunctionBuilder, ctx: Loop, term: Value) -> Value
+    {
+        let total = match combining(ctx.func)
+        {
+            Operation::Multiplication => builder.ins().fmul(ctx.total, term),
+            _ => builder.ins().fadd(ctx.total, term),
+        };
+        let one = builder.ins().f64const(1.);
+        let counter = builder.ins().fadd(ctx.counter, one);
+        builder
+            .ins()
+            .jump(ctx.header, &[counter.into(), total.into()]);
+
+        builder.switch_to_block(ctx.exit);
+        let total = builder.block_params(ctx.exit)[0];
+        let nan = builder.ins().f64const(f64::NAN);
+        builder.ins().select(ctx.valid, total, nan)
+    }
+
+    pub fn compile(expression: &Expression) -> JitFunction
+    {
+        let program = expression.compile();
+
+        let mut flag_builder = settings::builder();
+        flag_builder.set("use_colocated_libcalls", "false").unwrap();
+        flag_builder.set("is_pic", "false").unwrap();
+        flag_builder.set("opt_level", "speed").unwrap();
+        let isa = cranelift_native::builder()
+            .unwrap_or_else(|msg| panic!("host machine is not supported by the jit: {}", msg))
+            .finish(settings::Flags::new(flag_builder))
+            .expect("error creating jit target");
+
+        let mut jit_builder = JITBuilder::with_isa(isa, default_libcall_names());
+        jit_builder.symbol("math_evaluator_powf", powf as *const u8);
+        jit_builder.symbol("math_evaluator_remainder", remainder as *const u8);
+        jit_builder.symbol("math_evaluator_call_function", call_function as *const u8);
+        let mut module = JITModule::new(jit_builder);
+
+        let pointer_type = module.target_config().pointer_type();
+
+        let mut powf_signature = module.make_signature();
+        powf_signature.params.push(AbiParam::new(types::F64));
+        powf_signature.params.push(AbiParam::new(types::F64));
+        powf_signature.returns.push(AbiParam::new(types::F64));
+        let powf_id = module
+            .declare_function("math_evaluator_powf", Linkage::Import, &powf_signature)
+            .expect("error declaring powf for the jit");
+        let remainder_id = module
+            .declare_function("math_evaluator_remainder", Linkage::Import, &powf_signature)
+            .expect("error declaring remainder for the jit");
+
+        let mut call_function_signature = module.make_signature();
+        call_function_signature
+            .params
+            .push(AbiParam::new(types::I64));
+        call_function_signature
+            .params
+            .push(AbiParam::new(types::F64));
+        call_function_signature
+            .returns
+            .push(AbiParam::new(types::F64));
+        let call_function_id = module
+            .declare_function(
+                "math_evaluator_call_function",
+                Linkage::Import,
+                &call_function_signature,
+            )
+            .expect("error declaring function calls for the jit");
+
+        let mut signature = module.make_signature();
+        signature.params.push(AbiParam::new(pointer_type));
+        signature.returns.push(AbiParam::new(types::F64));
+        let function_id = module
+            .declare_function("expression", Linkage::Local, &signature)
+            .expect("error declaring jit function");
+
+        let mut context = module.make_context();
+        let mut function_context = FunctionBuilderContext::new();
+        context.func.signature = signature;
+        context.func.name = UserFuncName::user(0, function_id.as_u32());
+
+        {
+            let mut builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+            let powf_ref = module.declare_func_in_func(powf_id, builder.func);
+            let remainder_ref = module.declare_func_in_func(remainder_id, builder.func);
+            let call_function_ref = module.declare_func_in_func(call_function_id, builder.func);
+
+            let block = builder.create_block();
+            builder.append_block_params_for_function_params(block);
+            builder.switch_to_block(block);
+            let variables = builder.block_params(block)[0];
+
+            let mut stack: Vec<Value> = Vec::with_capacity(program.stack_size);
+            let mut loops: Vec<Loop> = Vec::new();
+            for (index, instruction) in program.instructions.iter().enumerate()
+            {
+                // the body of the innermost loop ends here, so its term goes into the total and
+                // the counter moves on
+                while loops.last().is_some_and(|ctx| ctx.end == index)
+                {
+                    let ctx = loops.pop().unwrap();
+                    let term = stack.pop().expect("tried to pop value off empty stack");
+                    stack.push(finish_loop(&mut builder, ctx, term));
+                }
+
+                match *instruction
+                {
+                    Instruction::Push(val) => stack.push(builder.ins().f64const(val)),
+                    Instruction::Counter(depth) => stack.push(loops[depth].counter),
+                    Instruction::Loop { func, len } =>
+                    {
+                        let upper = stack.pop().expect("tried to pop value off empty stack");
+                        let lower = stack.pop().expect("tried to pop value off empty stack");
+                        let end = index + 1 + len;
+                        loops.push(start_loop(&mut builder, func, lower, upper, end));
+                    },
+                    Instruction::Load(slot) =>
+                    {
+                        let offset = (slot * std::mem::size_of::<f64>()) as i32;
+                        let val = builder.ins().load(
+                            types::F64,
+                            MemFlagsData::trusted(),
+                            variables,
+                            offset,
+                        );
+                        stack.push(val);
+                    },
+                    Instruction::Apply(op) =>
+                    {
+                        let val2 = stack.pop().expect("tried to pop value off empty stack");
+                        let val1 = stack.pop().expect("tried to pop value off empty stack");
+
+                        let val = match op
+                        {
+                            Operation::Addition => builder.ins().fadd(val1, val2),
+                            Operation::Subtraction => builder.ins().fsub(val1, val2),
+                            Operation::Multiplication | Operation::Dot =>
+                            {
+                                builder.ins().fmul(val1, val2)
+                            },
+                            Operation::Division => builder.ins().fdiv(val1, val2),
+                            Operation::IntegerDivision =>
+                            {
+                                let val = builder.ins().fdiv(val1, val2);
+                                builder.ins().floor(val)
+                            },
+                            Operation::Remainder =>
+                            {
+                                let call = builder.ins().call(remainder_ref, &[val1, val2]);
+                                builder.inst_results(call)[0]
+                            },
+                            Operation::Exponentiation =>
+                            {
+                                let call = builder.ins().call(powf_ref, &[val1, val2]);
+                                builder.inst_results(call)[0]
+                            },
+                        };
+                        stack.push(val);
+                    },
+                    Instruction::Call(func) =>
+                    {
+                        let arg = stack.pop().expect("tried to pop value off empty stack");
+
+                        let val = match func
+                        {
+                            Function::Sqrt => builder.ins().sqrt(arg),
+                            Function::Negate => builder.ins().fneg(arg),
+                            _ =>
+                            {
+                                let index = FUNCTIONS.iter().position(|f| *f == func).unwrap();
+                                let index = builder.ins().iconst(types::I64, index as i64);
+                                let call = builder.ins().call(call_function_ref, &[index, arg]);
+                                builder.inst_results(call)[0]
+                            },
+                        };
+                        stack.push(val);
+                    },
+                }
+            }
+
+            while let Some(ctx) = loops.pop()
+            {
+                let term = stack.pop().expect("tried to pop value off empty stack");
+                stack.push(finish_loop(&mut builder, ctx, term));
+            }
+
+            let result = stack.pop().expect("program left no result on the stack");
+            builder.ins().return_(&[result]);
+            builder.seal_all_blocks();
+            builder.finalize(module.target_config());
+        }
+
+        module
+            .define_function(function_id, &mut context)
+            .expect("error compiling jit function");
+        module.clear_context(&mut context);
+        module
+            .finalize_definitions()
+            .expect("error linking jit function");
+
+        let code = module.get_finalized_function(function_id);
+        // SAFETY: the function was declared with this exact signature above
+        let function =
+            unsafe { std::mem::transmute::<*const u8, extern "C" fn(*const f64) -> f64>(code) };
+
+        JitFunction { variables: program.variables, module: Some(module), function }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_jit()
+    {
+        let tree = Expression::parse(
+            "(4*x-(8-y))/(5^.4*x) + 5^(.2-y/3) - x^y + sqrt(x)*exp(y) - ln(y)/tan(x) + sin(x)^cos(y)",
+        );
+        let function = tree.jit();
+
+        assert_eq!(function.variables(), ["x", "y"]);
+
+        for (x, y) in [(1., 2.), (-3.5, 0.), (10., 7.25), (0., 0.)]
+        {
+            let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+            let expected = tree.eval_with(&vars);
+            let result = function.call(&[x, y]);
+
+            assert!(result == expected || (result.is_nan() && expected.is_nan()));
+        }
+
+        assert_eq!(Expression::parse("2*4+6").jit().call(&[]), 14.);
+        assert_eq!(Expression::parse("x // 2 + x % 3 + 4!").jit().call(&[-7.]), 22.);
+
+        let function = Expression::parse("prod(i, 1, n, sum(j, i, n, x*j))").jit();
+        assert_eq!(function.call(&[3., 2.]), 12. * 10. * 6.);
+        assert_eq!(function.call(&[0., 2.]), 1.);
+        assert!(function.call(&[2.5, 2.]).is_nan());
+    }
+}
@@ -1,6 +1,29 @@
-mod solver;
-mod tokens;
+pub mod batch;
+pub mod bound;
+pub mod bytecode;
+pub mod complex;
+pub mod cse;
+pub mod decimal;
+pub mod derivative;
+pub mod dual;
+pub mod equation;
+pub mod fold;
+pub mod integer;
+pub mod integrate;
+pub mod interval;
+pub mod jit;
+pub mod matrix;
+pub mod numeric;
+pub mod parallel;
+pub mod rational;
+pub mod rewrite;
+pub mod roots;
+pub mod solver;
+pub mod tokens;
+pub mod uncertainty;
+pub mod units;
 
+use matrix::Array;
 use rand::{thread_rng, Rng};
 use solver::*;
 use tokens::*;
@@ -12,15 +35,42 @@ pub fn evaluate(expr: &str) -> f64
 
     let expr_tree = Expression::from_postfix_tokens(postfix_tokens);
 
+    // floats have no vectors or matrices, so those are worked out as arrays, where only a plain
+    // number result has a value here
+    if expr_tree.has_arrays()
+    {
+        return match expr_tree.eval_matrix()
+        {
+            Ok(Array::Scalar(val)) => val,
+            _ => f64::NAN,
+        };
+    }
+
     // TODO: add parameter to switch between simplify (show work) and eval (just get final result)
     let r1 = expr_tree.eval();
     let r2 = expr_tree.simplify();
 
-    assert_eq!(r1, r2);
+    // NaN is never equal to itself
+    assert!(
+        r1 == r2 || (r1.is_nan() && r2.is_nan()),
+        "eval gave {} but simplify gave {}",
+        r1,
+        r2
+    );
 
     r1
 }
 
+pub fn evaluate_parallel(expr: &str) -> f64
+{
+    let infix_tokens = tokenize(expr);
+    let postfix_tokens = shunting_yard(infix_tokens);
+
+    let expr_tree = Expression::from_postfix_tokens(postfix_tokens);
+
+    expr_tree.eval_parallel()
+}
+
 pub fn generate_expression(terms: usize) -> String
 {
     let (min, max) = (0f32, 10f32);
@@ -46,7 +96,7 @@ pub fn generate_expression(terms: usize) -> String
         op.to_string()
     }
 
-    for i in 0..terms - 1
+    for _ in 0..terms - 1
     {
         ops.push(get_rand_operator());
         nums.push(rng.gen_range(min..max));
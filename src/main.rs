@@ -31,17 +31,23 @@ fn main()
 
 // TODO: implement
 //
-// multi-threaded solving of large expressions (on gpu?)
+// solving of large expressions on the gpu
 //      find multiple leaf nodes at once and solve simultaneously
-//      or maybe find N (where N is the number of cores/threads) nodes in the expression tree where none are childen of each other and
-//      solve the subtrees independently
 //      if translating to gpu can maybe queue up operations and operands then push to gpu and evaluate
 //
 //
 // Formal syntax specification:
 //
 //
-// expression = number (bin_op number)?
+// expression = value (bin_op value)?
 // expression = "(" expression ")"
+// expression = function "(" expression ("," expression)* ")"
+// expression = expression "!"
+// expression = "-" expression
+// expression = "[" (expression ("," expression)*)? "]"
+// value = number | number "±" number | variable
 // number = "-"? digit+ ("."digit+)?
-// bin_op = "+" | "-" | "*" | "/"
+// variable = (letter | "_") (letter | digit | "_")*
+// bin_op = "+" | "-" | "*" | "/" | "//" | "%" | "."
+// function = "sqrt" | "exp" | "ln" | "sin" | "cos" | "tan" | "sum" | "prod" | "det" | "inv"
+// function = "mean" | "median" | "stdev" | "min" | "max"
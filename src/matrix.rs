@@ -0,0 +1,756 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::numeric::Numeric;
+use crate::solver::Expression;
+use crate::tokens::*;
+
+#[derive(Debug, PartialEq)]
+pub enum MatrixError
+{
+    InvalidLiteral(String),
+    // the shapes of values that don't fit together, like "[3] + [2]" for vectors of length 3 and
+    // 2, or "[2x3] * [2x3]" for two matrices with 2 rows and 3 columns
+    Shape(String),
+    // the inverse of a matrix whose determinant is zero
+    Singular,
+    Undefined(String),
+    // a statistic of a list that has too few values for it, like "mean( [] )" for an empty list
+    // or "stdev( [1] )"
+    NotEnoughValues(String),
+}
+
+// a plain number, a vector like "[1, 2, 3]", or a matrix like "[[1, 2], [3, 4]]". matrices are
+// stored as their rows, which all have the same length
+#[derive(Debug, PartialEq, Clone)]
+pub enum Array
+{
+    Scalar(f64),
+    Vector(Vec<f64>),
+    Matrix(Vec<Vec<f64>>),
+}
+
+impl Array
+{
+    // how the value is described in errors: plain numbers as themselves, vectors by their length
+    // like "[3]" or "[]" when empty, and matrices by their rows and columns like "[2x3]"
+    pub fn shape(&self) -> String
+    {
+        match self
+        {
+            Array::Scalar(val) => val.to_string(),
+            Array::Vector(entries) if entries.is_empty() => "[]".to_string(),
+            Array::Vector(entries) => format!("[{}]", entries.len()),
+            Array::Matrix(rows) =>
+            {
+                let (height, width) = dimensions(rows);
+                format!("[{}x{}]", height, width)
+            },
+        }
+    }
+
+    // every entry, row by row for a matrix
+    fn entries(&self) -> Vec<f64>
+    {
+        match self
+        {
+            Array::Scalar(val) => vec![*val],
+            Array::Vector(entries) => entries.clone(),
+            Array::Matrix(rows) => rows.concat(),
+        }
+    }
+
+    fn map(&self, f: impl Fn(f64) -> f64) -> Array
+    {
+        let map_row = |row: &Vec<f64>| row.iter().map(|val| f(*val)).collect();
+
+        match self
+        {
+            Array::Scalar(val) => Array::Scalar(f(*val)),
+            Array::Vector(entries) => Array::Vector(map_row(entries)),
+            Array::Matrix(rows) => Array::Matrix(rows.iter().map(map_row).collect()),
+        }
+    }
+
+    fn mismatch(&self, op: Operation, other: &Array) -> MatrixError
+    {
+        MatrixError::Shape(format!("{} {} {}", self.shape(), op.str(), other.shape()))
+    }
+
+    // `op` applied to each pair of entries in the same place. a plain number is paired with every
+    // entry of the other side, so "[1, 2, 3] * 2" doubles each one
+    fn elementwise(&self, op: Operation, other: &Array) -> Result<Array, MatrixError>
+    {
+        let apply = |val1: f64, val2: f64| Expression::eval_binary_op(val1, op, val2);
+        let zip = |row1: &Vec<f64>, row2: &Vec<f64>| {
+            row1.iter()
+                .zip(row2)
+                .map(|(val1, val2)| apply(*val1, *val2))
+                .collect()
+        };
+
+        match (self, other)
+        {
+            (Array::Scalar(val1), Array::Scalar(val2)) => Ok(Array::Scalar(apply(*val1, *val2))),
+            (Array::Scalar(val1), _) => Ok(other.map(|val2| apply(*val1, val2))),
+            (_, Array::Scalar(val2)) => Ok(self.map(|val1| apply(val1, *val2))),
+            (Array::Vector(entries1), Array::Vector(entries2))
+                if entries1.len() == entries2.len() =>
+            {
+                Ok(Array::Vector(zip(entries1, entries2)))
+            },
+            (Array::Matrix(rows1), Array::Matrix(rows2))
+                if dimensions(rows1) == dimensions(rows2) =>
+            {
+                Ok(Array::Matrix(
+                    rows1
+                        .iter()
+                        .zip(rows2)
+                        .map(|(row1, row2)| zip(row1, row2))
+                        .collect(),
+                ))
+            },
+            _ => Err(self.mismatch(op, other)),
+        }
+    }
+
+    // a square matrix multiplied by itself `exponent` times, using the inverse for negative
+    // powers
+    fn matrix_pow(&self, rows: &[Vec<f64>], exponent: f64) -> Result<Array, MatrixError>
+    {
+        let (height, width) = dimensions(rows);
+        if height != width
+        {
+            return Err(self.mismatch(Operation::Exponentiation, &Array::Scalar(exponent)));
+        }
+        if exponent.fract() != 0.
+        {
+            return Err(MatrixError::Undefined(format!(
+                "{} ^ {}",
+                self.shape(),
+                exponent
+            )));
+        }
+
+        let mut base = match exponent < 0.
+        {
+            true => invert(rows).ok_or(MatrixError::Singular)?,
+            false => rows.to_vec(),
+        };
+        let mut result = identity(height);
+
+        // squaring, so huge powers only take a few products
+        let mut remaining = exponent.abs() as u64;
+        while remaining > 0
+        {
+            if remaining % 2 == 1
+            {
+                result = product(&result, &base);
+            }
+            remaining /= 2;
+            if remaining > 0
+            {
+                base = product(&base, &base);
+            }
+        }
+
+        Ok(Array::Matrix(result))
+    }
+}
+
+// "[1, 2, 3]" and "[[1, 2], [3, 4]]", the same way they're written
+impl fmt::Display for Array
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let row_string = |row: &Vec<f64>| {
+            let entries: Vec<String> = row.iter().map(|val| val.to_string()).collect();
+            format!("[{}]", entries.join(", "))
+        };
+
+        match self
+        {
+            Array::Scalar(val) => write!(f, "{}", val),
+            Array::Vector(entries) => write!(f, "{}", row_string(entries)),
+            Array::Matrix(rows) =>
+            {
+                let rows: Vec<String> = rows.iter().map(row_string).collect();
+                write!(f, "[{}]", rows.join(", "))
+            },
+        }
+    }
+}
+
+// number of rows and columns
+fn dimensions(rows: &[Vec<f64>]) -> (usize, usize)
+{
+    (rows.len(), rows.first().map_or(0, Vec::len))
+}
+
+fn identity(size: usize) -> Vec<Vec<f64>>
+{
+    let mut rows = vec![vec![0.; size]; size];
+    for (i, row) in rows.iter_mut().enumerate()
+    {
+        row[i] = 1.;
+    }
+
+    rows
+}
+
+// the matrix product, for a left side with as many columns as the right side has rows
+fn product(rows1: &[Vec<f64>], rows2: &[Vec<f64>]) -> Vec<Vec<f64>>
+{
+    let (_, width) = dimensions(rows2);
+
+    rows1
+        .iter()
+        .map(|row| {
+            (0..width)
+                .map(|j| row.iter().zip(rows2).map(|(val, row2)| val * row2[j]).sum())
+                .collect()
+        })
+        .collect()
+}
+
+// fraction-free gauss-jordan elimination of a square matrix with the identity beside it. every
+// division comes out even when the entries are whole numbers, so those get an exact determinant
+// and an inverse that's only rounded once per entry. gives the determinant and the inverse, or
+// None for the inverse of a singular matrix
+fn eliminate(rows: &[Vec<f64>]) -> (f64, Option<Vec<Vec<f64>>>)
+{
+    let size = rows.len();
+    let mut augmented: Vec<Vec<f64>> = rows
+        .iter()
+        .zip(identity(size))
+        .map(|(row, identity_row)| [row.as_slice(), &identity_row].concat())
+        .collect();
+    let mut sign = 1.;
+    let mut previous = 1.;
+
+    for k in 0..size
+    {
+        match (k..size).find(|&i| augmented[i][k] != 0.)
+        {
+            Some(i) if i != k =>
+            {
+                augmented.swap(i, k);
+                sign = -sign;
+            },
+            Some(_) => (),
+            None => return (0., None),
+        }
+
+        let pivot_row = augmented[k].clone();
+        for (i, row) in augmented.iter_mut().enumerate()
+        {
+            if i == k
+            {
+                continue;
+            }
+
+            let factor = row[k];
+            for (j, val) in row.iter_mut().enumerate()
+            {
+                *val = match j == k
+                {
+                    true => 0.,
+                    false => (pivot_row[k] * *val - factor * pivot_row[j]) / previous,
+                };
+            }
+        }
+        previous = pivot_row[k];
+    }
+
+    // the left half is now the determinant times the identity, up to the sign of the row swaps,
+    // and the right half is that multiple of the inverse
+    let inverse = augmented
+        .iter()
+        .enumerate()
+        .map(|(i, row)| row[size..].iter().map(|val| val / row[i]).collect())
+        .collect();
+
+    (sign * previous, Some(inverse))
+}
+
+// for at least as many values as the statistic needs
+fn statistic(aggregate: Aggregate, values: &[f64]) -> f64
+{
+    let mean = || values.iter().sum::<f64>() / values.len() as f64;
+
+    match aggregate
+    {
+        // adding zero turns the -0 of an empty sum into 0
+        Aggregate::Sum => values.iter().sum::<f64>() + 0.,
+        Aggregate::Mean => mean(),
+        Aggregate::Median =>
+        {
+            let mut sorted = values.to_vec();
+            sorted.sort_by(f64::total_cmp);
+
+            let middle = sorted.len() / 2;
+            match sorted.len().is_multiple_of(2)
+            {
+                true => (sorted[middle - 1] + sorted[middle]) / 2.,
+                false => sorted[middle],
+            }
+        },
+        Aggregate::Stdev =>
+        {
+            let mean = mean();
+            let squares: f64 = values.iter().map(|val| (val - mean).powi(2)).sum();
+
+            (squares / (values.len() - 1) as f64).sqrt()
+        },
+        Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+        Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+    }
+}
+
+fn invert(rows: &[Vec<f64>]) -> Option<Vec<Vec<f64>>>
+{
+    eliminate(rows).1
+}
+
+impl Numeric for Array
+{
+    type Error = MatrixError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        f64::from_str(literal)
+            .map(Array::Scalar)
+            .map_err(|_| MatrixError::InvalidLiteral(literal.to_string()))
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        match self
+        {
+            Array::Scalar(val) => *val,
+            _ => f64::NAN,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.elementwise(Operation::Addition, other)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.elementwise(Operation::Subtraction, other)
+    }
+
+    // the matrix product when there's a matrix on either side, where a vector is a column on the
+    // right and a row on the left. anything else is multiplied entry by entry
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        match (self, other)
+        {
+            (Array::Matrix(rows1), Array::Matrix(rows2)) if dimensions(rows1).1 == rows2.len() =>
+            {
+                Ok(Array::Matrix(product(rows1, rows2)))
+            },
+            (Array::Matrix(rows), Array::Vector(entries))
+                if dimensions(rows).1 == entries.len() =>
+            {
+                let column: Vec<Vec<f64>> = entries.iter().map(|val| vec![*val]).collect();
+                let result = product(rows, &column);
+
+                Ok(Array::Vector(
+                    result.into_iter().map(|row| row[0]).collect(),
+                ))
+            },
+            (Array::Vector(entries), Array::Matrix(rows)) if entries.len() == rows.len() =>
+            {
+                let result = product(std::slice::from_ref(entries), rows);
+
+                Ok(Array::Vector(result.into_iter().next().unwrap()))
+            },
+            (Array::Matrix(_), Array::Vector(_) | Array::Matrix(_))
+            | (Array::Vector(_), Array::Matrix(_)) =>
+            {
+                Err(self.mismatch(Operation::Multiplication, other))
+            },
+            _ => self.elementwise(Operation::Multiplication, other),
+        }
+    }
+
+    // dividing by a matrix multiplies by its inverse
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        match other
+        {
+            Array::Matrix(rows) if rows.len() == dimensions(rows).1 =>
+            {
+                let inverse = invert(rows).ok_or(MatrixError::Singular)?;
+                self.mul(&Array::Matrix(inverse))
+            },
+            Array::Matrix(_) => Err(self.mismatch(Operation::Division, other)),
+            _ => self.elementwise(Operation::Division, other),
+        }
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.elementwise(Operation::IntegerDivision, other)
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.elementwise(Operation::Remainder, other)
+    }
+
+    // a square matrix to a whole power, and anything else entry by entry
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        match (self, other)
+        {
+            (Array::Matrix(rows), Array::Scalar(exponent)) => self.matrix_pow(rows, *exponent),
+            (Array::Matrix(_), _) | (_, Array::Matrix(_)) =>
+            {
+                Err(self.mismatch(Operation::Exponentiation, other))
+            },
+            _ => self.elementwise(Operation::Exponentiation, other),
+        }
+    }
+
+    // the sum of the products of entries in the same place, which is the usual dot product for
+    // vectors. with a plain number on either side it's the same as multiplying
+    fn dot(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        match (self, other)
+        {
+            (Array::Scalar(_), _) | (_, Array::Scalar(_)) =>
+            {
+                self.elementwise(Operation::Multiplication, other)
+            },
+            _ =>
+            {
+                let sum = match self.elementwise(Operation::Dot, other)?
+                {
+                    Array::Vector(entries) => entries.iter().sum(),
+                    Array::Matrix(rows) => rows.iter().flatten().sum(),
+                    Array::Scalar(val) => val,
+                };
+
+                Ok(Array::Scalar(sum))
+            },
+        }
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{}( {} )", func.name(), self.shape());
+
+        match (func, self)
+        {
+            // a plain number is a matrix of size one
+            (Function::Det, Array::Scalar(val)) => Ok(Array::Scalar(*val)),
+            (Function::Inverse, Array::Scalar(val)) if *val == 0. => Err(MatrixError::Singular),
+            (Function::Inverse, Array::Scalar(val)) => Ok(Array::Scalar(1. / val)),
+            (Function::Det | Function::Inverse, Array::Matrix(rows))
+                if rows.len() == dimensions(rows).1 =>
+            {
+                match (func, eliminate(rows))
+                {
+                    (Function::Det, (determinant, _)) => Ok(Array::Scalar(determinant)),
+                    (_, (_, Some(inverse))) => Ok(Array::Matrix(inverse)),
+                    (_, (_, None)) => Err(MatrixError::Singular),
+                }
+            },
+            (Function::Det | Function::Inverse, _) => Err(MatrixError::Shape(description())),
+            // statistics are taken over all the entries, and a plain number is a list of one
+            (Function::Aggregate(aggregate), _) =>
+            {
+                let values = self.entries();
+                match values.len() < aggregate.min_len()
+                {
+                    true => Err(MatrixError::NotEnoughValues(description())),
+                    false => Ok(Array::Scalar(statistic(aggregate, &values))),
+                }
+            },
+            (Function::Sum | Function::Product, _) =>
+            {
+                unreachable!("sums are evaluated with their bound variable")
+            },
+            _ => Ok(self.map(|val| Expression::eval_function(func, val))),
+        }
+    }
+
+    // a list of plain numbers is a vector, and a list of vectors of the same length is a matrix
+    // with them as its rows
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        let scalars: Option<Vec<f64>> = elements
+            .iter()
+            .map(|element| match element
+            {
+                Array::Scalar(val) => Some(*val),
+                _ => None,
+            })
+            .collect();
+        if let Some(entries) = scalars
+        {
+            return Ok(Array::Vector(entries));
+        }
+
+        let rows: Option<Vec<Vec<f64>>> = elements
+            .iter()
+            .map(|element| match element
+            {
+                Array::Vector(entries) => Some(entries.clone()),
+                _ => None,
+            })
+            .collect();
+        match rows
+        {
+            Some(rows) if rows.iter().all(|row| row.len() == rows[0].len()) =>
+            {
+                Ok(Array::Matrix(rows))
+            },
+            _ =>
+            {
+                let shapes: Vec<String> = elements.iter().map(Array::shape).collect();
+                Err(MatrixError::Shape(format!("[{}]", shapes.join(", "))))
+            },
+        }
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(MatrixError::Undefined(description))
+    }
+}
+
+impl Expression
+{
+    // whether there are any vectors or matrices written in the expression
+    pub fn has_arrays(&self) -> bool
+    {
+        self.to_postfix_tokens()
+            .iter()
+            .any(|token| matches!(token, Token::Array { .. }))
+    }
+
+    pub fn eval_matrix(&self) -> Result<Array, MatrixError>
+    {
+        self.eval_matrix_with(&HashMap::new())
+    }
+
+    pub fn eval_matrix_with(&self, vars: &HashMap<String, Array>) -> Result<Array, MatrixError>
+    {
+        self.eval_numeric_with(vars)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::complex::ComplexError;
+    use crate::rational::{Irrational, RationalError};
+
+    fn eval(expr: &str) -> Result<String, MatrixError>
+    {
+        Expression::parse(expr)
+            .eval_matrix()
+            .map(|val| val.to_string())
+    }
+
+    #[test]
+    fn test_eval_matrix()
+    {
+        assert_eq!(eval("[1,2,3] . [4,5,6]"), Ok("32".to_string()));
+        assert_eq!(
+            eval("[[1,2],[3,4]] * [[5],[6]]"),
+            Ok("[[17], [39]]".to_string())
+        );
+        assert_eq!(eval("[[1,2],[3,4]] * [5,6]"), Ok("[17, 39]".to_string()));
+        assert_eq!(eval("[5,6] * [[1,2],[3,4]]"), Ok("[23, 34]".to_string()));
+        assert_eq!(
+            eval("[1, 2, 3] * 2 - [1, 1, 1]"),
+            Ok("[1, 3, 5]".to_string())
+        );
+        assert_eq!(
+            eval("[-1, 2^3, sqrt(16)] ^ 2"),
+            Ok("[1, 64, 16]".to_string())
+        );
+        assert_eq!(
+            eval("[[1,1],[1,0]]^10"),
+            Ok("[[89, 55], [55, 34]]".to_string())
+        );
+        assert_eq!(
+            eval("[[2,0],[0,4]]^-1 * 8"),
+            Ok("[[4, 0], [0, 2]]".to_string())
+        );
+        assert_eq!(
+            eval("[[4,2],[6,8]] / [[1,0],[1,2]]"),
+            Ok("[[3, 1], [2, 4]]".to_string())
+        );
+        assert_eq!(eval("[]"), Ok("[]".to_string()));
+        assert_eq!(eval("sum(k, 1, 3, [k, k^2])"), Ok("[6, 14]".to_string()));
+
+        let vars = HashMap::from([(
+            "A".to_string(),
+            Array::Matrix(vec![vec![1., 2.], vec![3., 4.]]),
+        )]);
+        let result = Expression::parse("A * inv(A) - A^0 + det(A)").eval_matrix_with(&vars);
+        assert_eq!(
+            result.map(|val| val.to_string()),
+            Ok("[[-2, -2], [-2, -2]]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_arrays_elsewhere()
+    {
+        // floats have no vectors or matrices, so arrays are NaN
+        let tree = Expression::parse("[1, 2] . [3, x] + mean([])");
+        let vars = HashMap::from([("x".to_string(), 4.)]);
+        assert!(tree.eval_with(&vars).is_nan());
+        assert!(Expression::parse("[1, 2] . [3, 4]").simplify().is_nan());
+        assert!(tree.eval_gradient(&vars).unwrap().0.is_nan());
+        assert!(tree
+            .eval_batch(&HashMap::from([("x".to_string(), vec![1., 2.])]))
+            .unwrap()
+            .iter()
+            .all(|val| val.is_nan()));
+
+        // and the other kinds of numbers give an error
+        assert_eq!(
+            Expression::parse("mean([1, 2])").eval_complex(),
+            Err(ComplexError::Unsupported("[ 1, 2 ]".to_string()))
+        );
+        assert_eq!(
+            Expression::parse("det([])").eval_rational(Irrational::Float),
+            Err(RationalError::Unsupported("[]".to_string()))
+        );
+
+        // derivatives are taken element by element
+        assert_eq!(
+            Expression::parse("[x^2, 3x]")
+                .derivative("x")
+                .expression_string(),
+            "[ ( 2 * x ) , 3 ] "
+        );
+    }
+
+    #[test]
+    fn test_det_inv()
+    {
+        assert_eq!(eval("det([[1,2],[3,4]])"), Ok("-2".to_string()));
+        assert_eq!(eval("det([[0,2,1],[1,0,3],[2,1,0]])"), Ok("13".to_string()));
+        assert_eq!(eval("det([[1,2],[2,4]])"), Ok("0".to_string()));
+        assert_eq!(eval("det(5)"), Ok("5".to_string()));
+        assert_eq!(
+            eval("inv([[1,2],[3,4]])"),
+            Ok("[[-2, 1], [1.5, -0.5]]".to_string())
+        );
+        assert_eq!(
+            eval("inv([[0,1],[1,0]])"),
+            Ok("[[0, 1], [1, 0]]".to_string())
+        );
+        assert_eq!(eval("inv(4)"), Ok("0.25".to_string()));
+    }
+
+    #[test]
+    fn test_matrix_errors()
+    {
+        let shape = |description: &str| Err(MatrixError::Shape(description.to_string()));
+
+        assert_eq!(eval("[1, 2] + [1, 2, 3]"), shape("[2] + [3]"));
+        assert_eq!(eval("[[1,2],[3,4]] * [[1,2,3]]"), shape("[2x2] * [1x3]"));
+        assert_eq!(eval("[1, 2] . [[1, 2]]"), shape("[2] . [1x2]"));
+        assert_eq!(eval("[[1, 2], [3]]"), shape("[[2], [1]]"));
+        assert_eq!(eval("[[1, 2], 3]"), shape("[[2], 3]"));
+        assert_eq!(eval("[[1, 2, 3]]^2"), shape("[1x3] ^ 2"));
+        assert_eq!(eval("det([1, 2])"), shape("det( [2] )"));
+        assert_eq!(eval("inv([[1,2],[2,4]])"), Err(MatrixError::Singular));
+        assert_eq!(eval("[[1,2],[2,4]]^-2"), Err(MatrixError::Singular));
+        assert_eq!(
+            eval("[[1,0],[0,1]]^0.5"),
+            Err(MatrixError::Undefined("[2x2] ^ 0.5".to_string()))
+        );
+        assert_eq!(
+            eval("[1, 2±0.1]"),
+            Err(MatrixError::InvalidLiteral("2±0.1".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_aggregates()
+    {
+        assert_eq!(eval("mean([3, 5, 8])"), Ok("5.333333333333333".to_string()));
+        assert_eq!(eval("median([5, 1, 3])"), Ok("3".to_string()));
+        assert_eq!(eval("median([4, 1, 3, 2])"), Ok("2.5".to_string()));
+        assert_eq!(
+            eval("stdev([2, 4, 4, 4, 5, 5, 7, 9])"),
+            Ok("2.138089935299395".to_string())
+        );
+        assert_eq!(
+            eval("min([3, -1, 2]) * max([3, -1, 2])"),
+            Ok("-3".to_string())
+        );
+        assert_eq!(eval("mean([[1, 2], [3, 4]])"), Ok("2.5".to_string()));
+        assert_eq!(
+            eval("mean([sum([1, 2]), 5]) + mean(1)"),
+            Ok("5".to_string())
+        );
+
+        // a plain number goes with every element of a list
+        assert_eq!(eval("[1, 2, 3] * 2"), Ok("[2, 4, 6]".to_string()));
+        assert_eq!(eval("2 ^ [1, 2, 3] - 1"), Ok("[1, 3, 7]".to_string()));
+        assert_eq!(
+            eval("sum([1, 2, 3] * 2) + sum(k, 1, 3, k)"),
+            Ok("18".to_string())
+        );
+
+        // the names are only functions when they're called
+        let vars = HashMap::from([("min".to_string(), 2.), ("max".to_string(), 7.)]);
+        assert_eq!(Expression::parse("max - min").eval_with(&vars), 5.);
+        assert_eq!(Expression::parse("max(min)").eval_with(&vars), 2.);
+    }
+
+    #[test]
+    fn test_aggregate_errors()
+    {
+        let not_enough =
+            |description: &str| Err(MatrixError::NotEnoughValues(description.to_string()));
+
+        for aggregate in ["mean", "median", "min", "max", "stdev"]
+        {
+            let description = format!("{}( [] )", aggregate);
+            assert_eq!(
+                eval(&format!("{}([])", aggregate)),
+                not_enough(&description)
+            );
+        }
+        assert_eq!(eval("stdev([1])"), not_enough("stdev( [1] )"));
+        assert_eq!(eval("stdev(4)"), not_enough("stdev( 4 )"));
+        assert_eq!(eval("sum([])"), Ok("0".to_string()));
+        assert_eq!(
+            eval("mean([1, 2] + [])"),
+            Err(MatrixError::Shape("[2] + []".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_matrix_latex()
+    {
+        assert_eq!(
+            Expression::parse("[[1,2],[3,x]] * [5, 6]").latex_string(),
+            "{\\begin{bmatrix}1&2\\\\3&x\\end{bmatrix}}\\cdot{\\begin{bmatrix}5\\\\6\\end{bmatrix}}"
+        );
+        assert_eq!(
+            Expression::parse("det(inv(A))").latex_string(),
+            "\\det\\left({\\left(A\\right)}^{-1}\\right)"
+        );
+        assert_eq!(
+            Expression::parse("mean([1, x]) - sum([])").latex_string(),
+            "{\\operatorname{mean}\\left(\\begin{bmatrix}1\\\\x\\end{bmatrix}\\right)}-{\\sum\\left(\\begin{bmatrix}\\end{bmatrix}\\right)}"
+        );
+    }
+}
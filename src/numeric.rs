@@ -0,0 +1,447 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::parallel::find_ready_nodes;
+use crate::solver::{factorial, Expression};
+use crate::tokens::*;
+
+// a kind of number expressions can be evaluated with. number literals stay strings in the tokens
+// and each implementation parses them itself, so adding one doesn't change the parser
+pub trait Numeric: Sized + Clone + fmt::Display
+{
+    type Error: fmt::Debug;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>;
+
+    // for things that need a plain number, like the bounds of a sum
+    fn to_f64(&self) -> f64;
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>;
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>;
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>;
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>;
+    // rounded down, with the remainder taking the sign of `other`
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>;
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>;
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>;
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>;
+
+    // a plain number is a vector of length one, so its dot product is just the product
+    fn dot(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.mul(other)
+    }
+
+    // the value of a bracket literal like "[1, 2, 3]" from the values of its elements. kinds of
+    // numbers without vectors or matrices give an error
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>;
+
+    // the value of a sum or product whose bounds aren't whole numbers, described like
+    // "sum( k, 1, 2.5 )"
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>;
+
+    fn apply(&self, op: Operation, other: &Self) -> Result<Self, Self::Error>
+    {
+        match op
+        {
+            Operation::Addition => self.add(other),
+            Operation::Subtraction => self.sub(other),
+            Operation::Multiplication => self.mul(other),
+            Operation::Division => self.div(other),
+            Operation::IntegerDivision => self.int_div(other),
+            Operation::Remainder => self.rem(other),
+            Operation::Exponentiation => self.pow(other),
+            Operation::Dot => self.dot(other),
+        }
+    }
+}
+
+// how kinds of numbers without vectors describe an array literal in their errors, like "[ 1, 2 ]"
+pub(crate) fn array_description<N: Numeric>(elements: &[N]) -> String
+{
+    match elements.is_empty()
+    {
+        true => "[]".to_string(),
+        false =>
+        {
+            let elements: Vec<String> =
+                elements.iter().map(|element| element.to_string()).collect();
+            format!("[ {} ]", elements.join(", "))
+        },
+    }
+}
+
+// reads the number literals of an expression. usually N::parse_literal, but a kind of number with
+// settings like a precision can pass a closure that applies them
+pub(crate) type LiteralParser<'a, N> = &'a dyn Fn(&str) -> Result<N, <N as Numeric>::Error>;
+
+#[derive(Debug, PartialEq)]
+pub enum NumericError
+{
+    InvalidLiteral(String),
+}
+
+impl Numeric for f64
+{
+    type Error = NumericError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        f64::from_str(literal).map_err(|_| NumericError::InvalidLiteral(literal.to_string()))
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        *self
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_binary_op(
+            *self,
+            Operation::Addition,
+            *other,
+        ))
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_binary_op(
+            *self,
+            Operation::Subtraction,
+            *other,
+        ))
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_binary_op(
+            *self,
+            Operation::Multiplication,
+            *other,
+        ))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_binary_op(
+            *self,
+            Operation::Division,
+            *other,
+        ))
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_binary_op(*self, Operation::IntegerDivision, *other))
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_binary_op(*self, Operation::Remainder, *other))
+    }
+
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_binary_op(
+            *self,
+            Operation::Exponentiation,
+            *other,
+        ))
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        Ok(Expression::eval_function(func, *self))
+    }
+
+    // floats have no vectors or matrices, so an array is NaN like any other value they can't hold
+    fn array(_elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Ok(f64::NAN)
+    }
+
+    fn invalid_bounds(_description: String) -> Result<Self, Self::Error>
+    {
+        Ok(f64::NAN)
+    }
+}
+
+impl Numeric for f32
+{
+    type Error = NumericError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        f32::from_str(literal).map_err(|_| NumericError::InvalidLiteral(literal.to_string()))
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        *self as f64
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self + other)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self - other)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self * other)
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self / other)
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok((self / other).floor())
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let remainder = self % other;
+
+        Ok(match remainder != 0. && (remainder < 0.) != (*other < 0.)
+        {
+            true => remainder + other,
+            false => remainder,
+        })
+    }
+
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self.powf(*other))
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        Ok(match func
+        {
+            Function::Sqrt => self.sqrt(),
+            Function::Exp => self.exp(),
+            Function::Ln => self.ln(),
+            Function::Sin => self.sin(),
+            Function::Cos => self.cos(),
+            Function::Tan => self.tan(),
+            Function::Factorial => factorial(*self as f64) as f32,
+            Function::Negate => -self,
+            Function::Det => *self,
+            Function::Inverse => 1. / self,
+            Function::Aggregate(aggregate) if aggregate.min_len() > 1 => f32::NAN,
+            Function::Aggregate(_) => *self,
+            Function::Sum | Function::Product =>
+            {
+                unreachable!("sums are evaluated with their bound variable")
+            },
+        })
+    }
+
+    fn array(_elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Ok(f32::NAN)
+    }
+
+    fn invalid_bounds(_description: String) -> Result<Self, Self::Error>
+    {
+        Ok(f32::NAN)
+    }
+}
+
+impl Token
+{
+    // the value of a number token in whichever kind of number is wanted
+    pub fn get_value<N: Numeric>(&self) -> Option<Result<N, N::Error>>
+    {
+        match self
+        {
+            Token::Number { val } => Some(N::parse_literal(val)),
+            _ => None,
+        }
+    }
+}
+
+impl Expression
+{
+    pub fn eval_numeric<N: Numeric>(&self) -> Result<N, N::Error>
+    {
+        self.eval_numeric_with(&HashMap::new())
+    }
+
+    pub fn eval_numeric_with<N: Numeric>(&self, vars: &HashMap<String, N>) -> Result<N, N::Error>
+    {
+        Self::eval_helper(self, vars, &N::parse_literal)
+    }
+
+    // like simplify_wavefront in any kind of number, returning every intermediate expression
+    pub fn simplify_numeric<N: Numeric>(self) -> Result<(N, Vec<String>), N::Error>
+    {
+        self.simplify_with(&N::parse_literal)
+    }
+
+    pub(crate) fn simplify_with<N: Numeric>(
+        self,
+        literal: LiteralParser<N>,
+    ) -> Result<(N, Vec<String>), N::Error>
+    {
+        let mut steps = Vec::new();
+
+        let expression = match self.has_bound_variables()
+        {
+            true =>
+            {
+                let expanded = self.expand_bound_variables();
+                steps.push(expanded.expression_string());
+                expanded
+            },
+            false => self,
+        };
+        let tree: Rc<RefCell<Expression>> = Rc::new(RefCell::new(expression));
+
+        // printing a result might not keep all of it, so evaluated nodes keep their value here
+        let mut values: HashMap<*const RefCell<Expression>, N> = HashMap::new();
+        let value_of = |node: &Rc<RefCell<Expression>>, values: &HashMap<_, N>| match values
+            .get(&Rc::as_ptr(node))
+        {
+            Some(val) => Ok(val.clone()),
+            None => match &node.borrow().operator
+            {
+                Token::Number { val } => literal(val),
+                // "[]" has no elements, so it's a leaf
+                Token::Array { .. } => N::array(Vec::new()),
+                Token::Variable { name } => panic!("no value given for variable: {}", name),
+                operator => panic!("attempted to eval invalid token: {:?}", operator),
+            },
+        };
+
+        while !tree.borrow().operands.is_empty()
+        {
+            for node in find_ready_nodes(tree.clone())
+            {
+                let operands = node
+                    .borrow()
+                    .operands
+                    .iter()
+                    .map(|operand| value_of(operand, &values))
+                    .collect::<Result<Vec<N>, N::Error>>()?;
+
+                let val = match node.borrow().operator
+                {
+                    Token::Operator { op } => operands[0].apply(op, &operands[1])?,
+                    Token::Function { func } => operands[0].call(func)?,
+                    Token::Array { .. } => N::array(operands)?,
+                    ref operator => panic!("attempted to eval invalid token: {:?}", operator),
+                };
+
+                node.borrow_mut().set_value(&val);
+                values.insert(Rc::as_ptr(&node), val);
+            }
+
+            steps.push(tree.borrow().expression_string());
+        }
+
+        let result = value_of(&tree, &values)?;
+        Ok((result, steps))
+    }
+
+    pub(crate) fn set_value<N: Numeric>(&mut self, val: &N)
+    {
+        self.operator = Token::Number { val: val.to_string() };
+        self.operands.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use num_rational::BigRational;
+
+    use super::*;
+    use crate::rational::RationalError;
+
+    #[test]
+    fn test_eval_numeric()
+    {
+        let tree = Expression::parse("(4*x-(8-y))/(5^.4*x) + sqrt(x) * sum(k, 1, 3, k*y)");
+        let vars = HashMap::from([("x".to_string(), 2.), ("y".to_string(), 3.)]);
+        assert_eq!(tree.eval_numeric_with(&vars), Ok(tree.eval_with(&vars)));
+
+        let vars = HashMap::from([("x".to_string(), 2f32), ("y".to_string(), 3f32)]);
+        let result: f32 = tree.eval_numeric_with(&vars).unwrap();
+        assert!(
+            (result as f64
+                - tree.eval_with(&HashMap::from([
+                    ("x".to_string(), 2.),
+                    ("y".to_string(), 3.)
+                ])))
+            .abs()
+                < 1e-4
+        );
+
+        assert_eq!(
+            Expression::parse("0.1 + 0.2").eval_numeric::<BigRational>(),
+            Ok(BigRational::new(3.into(), 10.into()))
+        );
+        assert_eq!(
+            Expression::parse("sqrt(3)").eval_numeric::<BigRational>(),
+            Err(RationalError::Irrational("sqrt( 3 )".to_string()))
+        );
+        assert_eq!(
+            Expression::parse("1 + x")
+                .eval_numeric_with(&HashMap::from([("x".to_string(), 0.5f32)])),
+            Ok(1.5)
+        );
+
+        // a plain number is a list of one, which is too short for a standard deviation. floats
+        // are NaN like for anything else that's undefined, and the rest give an error
+        let tree = Expression::parse("stdev(3)");
+        assert!(tree.eval().is_nan());
+        assert_eq!(
+            tree.eval_numeric::<BigRational>(),
+            Err(RationalError::NotEnoughValues("stdev( 3 )".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_literal()
+    {
+        let tree = Expression {
+            operator: Token::Number { val: "1.2.3".to_string() },
+            operands: Vec::new(),
+        };
+
+        assert_eq!(
+            tree.eval_numeric::<f64>(),
+            Err(NumericError::InvalidLiteral("1.2.3".to_string()))
+        );
+        assert!(tree.eval_numeric::<BigRational>().is_err());
+    }
+
+    #[test]
+    fn test_simplify_numeric()
+    {
+        let (result, steps) = Expression::parse("4*3+2^7")
+            .simplify_numeric::<f32>()
+            .unwrap();
+        assert_eq!(result, 140.);
+        assert_eq!(steps, vec!["( 12 + 128 ) ", "140 "]);
+
+        let (result, steps) = Expression::parse("1/3 + 1/6")
+            .simplify_numeric::<BigRational>()
+            .unwrap();
+        assert_eq!(result, BigRational::new(1.into(), 2.into()));
+        assert_eq!(steps, vec!["( 1/3 + 1/6 ) ", "1/2 "]);
+    }
+}
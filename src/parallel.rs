@@ -0,0 +1,397 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::rc::Rc;
+use std::thread;
+
+use crate::bound::EXPANDED_TERMS;
+use crate::solver::Expression;
+use crate::tokens::*;
+
+// expressions with fewer nodes than this aren't worth spawning threads for
+pub const PARALLEL_THRESHOLD: usize = 10_000;
+
+// rounds of the wavefront simplifier with fewer ready nodes than this are evaluated on one thread
+pub const WAVEFRONT_PARALLEL_THRESHOLD: usize = 1_000;
+
+impl Expression
+{
+    pub fn eval_parallel(&self) -> f64
+    {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        self.eval_parallel_with(threads, PARALLEL_THRESHOLD)
+    }
+
+    pub fn eval_parallel_with(&self, threads: usize, threshold: usize) -> f64
+    {
+        let postfix_tokens = self.to_postfix_tokens();
+        // a partition could separate the body of a sum from the variable it binds
+        let binds_variables = postfix_tokens.iter().any(|token| {
+            token
+                .get_function()
+                .is_some_and(|func| func.binds_variable())
+        });
+
+        if threads <= 1 || postfix_tokens.len() < threshold || binds_variables
+        {
+            return self.eval();
+        }
+
+        let subtrees = partition_subtrees(&postfix_tokens, threads);
+
+        // Rc trees can't be shared between threads, so each thread rebuilds its own subtrees
+        let results: Vec<f64> = thread::scope(|scope| {
+            let handles: Vec<_> = batch_subtrees(&subtrees, threads)
+                .into_iter()
+                .map(|batch| {
+                    let postfix_tokens = &postfix_tokens;
+                    scope.spawn(move || {
+                        batch
+                            .iter()
+                            .map(|range| {
+                                let tokens = postfix_tokens[range.clone()].to_vec();
+                                Expression::from_postfix_tokens(tokens).eval()
+                            })
+                            .collect::<Vec<f64>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("evaluation thread panicked"))
+                .collect()
+        });
+
+        // replace every solved subtree with its result and evaluate whatever is left on top
+        let mut remaining_tokens = Vec::new();
+        let mut i = 0;
+        for (range, result) in subtrees.iter().zip(results)
+        {
+            remaining_tokens.extend_from_slice(&postfix_tokens[i..range.start]);
+            remaining_tokens.push(Token::Number { val: result.to_string() });
+            i = range.end;
+        }
+        remaining_tokens.extend_from_slice(&postfix_tokens[i..]);
+
+        Expression::from_postfix_tokens(remaining_tokens).eval()
+    }
+
+    // like simplify, but every round evaluates all nodes whose operands are both numbers at once.
+    // returns the result and the expression as it looked after each round
+    pub fn simplify_wavefront(self) -> (f64, Vec<String>)
+    {
+        // variables are leaves just like numbers, so they'd otherwise look ready to evaluate
+        if let Some(name) = self.free_variables().first()
+        {
+            panic!("no value given for variable: {}", name);
+        }
+
+        let mut steps = Vec::new();
+
+        // small sums and products are written out term by term as the first step, and bigger
+        // ones are replaced by their value
+        let expression = match self.has_bound_variables()
+        {
+            true =>
+            {
+                let expanded = self.expand_ranges(EXPANDED_TERMS);
+                steps.push(expanded.expression_string());
+                expanded
+            },
+            false => self,
+        };
+        let tree: Rc<RefCell<Expression>> = Rc::new(RefCell::new(expression));
+
+        while !tree.borrow().operands.is_empty()
+        {
+            let ready_nodes = find_ready_nodes(tree.clone());
+            if ready_nodes.len() < WAVEFRONT_PARALLEL_THRESHOLD
+            {
+                for node in ready_nodes
+                {
+                    Self::evaluate_node(node);
+                }
+            }
+            else
+            {
+                evaluate_nodes_parallel(ready_nodes);
+            }
+
+            steps.push(tree.borrow().expression_string());
+        }
+
+        let result = tree.borrow().leaf_value();
+
+        (result, steps)
+    }
+}
+
+// every operator node whose operands are both numbers, i.e. what evaluate_node can handle
+pub(crate) fn find_ready_nodes(root: Rc<RefCell<Expression>>) -> Vec<Rc<RefCell<Expression>>>
+{
+    let mut node_queue: VecDeque<Rc<RefCell<Expression>>> = VecDeque::new();
+    let mut ready_nodes = Vec::new();
+
+    node_queue.push_back(root);
+    while let Some(current_node) = node_queue.pop_front()
+    {
+        let node = current_node.borrow();
+        if !node.operands.is_empty()
+        {
+            if node
+                .operands
+                .iter()
+                .all(|operand| operand.borrow().operands.is_empty())
+            {
+                ready_nodes.push(current_node.clone());
+            }
+            else
+            {
+                node_queue.extend(node.operands.iter().cloned());
+            }
+        }
+    }
+
+    ready_nodes
+}
+
+fn evaluate_nodes_parallel(nodes: Vec<Rc<RefCell<Expression>>>)
+{
+    // pull the numbers out of the tree so the threads never touch the Rc nodes
+    let operations: Vec<(Token, Vec<f64>)> = nodes
+        .iter()
+        .map(|node| {
+            let node = node.borrow();
+            let operand_values = node
+                .operands
+                .iter()
+                .map(|operand| operand.borrow().leaf_value())
+                .collect();
+
+            (node.operator.clone(), operand_values)
+        })
+        .collect();
+
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let chunk_size = operations.len().div_ceil(threads);
+
+    let results: Vec<f64> = thread::scope(|scope| {
+        let handles: Vec<_> = operations
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(operator, operand_values)| {
+                            Expression::eval_operation(operator, operand_values)
+                        })
+                        .collect::<Vec<f64>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("evaluation thread panicked"))
+            .collect()
+    });
+
+    for (node, val) in nodes.iter().zip(results)
+    {
+        node.borrow_mut().set_number(val);
+    }
+}
+
+// splits the tree into subtrees with no more than 1/`count` of the tokens each, by splitting every
+// subtree bigger than that into its children. in a long chain like 1*2 + 3*4 + 5*6 + ... that's a
+// short start of the chain and every term hanging off the rest of it. returned ranges index into
+// `postfix_tokens`, are sorted and never contain a lone number
+fn partition_subtrees(postfix_tokens: &[Token], count: usize) -> Vec<Range<usize>>
+{
+    let starts = subtree_starts(postfix_tokens);
+    let max_len = postfix_tokens.len().div_ceil(count.max(1));
+    let mut subtrees: Vec<Range<usize>> = Vec::new();
+    let mut range_stack: Vec<Range<usize>> = Vec::new();
+    range_stack.push(0..postfix_tokens.len());
+
+    while let Some(range) = range_stack.pop()
+    {
+        if range.len() <= max_len
+        {
+            subtrees.push(range);
+            continue;
+        }
+
+        let root = range.end - 1;
+        let mut end = root;
+        for _ in 0..postfix_tokens[root].arity()
+        {
+            let start = starts[end - 1];
+            range_stack.push(start..end);
+            end = start;
+        }
+    }
+
+    subtrees.retain(|range| range.len() > 1);
+    subtrees.sort_by_key(|range| range.start);
+    subtrees
+}
+
+// groups neighbouring subtrees into at most `count` batches with about the same number of tokens
+// in each, one for every thread
+fn batch_subtrees(subtrees: &[Range<usize>], count: usize) -> Vec<&[Range<usize>]>
+{
+    let total: usize = subtrees.iter().map(|range| range.len()).sum();
+    let batch_len = total.div_ceil(count.max(1));
+    let mut batches = Vec::new();
+    let (mut start, mut len) = (0, 0);
+
+    for (i, range) in subtrees.iter().enumerate()
+    {
+        len += range.len();
+        if len >= batch_len
+        {
+            batches.push(&subtrees[start..=i]);
+            (start, len) = (i + 1, 0);
+        }
+    }
+    if start < subtrees.len()
+    {
+        batches.push(&subtrees[start..]);
+    }
+
+    batches
+}
+
+// index of the first token of the subtree rooted at each token
+fn subtree_starts(postfix_tokens: &[Token]) -> Vec<usize>
+{
+    let mut starts = Vec::with_capacity(postfix_tokens.len());
+    let mut start_stack = Vec::new();
+
+    for (i, token) in postfix_tokens.iter().enumerate()
+    {
+        let mut start = i;
+        for _ in 0..token.arity()
+        {
+            start = start_stack
+                .pop()
+                .expect("tried to pop value off empty stack");
+        }
+
+        start_stack.push(start);
+        starts.push(start);
+    }
+
+    starts
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::create_tokens;
+    use crate::generate_expression;
+
+    use super::*;
+
+    #[test]
+    fn test_partition_subtrees()
+    {
+        let postfix_tokens = create_tokens!["4", "3", "*", "2", "7", "^", "+"];
+
+        assert_eq!(partition_subtrees(&postfix_tokens, 1), vec![0..7]);
+        assert_eq!(partition_subtrees(&postfix_tokens, 2), vec![0..3, 3..6]);
+        assert_eq!(partition_subtrees(&postfix_tokens, 8), vec![]);
+
+        // a chain leaning left splits into its start and the terms hanging off the rest
+        let expr = "1*2 + 3*4 + 5*6 + 7*8 + 9*10 + 11*12 + 13*14 + 15*16";
+        let postfix_tokens = shunting_yard(tokenize(expr));
+        let subtrees = partition_subtrees(&postfix_tokens, 2);
+
+        assert_eq!(subtrees, vec![0..15, 15..18, 19..22, 23..26, 27..30]);
+        assert_eq!(
+            batch_subtrees(&subtrees, 2),
+            vec![&subtrees[..1], &subtrees[1..]]
+        );
+    }
+
+    #[test]
+    fn test_eval_parallel_chain()
+    {
+        let expr = (1..2000)
+            .map(|i| format!("{}*{}/3", i, i + 1))
+            .collect::<Vec<String>>()
+            .join(" + ");
+        let tree = Expression::parse(&expr);
+
+        let subtrees = partition_subtrees(&tree.to_postfix_tokens(), 4);
+        assert_eq!(batch_subtrees(&subtrees, 4).len(), 4);
+
+        for threads in [2, 4, 7]
+        {
+            assert_eq!(tree.eval_parallel_with(threads, 0), tree.eval());
+        }
+    }
+
+    #[test]
+    fn test_eval_parallel()
+    {
+        let expr = generate_expression(500);
+        let tree = Expression::from_postfix_tokens(shunting_yard(tokenize(&expr)));
+
+        // randomly generated expressions can divide by zero, so NaN has to compare equal to itself
+        let same = |a: f64, b: f64| a == b || (a.is_nan() && b.is_nan());
+
+        for threads in [1, 2, 4, 7]
+        {
+            assert!(same(tree.eval_parallel_with(threads, 0), tree.eval()));
+        }
+        assert!(same(tree.eval_parallel(), tree.eval()));
+    }
+
+    #[test]
+    fn test_simplify_wavefront()
+    {
+        let tree = Expression::from_postfix_tokens(shunting_yard(tokenize("4*3+2^7")));
+        let (result, steps) = tree.simplify_wavefront();
+
+        assert_eq!(result, 140.);
+        assert_eq!(steps, vec!["( 12 + 128 ) ", "140 "]);
+
+        // big enough that the first rounds get split across threads
+        let expr = generate_expression(5000);
+        let tree = Expression::from_postfix_tokens(shunting_yard(tokenize(&expr)));
+        let expected = tree.eval();
+        let (result, steps) = tree.simplify_wavefront();
+
+        assert!(result == expected || (result.is_nan() && expected.is_nan()));
+        assert!(steps.len() < 5000);
+
+        let tree = Expression::parse("sum(k, 1, 3, k^2) * 2");
+        let (result, steps) = tree.simplify_wavefront();
+
+        assert_eq!(result, 28.);
+        assert_eq!(
+            steps,
+            vec![
+                "( ( ( ( 1 ^ 2 ) + ( 2 ^ 2 ) ) + ( 3 ^ 2 ) ) * 2 ) ",
+                "( ( ( 1 + 4 ) + 9 ) * 2 ) ",
+                "( ( 5 + 9 ) * 2 ) ",
+                "( 14 * 2 ) ",
+                "28 ",
+            ]
+        );
+        assert_eq!(
+            Expression::parse("sum(k, 1, 100, k^2)")
+                .simplify_wavefront()
+                .1,
+            vec!["338350 "]
+        );
+    }
+}
@@ -0,0 +1,641 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::numeric::{array_description, Numeric};
+use crate::solver::Expression;
+use crate::tokens::*;
+
+// what to do with operations whose result isn't rational, like sqrt(2)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Irrational
+{
+    // carry on with the closest f64 instead
+    Float,
+    Error,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RationalError
+{
+    DivisionByZero,
+    InvalidLiteral(String),
+    // the operation that had no rational result, like "sqrt( 2 )"
+    Irrational(String),
+    // a statistic that needs more values than the one it was given, like "stdev( 1/2 )"
+    NotEnoughValues(String),
+    // the vector or matrix literal, like "[ 1/2, 3 ]", which fractions can't hold
+    Unsupported(String),
+    // a power whose exact result would be too big to work out, like "10001/10000 ^ 10000000"
+    TooLarge(String),
+    // the sum or product whose bounds aren't whole numbers, like "sum( k, 1, 5/2 )"
+    Undefined(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value
+{
+    Exact(BigRational),
+    // an irrational result, and everything computed from it
+    Float(f64),
+}
+
+impl Value
+{
+    pub fn to_f64(&self) -> f64
+    {
+        match self
+        {
+            Value::Exact(val) => ToPrimitive::to_f64(val).unwrap_or(f64::NAN),
+            Value::Float(val) => *val,
+        }
+    }
+
+    pub fn apply(
+        self,
+        op: Operation,
+        other: Value,
+        irrational: Irrational,
+    ) -> Result<Value, RationalError>
+    {
+        let (Value::Exact(val1), Value::Exact(val2)) = (&self, &other)
+        else
+        {
+            let val = Expression::eval_binary_op(self.to_f64(), op, other.to_f64());
+            return Ok(Value::Float(val));
+        };
+
+        match op
+        {
+            Operation::Addition => Ok(Value::Exact(val1 + val2)),
+            Operation::Subtraction => Ok(Value::Exact(val1 - val2)),
+            Operation::Multiplication | Operation::Dot => Ok(Value::Exact(val1 * val2)),
+            Operation::Division if val2.is_zero() => Err(RationalError::DivisionByZero),
+            Operation::Division => Ok(Value::Exact(val1 / val2)),
+            Operation::IntegerDivision | Operation::Remainder if val2.is_zero() =>
+            {
+                Err(RationalError::DivisionByZero)
+            },
+            Operation::IntegerDivision => Ok(Value::Exact((val1 / val2).floor())),
+            Operation::Remainder => Ok(Value::Exact(val1 - val2 * (val1 / val2).floor())),
+            Operation::Exponentiation => match (exact_pow(val1, val2), irrational)
+            {
+                (Ok(Some(val)), _) => Ok(Value::Exact(val)),
+                // a result too big to work out exactly carries on as a float like irrational ones
+                (Ok(None), _) | (Err(RationalError::TooLarge(_)), Irrational::Float) => inexact(
+                    format!("{} ^ {}", self, other),
+                    Expression::eval_binary_op(self.to_f64(), op, other.to_f64()),
+                    irrational,
+                ),
+                (Err(err), _) => Err(err),
+            },
+        }
+    }
+
+    pub fn call(self, func: Function, irrational: Irrational) -> Result<Value, RationalError>
+    {
+        if let Function::Aggregate(aggregate) = func
+        {
+            if aggregate.min_len() > 1
+            {
+                let description = format!("{}( {} )", func.name(), self);
+                return Err(RationalError::NotEnoughValues(description));
+            }
+        }
+
+        let Value::Exact(val) = &self
+        else
+        {
+            return Ok(Value::Float(Expression::eval_function(func, self.to_f64())));
+        };
+
+        let exact = match func
+        {
+            Function::Sqrt => exact_root(val, 2),
+            Function::Exp | Function::Cos if val.is_zero() => Some(BigRational::one()),
+            Function::Ln if val.is_one() => Some(BigRational::zero()),
+            Function::Sin | Function::Tan if val.is_zero() => Some(BigRational::zero()),
+            Function::Factorial => match (exact_factorial(val), irrational)
+            {
+                (Ok(val), _) => val,
+                // like powers, carrying on as a float
+                (Err(RationalError::TooLarge(_)), Irrational::Float) => None,
+                (Err(err), _) => return Err(err),
+            },
+            Function::Negate => Some(-val),
+            Function::Det => Some(val.clone()),
+            Function::Inverse if val.is_zero() => return Err(RationalError::DivisionByZero),
+            Function::Inverse => Some(val.recip()),
+            Function::Aggregate(_) => Some(val.clone()),
+            Function::Sum | Function::Product =>
+            {
+                unreachable!("sums are expanded before evaluating")
+            },
+            _ => None,
+        };
+
+        match exact
+        {
+            Some(val) => Ok(Value::Exact(val)),
+            None => inexact(
+                format!("{}( {} )", func.name(), self),
+                Expression::eval_function(func, self.to_f64()),
+                irrational,
+            ),
+        }
+    }
+}
+
+// integers on their own and everything else as a reduced fraction, like "-7/2"
+impl fmt::Display for Value
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Value::Exact(val) if val.is_integer() => write!(f, "{}", val.numer()),
+            Value::Exact(val) => write!(f, "{}/{}", val.numer(), val.denom()),
+            Value::Float(val) => write!(f, "{}", val),
+        }
+    }
+}
+
+fn inexact(description: String, val: f64, irrational: Irrational) -> Result<Value, RationalError>
+{
+    match irrational
+    {
+        Irrational::Float => Ok(Value::Float(val)),
+        Irrational::Error => Err(RationalError::Irrational(description)),
+    }
+}
+
+// exact powers that would need more bits than this for their numerator and denominator are given
+// up on, since working them out takes longer than anyone would wait
+const MAX_POWER_BITS: u64 = 1 << 20;
+
+// base^exponent when the result is rational. integer exponents always are, and fractional ones
+// are when they come out even, like (27/8)^(2/3)
+pub(crate) fn exact_pow(
+    base: &BigRational,
+    exponent: &BigRational,
+) -> Result<Option<BigRational>, RationalError>
+{
+    if base.is_zero() && exponent.is_negative()
+    {
+        return Err(RationalError::DivisionByZero);
+    }
+    // these stay where they are however big the exponent is
+    if exponent.is_zero()
+    {
+        return Ok(Some(BigRational::one()));
+    }
+    if base.is_zero() || base.is_one()
+    {
+        return Ok(Some(base.clone()));
+    }
+    if base.abs().is_one() && exponent.is_integer()
+    {
+        return Ok(Some(match exponent.numer().is_odd()
+        {
+            true => base.clone(),
+            false => BigRational::one(),
+        }));
+    }
+
+    let root = match exponent.denom().to_u32()
+    {
+        Some(1) => Some(base.clone()),
+        Some(degree) => exact_root(base, degree),
+        None => None,
+    };
+    let Some(root) = root
+    else
+    {
+        return Ok(None);
+    };
+
+    let too_large = || {
+        let (base, exponent) = (Value::Exact(base.clone()), Value::Exact(exponent.clone()));
+        RationalError::TooLarge(format!("{} ^ {}", base, exponent))
+    };
+    // every bit past the leading one of the root gets multiplied by the power, and there's at
+    // least one since the root isn't 0 or ±1
+    let power = exponent.numer().to_i32().ok_or_else(too_large)?;
+    let bits = |val: &BigInt| val.bits().saturating_sub(1);
+    let power_bits = (bits(root.numer()) + bits(root.denom())) * power.unsigned_abs() as u64;
+    if power_bits > MAX_POWER_BITS
+    {
+        return Err(too_large());
+    }
+
+    Ok(Some(root.pow(power)))
+}
+
+// val! for whole numbers, as long as it fits in MAX_POWER_BITS like powers have to
+pub(crate) fn exact_factorial(val: &BigRational) -> Result<Option<BigRational>, RationalError>
+{
+    if !val.is_integer() || val.is_negative()
+    {
+        return Ok(None);
+    }
+
+    let too_large = || RationalError::TooLarge(format!("factorial( {} )", val.numer()));
+    let n = val.numer().to_u64().ok_or_else(too_large)?;
+    // stirling's approximation, log2(n!) ~ n log2(n / e) + log2(2 pi n) / 2
+    let n_f64 = n as f64;
+    let bits = n_f64 * (n_f64 / std::f64::consts::E).log2() + (TAU * n_f64).log2() / 2.;
+    if bits > MAX_POWER_BITS as f64
+    {
+        return Err(too_large());
+    }
+
+    Ok(Some(BigRational::from_integer(range_product(2, n))))
+}
+
+// lower * (lower + 1) * ... * upper, split in halves so that the numbers multiplied together
+// have about the same size, which is much faster than multiplying one at a time
+fn range_product(lower: u64, upper: u64) -> BigInt
+{
+    if upper < lower + 16
+    {
+        return (lower..=upper).map(BigInt::from).product();
+    }
+
+    let middle = lower + (upper - lower) / 2;
+    range_product(lower, middle) * range_product(middle + 1, upper)
+}
+
+pub(crate) fn exact_root(val: &BigRational, degree: u32) -> Option<BigRational>
+{
+    if val.is_negative()
+    {
+        return None;
+    }
+    if val.is_zero() || val.is_one()
+    {
+        return Some(val.clone());
+    }
+    // anything else would need billions of bits to have a root that deep
+    let power = i32::try_from(degree).ok()?;
+
+    let num = val.numer().nth_root(degree);
+    let den = val.denom().nth_root(degree);
+    let root = BigRational::new(num, den);
+
+    (root.pow(power) == *val).then_some(root)
+}
+
+// reads number literals like "12", "0.25" or ".5" exactly, as well as fractions like "1/3"
+pub(crate) fn parse_exact(literal: &str) -> Result<BigRational, RationalError>
+{
+    let invalid = || RationalError::InvalidLiteral(literal.to_string());
+
+    if let Some((num, den)) = literal.split_once('/')
+    {
+        let num: BigInt = num.parse().map_err(|_| invalid())?;
+        let den: BigInt = den.parse().map_err(|_| invalid())?;
+        if den.is_zero()
+        {
+            return Err(RationalError::DivisionByZero);
+        }
+
+        return Ok(BigRational::new(num, den));
+    }
+
+    let (whole, fraction) = literal.split_once('.').unwrap_or((literal, ""));
+    if fraction.starts_with(['+', '-'])
+    {
+        return Err(invalid());
+    }
+    let digits: BigInt = format!("{}{}", whole, fraction)
+        .parse()
+        .map_err(|_| invalid())?;
+    let scale = num_traits::pow(BigInt::from(10), fraction.len());
+
+    Ok(BigRational::new(digits, scale))
+}
+
+// irrational results carry on as floats
+impl Numeric for Value
+{
+    type Error = RationalError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        parse_exact(literal).map(Value::Exact)
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        Value::to_f64(self)
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.clone()
+            .apply(Operation::Addition, other.clone(), Irrational::Float)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.clone()
+            .apply(Operation::Subtraction, other.clone(), Irrational::Float)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.clone()
+            .apply(Operation::Multiplication, other.clone(), Irrational::Float)
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.clone()
+            .apply(Operation::Division, other.clone(), Irrational::Float)
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.clone().apply(Operation::IntegerDivision, other.clone(), Irrational::Float)
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.clone().apply(Operation::Remainder, other.clone(), Irrational::Float)
+    }
+
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        self.clone()
+            .apply(Operation::Exponentiation, other.clone(), Irrational::Float)
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        self.clone().call(func, Irrational::Float)
+    }
+
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Err(RationalError::Unsupported(array_description(&elements)))
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(RationalError::Undefined(description))
+    }
+}
+
+// irrational results are errors
+impl Numeric for BigRational
+{
+    type Error = RationalError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        parse_exact(literal)
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        ToPrimitive::to_f64(self).unwrap_or(f64::NAN)
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self + other)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self - other)
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self * other)
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        exact(Value::Exact(self.clone()).apply(
+            Operation::Division,
+            Value::Exact(other.clone()),
+            Irrational::Error,
+        ))
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        exact(Value::Exact(self.clone()).apply(
+            Operation::IntegerDivision,
+            Value::Exact(other.clone()),
+            Irrational::Error,
+        ))
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        exact(Value::Exact(self.clone()).apply(
+            Operation::Remainder,
+            Value::Exact(other.clone()),
+            Irrational::Error,
+        ))
+    }
+
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        exact(Value::Exact(self.clone()).apply(
+            Operation::Exponentiation,
+            Value::Exact(other.clone()),
+            Irrational::Error,
+        ))
+    }
+
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        exact(Value::Exact(self.clone()).call(func, Irrational::Error))
+    }
+
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Err(RationalError::Unsupported(array_description(&elements)))
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(RationalError::Undefined(description))
+    }
+}
+
+fn exact(val: Result<Value, RationalError>) -> Result<BigRational, RationalError>
+{
+    match val?
+    {
+        Value::Exact(val) => Ok(val),
+        Value::Float(_) => unreachable!("exact operations only give floats for float operands"),
+    }
+}
+
+impl Expression
+{
+    pub fn eval_rational(&self, irrational: Irrational) -> Result<Value, RationalError>
+    {
+        self.eval_rational_with(&HashMap::new(), irrational)
+    }
+
+    // with Irrational::Error, float variables are read as the exact fraction they store
+    pub fn eval_rational_with(
+        &self,
+        vars: &HashMap<String, Value>,
+        irrational: Irrational,
+    ) -> Result<Value, RationalError>
+    {
+        match irrational
+        {
+            Irrational::Float => self.eval_numeric_with(vars),
+            Irrational::Error =>
+            {
+                let vars = vars
+                    .iter()
+                    .map(|(name, val)| {
+                        let val = match val
+                        {
+                            Value::Exact(val) => val.clone(),
+                            Value::Float(val) =>
+                            {
+                                BigRational::from_float(*val).unwrap_or_else(|| {
+                                    panic!("variable {} isn't a finite number: {}", name, val)
+                                })
+                            },
+                        };
+                        (name.clone(), val)
+                    })
+                    .collect();
+
+                self.eval_numeric_with(&vars).map(Value::Exact)
+            },
+        }
+    }
+
+    // like simplify_wavefront, but exact. steps show results as reduced fractions
+    pub fn simplify_rational(
+        self,
+        irrational: Irrational,
+    ) -> Result<(Value, Vec<String>), RationalError>
+    {
+        match irrational
+        {
+            Irrational::Float => self.simplify_numeric(),
+            Irrational::Error => self
+                .simplify_numeric::<BigRational>()
+                .map(|(result, steps)| (Value::Exact(result), steps)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn exact(num: i64, den: i64) -> Value
+    {
+        Value::Exact(BigRational::new(num.into(), den.into()))
+    }
+
+    #[test]
+    fn test_eval_rational()
+    {
+        let eval = |expr| Expression::parse(expr).eval_rational(Irrational::Error);
+
+        assert_eq!(eval("1/3 + 1/3 + 1/3"), Ok(exact(1, 1)));
+        assert_eq!(eval("0.1 + 0.2"), Ok(exact(3, 10)));
+        assert_eq!(eval("(2/3)^3 * 2^(0-2)"), Ok(exact(2, 27)));
+        assert_eq!(eval("(27/8)^(2/3) + sqrt(16/9)"), Ok(exact(43, 12)));
+        assert_eq!(eval("ln(exp(0)) + sum(k, 1, 3, 1/k)"), Ok(exact(11, 6)));
+        assert_eq!(eval("1/(2 - 2)"), Err(RationalError::DivisionByZero));
+        assert_eq!(
+            eval("1 + sqrt(2)"),
+            Err(RationalError::Irrational("sqrt( 2 )".to_string()))
+        );
+        assert_eq!(
+            eval("2^(1/2)"),
+            Err(RationalError::Irrational("2 ^ 1/2".to_string()))
+        );
+
+        let float = Expression::parse("sqrt(2) * sqrt(2) + 1/4").eval_rational(Irrational::Float);
+        assert_eq!(float, Ok(Value::Float(2f64.sqrt() * 2f64.sqrt() + 0.25)));
+
+        let vars = HashMap::from([("x".to_string(), exact(1, 6))]);
+        let result = Expression::parse("x*3 - 1/2").eval_rational_with(&vars, Irrational::Error);
+        assert_eq!(result, Ok(exact(0, 1)));
+
+        // powers too big to work out exactly are errors, or floats when irrational results are
+        assert_eq!(
+            eval("1.0001^10000000"),
+            Err(RationalError::TooLarge(
+                "10001/10000 ^ 10000000".to_string()
+            ))
+        );
+        let float = Expression::parse("1.0001^10000000").eval_rational(Irrational::Float);
+        assert_eq!(float, Ok(Value::Float(1.0001f64.powf(1e7))));
+        assert_eq!(eval("1^10000000 - (0-1)^10000001"), Ok(exact(2, 1)));
+        assert_eq!(eval("0^(1/3000000000) + 1^(1/3000000000)"), Ok(exact(1, 1)));
+        assert_eq!(
+            eval("1^(10^10) + (0-1)^(10^10 + 1) + 0^(10^10) + (1/2)^0"),
+            Ok(exact(1, 1))
+        );
+        assert_eq!(
+            eval("2^(10^10)"),
+            Err(RationalError::TooLarge("2 ^ 10000000000".to_string()))
+        );
+        assert_eq!(
+            eval("100000!"),
+            Err(RationalError::TooLarge("factorial( 100000 )".to_string()))
+        );
+        let float = Expression::parse("100000!").eval_rational(Irrational::Float);
+        assert_eq!(float, Ok(Value::Float(f64::INFINITY)));
+        assert_eq!(
+            eval("3^(10^10 / 3)"),
+            Err(RationalError::Irrational("3 ^ 10000000000/3".to_string()))
+        );
+        assert_eq!(
+            eval("2^(1/3000000000)"),
+            Err(RationalError::Irrational("2 ^ 1/3000000000".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_simplify_rational()
+    {
+        let (result, steps) = Expression::parse("1/3 + 1/3 + 1/3")
+            .simplify_rational(Irrational::Error)
+            .unwrap();
+
+        assert_eq!(result, exact(1, 1));
+        assert_eq!(
+            steps,
+            vec!["( ( 1/3 + 1/3 ) + 1/3 ) ", "( 2/3 + 1/3 ) ", "1 "]
+        );
+
+        let (result, steps) = Expression::parse("(1 - 1.5) / 4 + sqrt(2)")
+            .simplify_rational(Irrational::Float)
+            .unwrap();
+
+        assert_eq!(result.to_string(), "1.2892135623730951");
+        assert_eq!(
+            steps,
+            vec![
+                "( ( -1/2 / 4 ) + 1.4142135623730951 ) ",
+                "( -1/8 + 1.4142135623730951 ) ",
+                "1.2892135623730951 ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::solver::Expression;
+use crate::tokens::*;
+
+// rewrites anything matching `pattern` into `replacement`. every variable in the pattern matches
+// any subtree, and a variable used twice has to match the same subtree both times. variables
+// named c, c1, c2, ... only match numbers, and ones named n, n1, n2, ... only whole numbers
+pub struct Rule
+{
+    pub name: String,
+    pub pattern: Expression,
+    pub replacement: Expression,
+}
+
+impl Rule
+{
+    pub fn new(name: &str, pattern: &str, replacement: &str) -> Rule
+    {
+        Rule {
+            name: name.to_string(),
+            pattern: Expression::parse(pattern),
+            replacement: Expression::parse(replacement),
+        }
+    }
+}
+
+// one rewrite done by the simplifier. `path` is the list of operand indices leading from the root
+// to the subtree that was rewritten, and `after` is that subtree, both once constants are folded
+// again. when folding merges the subtree into the one around it, the path leads to that one
+#[derive(Debug, PartialEq)]
+pub struct RewriteStep
+{
+    pub rule: String,
+    pub path: Vec<usize>,
+    pub before: String,
+    pub after: String,
+}
+
+pub fn default_rules() -> Vec<Rule>
+{
+    vec![
+        // like terms
+        Rule::new("collect like terms", "x + x", "2*x"),
+        Rule::new("collect like terms", "c*x + x", "(c+1)*x"),
+        Rule::new("collect like terms", "x + c*x", "(1+c)*x"),
+        Rule::new("collect like terms", "c1*x + c2*x", "(c1+c2)*x"),
+        Rule::new("collect like terms", "c1*x - c2*x", "(c1-c2)*x"),
+        Rule::new("collect like terms", "c*x - x", "(c-1)*x"),
+        Rule::new("collect like terms", "x - c*x", "(1-c)*x"),
+        Rule::new("cancel terms", "x - x", "0"),
+        Rule::new("collect like terms", "(y + x) + x", "y + 2*x"),
+        Rule::new("collect like terms", "(y + c*x) + x", "y + (c+1)*x"),
+        Rule::new("collect like terms", "(y + c1*x) + c2*x", "y + (c1+c2)*x"),
+        Rule::new("collect like terms", "(y + c*x) - x", "y + (c-1)*x"),
+        Rule::new("collect like terms", "(y + c1*x) - c2*x", "y + (c1-c2)*x"),
+        // powers
+        Rule::new("combine powers", "x*x", "x^2"),
+        Rule::new("combine powers", "x^a*x", "x^(a+1)"),
+        Rule::new("combine powers", "x*x^a", "x^(a+1)"),
+        Rule::new("combine powers", "x^a*x^b", "x^(a+b)"),
+        Rule::new("combine powers", "x^a/x", "x^(a-1)"),
+        Rule::new("combine powers", "x^a/x^b", "x^(a-b)"),
+        // only for whole outer exponents, since ((-2)^2)^0.5 is 2 but (-2)^1 is -2. x^0 and x/x
+        // aren't 1 when x is 0, so those are left alone
+        Rule::new("power of a power", "(x^a)^n", "x^(a*n)"),
+        // cancelling factors
+        Rule::new("cancel factors", "(x*y)/x", "y"),
+        Rule::new("cancel factors", "(y*x)/x", "y"),
+        Rule::new("cancel factors", "(x*y)/(x*z)", "y/z"),
+        Rule::new("cancel factors", "(y*x)/(z*x)", "y/z"),
+        Rule::new("zero numerator", "0/x", "0"),
+        // keep numbers in front so the rules above see them
+        Rule::new("move constant first", "x*c", "c*x"),
+        Rule::new("combine constants", "c1*(c2*x)", "(c1*c2)*x"),
+    ]
+}
+
+pub fn node_count(expression: &Expression) -> usize
+{
+    expression.to_postfix_tokens().len()
+}
+
+// applies rules anywhere in an expression until none of them match any more, folding constants
+// after every rewrite. the cheapest expression seen according to `cost` is the result
+pub struct Rewriter
+{
+    pub rules: Vec<Rule>,
+    pub cost: fn(&Expression) -> usize,
+    pub max_rewrites: usize,
+}
+
+impl Rewriter
+{
+    pub fn new(rules: Vec<Rule>) -> Rewriter
+    {
+        Rewriter { rules, cost: node_count, max_rewrites: 1000 }
+    }
+
+    pub fn simplify(&self, expression: &Expression) -> (Expression, Vec<RewriteStep>)
+    {
+        let tree = Rc::new(RefCell::new(expression.partial_eval()));
+        let mut steps = Vec::new();
+
+        let mut best_cost = (self.cost)(&tree.borrow());
+        let mut best = (tree.borrow().to_postfix_tokens(), 0);
+
+        for _ in 0..self.max_rewrites
+        {
+            let Some(mut step) = self.rewrite_once(&tree)
+            else
+            {
+                break;
+            };
+
+            step.path = folded_path(&tree.borrow(), &step.path);
+            let folded = tree.borrow().partial_eval();
+            *tree.borrow_mut() = folded;
+
+            step.after = subtree_string(&tree.borrow(), &step.path);
+            steps.push(step);
+
+            let cost = (self.cost)(&tree.borrow());
+            if cost <= best_cost
+            {
+                best_cost = cost;
+                best = (tree.borrow().to_postfix_tokens(), steps.len());
+            }
+        }
+
+        // a rule that makes things worse might have run after the best expression was found
+        steps.truncate(best.1);
+        (Expression::from_postfix_tokens(best.0), steps)
+    }
+
+    // rewrites the first subtree, in preorder, that some rule matches
+    fn rewrite_once(&self, tree: &Rc<RefCell<Expression>>) -> Option<RewriteStep>
+    {
+        let mut node_stack: Vec<(Rc<RefCell<Expression>>, Vec<usize>)> =
+            vec![(tree.clone(), Vec::new())];
+
+        while let Some((node, path)) = node_stack.pop()
+        {
+            for rule in &self.rules
+            {
+                let mut bindings = HashMap::new();
+                if !match_pattern(&rule.pattern, &node.borrow(), &mut bindings)
+                {
+                    continue;
+                }
+
+                let replacement = instantiate(&rule.replacement, &bindings);
+                let before = node.borrow().expression_string();
+                let after = replacement.expression_string();
+                *node.borrow_mut() = replacement;
+
+                return Some(RewriteStep { rule: rule.name.clone(), path, before, after });
+            }
+
+            let operands = node.borrow().operands.clone();
+            for (i, operand) in operands.into_iter().enumerate().rev()
+            {
+                let mut operand_path = path.clone();
+                operand_path.push(i);
+                node_stack.push((operand, operand_path));
+            }
+        }
+
+        None
+    }
+}
+
+impl Expression
+{
+    pub fn simplify_symbolic(&self) -> (Expression, Vec<RewriteStep>)
+    {
+        Rewriter::new(default_rules()).simplify(self)
+    }
+}
+
+// where the subtree at `path` ends up once `tree` is folded. folding works from the leaves up, so
+// a node either keeps its operands in place, turns into the one operand the path goes through, or
+// is folded away along with everything under it
+fn folded_path(tree: &Expression, path: &[usize]) -> Vec<usize>
+{
+    let mut resolved = Vec::new();
+    let mut folded = tree.partial_eval();
+    let mut operator = tree.operator.clone();
+    let mut operands = tree.operands.clone();
+
+    for &i in path
+    {
+        let child = operands[i].clone();
+        let folded_child = child.borrow().partial_eval();
+
+        if folded.to_postfix_tokens() != folded_child.to_postfix_tokens()
+        {
+            if folded.operator != operator || folded.operands.len() != operands.len()
+            {
+                break;
+            }
+            resolved.push(i);
+        }
+
+        folded = folded_child;
+        operator = child.borrow().operator.clone();
+        operands = child.borrow().operands.clone();
+    }
+
+    resolved
+}
+
+fn subtree_string(tree: &Expression, path: &[usize]) -> String
+{
+    let Some((&first, rest)) = path.split_first()
+    else
+    {
+        return tree.expression_string();
+    };
+
+    let mut node = tree.operands[first].clone();
+    for &i in rest
+    {
+        let child = node.borrow().operands[i].clone();
+        node = child;
+    }
+
+    let string = node.borrow().expression_string();
+    string
+}
+
+fn is_pattern_variable(name: &str, prefix: char) -> bool
+{
+    name.strip_prefix(prefix)
+        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+// bindings map each pattern variable to the postfix tokens of the subtree it matched
+fn match_pattern(
+    pattern: &Expression,
+    expression: &Expression,
+    bindings: &mut HashMap<String, Vec<Token>>,
+) -> bool
+{
+    match &pattern.operator
+    {
+        Token::Variable { name } =>
+        {
+            let number = expression.number_value();
+            if (is_pattern_variable(name, 'c') && number.is_none())
+                || (is_pattern_variable(name, 'n') && !number.is_some_and(|val| val.fract() == 0.))
+            {
+                return false;
+            }
+
+            let tokens = expression.to_postfix_tokens();
+            match bindings.get(name)
+            {
+                Some(bound) => *bound == tokens,
+                None =>
+                {
+                    bindings.insert(name.clone(), tokens);
+                    true
+                },
+            }
+        },
+        Token::Number { .. } => expression.number_value() == pattern.number_value(),
+        _ =>
+        {
+            pattern.operator == expression.operator
+                && pattern.operands.len() == expression.operands.len()
+                && pattern
+                    .operands
+                    .iter()
+                    .zip(&expression.operands)
+                    .all(|(p, e)| match_pattern(&p.borrow(), &e.borrow(), bindings))
+        },
+    }
+}
+
+fn instantiate(replacement: &Expression, bindings: &HashMap<String, Vec<Token>>) -> Expression
+{
+    let mut tokens = Vec::new();
+
+    for token in replacement.to_postfix_tokens()
+    {
+        match token.get_variable().and_then(|name| bindings.get(name))
+        {
+            Some(bound) => tokens.extend_from_slice(bound),
+            None => tokens.push(token),
+        }
+    }
+
+    Expression::from_postfix_tokens(tokens)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn simplify(expr: &str) -> String
+    {
+        Expression::parse(expr)
+            .simplify_symbolic()
+            .0
+            .expression_string()
+    }
+
+    #[test]
+    fn test_simplify_symbolic()
+    {
+        assert_eq!(simplify("2*x + 3*x - x"), "( 4 * x ) ");
+        assert_eq!(simplify("x*x*x"), "( x ^ 3 ) ");
+        assert_eq!(simplify("x^2 * x^3 / x"), "( x ^ 4 ) ");
+        assert_eq!(simplify("(a*b)/a + y*2 - y"), "( b + y ) ");
+        assert_eq!(simplify("(x^2)^3 - x^6"), "0 ");
+        assert_eq!(simplify("(x^a)^2 * x"), "( x ^ ( ( 2 * a ) + 1 ) ) ");
+
+        // only rewrites that keep the value for every x
+        assert_eq!(
+            simplify("(a+b)/(a+b) + 1"),
+            "( ( ( a + b ) / ( a + b ) ) + 1 ) "
+        );
+        assert_eq!(simplify("x^0"), "( x ^ 0 ) ");
+        assert_eq!(simplify("(x^2)^0.5"), "( ( x ^ 2 ) ^ 0.5 ) ");
+    }
+
+    #[test]
+    fn test_rewrite_trace()
+    {
+        let (result, steps) = Expression::parse("3 + x*2 + x").simplify_symbolic();
+
+        assert_eq!(result.expression_string(), "( 3 + ( 3 * x ) ) ");
+        assert_eq!(
+            steps,
+            vec![
+                RewriteStep {
+                    rule: "move constant first".to_string(),
+                    path: vec![0, 1],
+                    before: "( x * 2 ) ".to_string(),
+                    after: "( 2 * x ) ".to_string(),
+                },
+                RewriteStep {
+                    rule: "collect like terms".to_string(),
+                    path: vec![],
+                    before: "( ( 3 + ( 2 * x ) ) + x ) ".to_string(),
+                    after: "( 3 + ( 3 * x ) ) ".to_string(),
+                },
+            ]
+        );
+
+        // the cancelled terms fold into the y next to them, so the rewrite ends up there
+        let (result, steps) = Expression::parse("(x - x + y) * (z*2)").simplify_symbolic();
+
+        assert_eq!(result.expression_string(), "( y * ( 2 * z ) ) ");
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].path, vec![0]);
+        assert_eq!(steps[0].after, "y ");
+        assert_eq!(steps[1].path, vec![1]);
+        assert_eq!(steps[1].after, "( 2 * z ) ");
+    }
+
+    #[test]
+    fn test_custom_rules()
+    {
+        let rewriter = Rewriter::new(vec![Rule::new("double", "x + x", "2*x")]);
+        let (result, steps) = rewriter.simplify(&Expression::parse("(y + y) + (y + y)"));
+
+        assert_eq!(result.expression_string(), "( 2 * ( 2 * y ) ) ");
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].path, vec![]);
+    }
+}
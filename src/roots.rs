@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use crate::bytecode::SingleVariable;
+use crate::solver::Expression;
+
+#[derive(Debug, PartialEq)]
+pub enum RootError
+{
+    // the expression uses a variable other than the one being solved for
+    UnboundVariable(String),
+    // bisection and brent need the function to change sign over the interval
+    NoSignChange,
+    DidNotConverge,
+    // newton's method hit a point where the derivative is zero or not a number
+    ZeroDerivative,
+    // the text given to solve_numeric doesn't have exactly one '='
+    NotAnEquation(String),
+}
+
+pub struct RootFinder
+{
+    pub tolerance: f64,
+    pub max_iterations: usize,
+    // how many pieces find_roots splits the interval into when looking for sign changes
+    pub samples: usize,
+}
+
+impl Default for RootFinder
+{
+    fn default() -> Self
+    {
+        RootFinder { tolerance: 1e-12, max_iterations: 100, samples: 1000 }
+    }
+}
+
+impl RootFinder
+{
+    pub fn new() -> RootFinder
+    {
+        Self::default()
+    }
+
+    pub fn bisection(
+        &self,
+        expression: &Expression,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<f64, RootError>
+    {
+        let mut f = SingleVariable::new(expression, var).map_err(RootError::UnboundVariable)?;
+        let (mut a, mut b) = (lower, upper);
+        let (mut fa, fb) = (f.eval(a), f.eval(b));
+
+        if fa == 0.
+        {
+            return Ok(a);
+        }
+        if fb == 0.
+        {
+            return Ok(b);
+        }
+        if fa.signum() == fb.signum()
+        {
+            return Err(RootError::NoSignChange);
+        }
+
+        for _ in 0..self.max_iterations
+        {
+            let mid = a + (b - a) / 2.;
+            let fmid = f.eval(mid);
+
+            if fmid == 0. || (b - a).abs() / 2. < self.tolerance
+            {
+                return Ok(mid);
+            }
+
+            if fmid.signum() == fa.signum()
+            {
+                (a, fa) = (mid, fmid);
+            }
+            else
+            {
+                b = mid;
+            }
+        }
+
+        Err(RootError::DidNotConverge)
+    }
+
+    // brent's method: inverse quadratic interpolation or the secant method where they make
+    // progress, falling back to bisection where they don't
+    pub fn brent(
+        &self,
+        expression: &Expression,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<f64, RootError>
+    {
+        let mut f = SingleVariable::new(expression, var).map_err(RootError::UnboundVariable)?;
+        self.brent_with(&mut f, lower, upper)
+    }
+
+    fn brent_with(&self, f: &mut SingleVariable, lower: f64, upper: f64) -> Result<f64, RootError>
+    {
+        let (mut a, mut b) = (lower, upper);
+        let (mut fa, mut fb) = (f.eval(a), f.eval(b));
+
+        if fa == 0.
+        {
+            return Ok(a);
+        }
+        if fb == 0.
+        {
+            return Ok(b);
+        }
+        if fa.signum() == fb.signum()
+        {
+            return Err(RootError::NoSignChange);
+        }
+
+        // b is always the best guess so far and c the previous one
+        if fa.abs() < fb.abs()
+        {
+            (a, b, fa, fb) = (b, a, fb, fa);
+        }
+        let (mut c, mut fc) = (a, fa);
+        let mut d = b - a;
+        let mut bisected = true;
+
+        for _ in 0..self.max_iterations
+        {
+            if fb == 0. || (b - a).abs() < self.tolerance
+            {
+                return Ok(b);
+            }
+
+            let mut s = if fa != fc && fb != fc
+            {
+                a * fb * fc / ((fa - fb) * (fa - fc))
+                    + b * fa * fc / ((fb - fa) * (fb - fc))
+                    + c * fa * fb / ((fc - fa) * (fc - fb))
+            }
+            else
+            {
+                b - fb * (b - a) / (fb - fa)
+            };
+
+            let between = (s - (3. * a + b) / 4.) * (s - b) < 0.;
+            let slow = if bisected
+            {
+                (s - b).abs() >= (b - c).abs() / 2. || (b - c).abs() < self.tolerance
+            }
+            else
+            {
+                (s - b).abs() >= (c - d).abs() / 2. || (c - d).abs() < self.tolerance
+            };
+
+            bisected = !between || slow;
+            if bisected
+            {
+                s = (a + b) / 2.;
+            }
+
+            let fs = f.eval(s);
+            d = c;
+            (c, fc) = (b, fb);
+
+            if fa.signum() == fs.signum()
+            {
+                (a, fa) = (s, fs);
+            }
+            else
+            {
+                (b, fb) = (s, fs);
+            }
+
+            if fa.abs() < fb.abs()
+            {
+                (a, b, fa, fb) = (b, a, fb, fa);
+            }
+        }
+
+        Err(RootError::DidNotConverge)
+    }
+
+    // newton's method starting from `guess`, with derivatives from dual numbers
+    pub fn newton(&self, expression: &Expression, var: &str, guess: f64) -> Result<f64, RootError>
+    {
+        let mut x = guess;
+        for _ in 0..self.max_iterations
+        {
+            let (val, gradient) = expression
+                .eval_gradient(&HashMap::from([(var.to_string(), x)]))
+                .map_err(RootError::UnboundVariable)?;
+            if val == 0.
+            {
+                return Ok(x);
+            }
+
+            let slope = gradient.get(var).copied().unwrap_or(0.);
+            if slope == 0. || !slope.is_finite()
+            {
+                return Err(RootError::ZeroDerivative);
+            }
+
+            let step = val / slope;
+            x -= step;
+
+            if !x.is_finite()
+            {
+                return Err(RootError::DidNotConverge);
+            }
+            if step.abs() <= self.tolerance * x.abs().max(1.)
+            {
+                return Ok(x);
+            }
+        }
+
+        Err(RootError::DidNotConverge)
+    }
+
+    // every root in [lower, upper] that can be found by splitting the interval into `samples`
+    // pieces. sign changes are refined with brent's method, and roots where the function only
+    // touches zero are found by running newton's method from the smallest sampled values
+    pub fn find_roots(
+        &self,
+        expression: &Expression,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<Vec<f64>, RootError>
+    {
+        let mut f = SingleVariable::new(expression, var).map_err(RootError::UnboundVariable)?;
+        let mut roots: Vec<f64> = Vec::new();
+
+        let width = (upper - lower) / self.samples as f64;
+        let xs: Vec<f64> = (0..=self.samples)
+            .map(|i| lower + width * i as f64)
+            .collect();
+        let ys: Vec<f64> = xs.iter().map(|&x| f.eval(x)).collect();
+
+        for i in 0..self.samples
+        {
+            if ys[i] == 0.
+            {
+                roots.push(xs[i]);
+            }
+            else if ys[i + 1] != 0. && ys[i].signum() != ys[i + 1].signum()
+            {
+                // a sign change without a root, like 1/x around 0, fails to shrink and is skipped,
+                // and so is one brent can't narrow down in time
+                match self.brent_with(&mut f, xs[i], xs[i + 1])
+                {
+                    Ok(root) if f.eval(root).abs() <= ys[i].abs().min(ys[i + 1].abs()) =>
+                    {
+                        roots.push(root)
+                    },
+                    _ => (),
+                }
+            }
+            else if i > 0 && ys[i].abs() < ys[i - 1].abs() && ys[i].abs() < ys[i + 1].abs()
+            {
+                match self.newton(expression, var, xs[i])
+                {
+                    Ok(root) if root >= xs[i - 1] && root <= xs[i + 1] => roots.push(root),
+                    _ => (),
+                }
+            }
+        }
+        if ys[self.samples] == 0.
+        {
+            roots.push(xs[self.samples]);
+        }
+
+        roots.sort_by(f64::total_cmp);
+        roots.dedup_by(|root1, root2| (*root1 - *root2).abs() <= width);
+
+        Ok(roots)
+    }
+}
+
+// parses an equation like "3*x^2 - 2 = 5" into an expression that is zero wherever the equation
+// holds
+pub fn parse_equation(equation: &str) -> Result<Expression, RootError>
+{
+    match equation.split_once('=')
+    {
+        Some((lhs, rhs)) if !rhs.contains('=') =>
+        {
+            Ok(Expression::parse(&format!("({}) - ({})", lhs, rhs)))
+        },
+        _ => Err(RootError::NotAnEquation(equation.to_string())),
+    }
+}
+
+// roots of the equation for `var` between lower and upper
+pub fn solve_numeric(
+    equation: &str,
+    var: &str,
+    lower: f64,
+    upper: f64,
+) -> Result<Vec<f64>, RootError>
+{
+    RootFinder::new().find_roots(&parse_equation(equation)?, var, lower, upper)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn assert_close(result: f64, expected: f64)
+    {
+        assert!(
+            (result - expected).abs() < 1e-9,
+            "{} is not close to {}",
+            result,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_bracketing()
+    {
+        let finder = RootFinder::new();
+        let tree = Expression::parse("x^3 - 2*x - 5");
+        let expected = 2.0945514815423265;
+
+        assert_close(finder.bisection(&tree, "x", 2., 3.).unwrap(), expected);
+        assert_close(finder.brent(&tree, "x", 2., 3.).unwrap(), expected);
+        assert_close(finder.newton(&tree, "x", 2.).unwrap(), expected);
+
+        assert_eq!(
+            finder.brent(&tree, "x", 3., 4.),
+            Err(RootError::NoSignChange)
+        );
+        assert_eq!(
+            finder.brent(&Expression::parse("x - y"), "x", 0., 1.),
+            Err(RootError::UnboundVariable("y".to_string()))
+        );
+        assert_eq!(
+            finder.newton(&Expression::parse("x - y"), "x", 0.),
+            Err(RootError::UnboundVariable("y".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_non_convergence()
+    {
+        let finder = RootFinder { max_iterations: 5, ..RootFinder::new() };
+        let tree = Expression::parse("x^3 - 2*x - 5");
+
+        assert_eq!(
+            finder.bisection(&tree, "x", 2., 3.),
+            Err(RootError::DidNotConverge)
+        );
+        assert_eq!(
+            RootFinder::new().newton(&Expression::parse("x^2 + 1"), "x", 0.),
+            Err(RootError::ZeroDerivative)
+        );
+
+        // brent runs out of iterations on the root at 5^(1/3), but 1 is still found
+        let finder = RootFinder { max_iterations: 3, samples: 10, ..RootFinder::new() };
+        let tree = Expression::parse("(x - 1)*(x^3 - 5)");
+        assert_eq!(finder.find_roots(&tree, "x", 0., 2.5), Ok(vec![1.]));
+    }
+
+    #[test]
+    fn test_find_roots()
+    {
+        let roots = solve_numeric("3*x^2 - 2 = 5", "x", -10., 10.).unwrap();
+        let root = (7f64 / 3.).sqrt();
+        assert_eq!(roots.len(), 2);
+        assert_close(roots[0], -root);
+        assert_close(roots[1], root);
+
+        let roots = solve_numeric("sin(x) = 0", "x", -1., 7.).unwrap();
+        assert_eq!(roots.len(), 3);
+        for (root, expected) in
+            roots
+                .into_iter()
+                .zip([0., std::f64::consts::PI, std::f64::consts::TAU])
+        {
+            assert_close(root, expected);
+        }
+
+        // touches zero without crossing it
+        let roots = solve_numeric("(x - 1.3)^2 = 0", "x", 0., 2.).unwrap();
+        assert_eq!(roots.len(), 1);
+        assert!((roots[0] - 1.3).abs() < 1e-6);
+
+        assert_eq!(solve_numeric("1/x = 0", "x", -1., 1.5).unwrap(), vec![]);
+
+        assert_eq!(
+            solve_numeric("x^2 - 2", "x", 0., 2.),
+            Err(RootError::NotAnEquation("x^2 - 2".to_string()))
+        );
+        assert_eq!(
+            solve_numeric("x = 1 = y", "x", 0., 2.),
+            Err(RootError::NotAnEquation("x = 1 = y".to_string()))
+        );
+    }
+}
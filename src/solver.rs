@@ -1,7 +1,9 @@
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
 
+use crate::bound::EXPANDED_TERMS;
+use crate::numeric::{LiteralParser, Numeric};
 use crate::tokens::*;
 
 #[derive(Debug, PartialEq)]
@@ -11,6 +13,41 @@ pub struct Expression
     pub operands: Vec<Rc<RefCell<Expression>>>,
 }
 
+// part of an expression waiting to be printed
+enum Piece
+{
+    Text(String),
+    Node(Rc<RefCell<Expression>>),
+}
+
+// work left to do while evaluating an expression
+enum EvalFrame
+{
+    Visit(Rc<RefCell<Expression>>),
+    Apply(Operation),
+    Call(Function),
+    // gathers the last `len` values into an array
+    Collect(usize),
+}
+
+// the default drop would recurse once per level of the tree, so take the children apart with an
+// explicit stack instead. nodes shared with another tree are left to whoever else holds them
+impl Drop for Expression
+{
+    fn drop(&mut self)
+    {
+        let mut nodes = std::mem::take(&mut self.operands);
+
+        while let Some(node) = nodes.pop()
+        {
+            if let Ok(node) = Rc::try_unwrap(node)
+            {
+                nodes.append(&mut node.into_inner().operands);
+            }
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! vec_nodes {
     ($($i:ident),*) => {
@@ -38,6 +75,75 @@ macro_rules! vec_nodes {
 
 impl Expression
 {
+    pub fn parse(expr: &str) -> Expression
+    {
+        Self::from_postfix_tokens(shunting_yard(insert_implicit_multiplication(tokenize(expr))))
+    }
+
+    pub fn number(val: f64) -> Expression
+    {
+        Expression {
+            operator: Token::Number { val: val.to_string() },
+            operands: Vec::new(),
+        }
+    }
+
+    pub fn variable(name: &str) -> Expression
+    {
+        Expression {
+            operator: Token::Variable { name: name.to_string() },
+            operands: Vec::new(),
+        }
+    }
+
+    pub fn binary(op: Operation, lhs: Expression, rhs: Expression) -> Expression
+    {
+        Expression {
+            operator: Token::Operator { op },
+            operands: vec_nodes![lhs, rhs],
+        }
+    }
+
+    pub fn function(func: Function, arg: Expression) -> Expression
+    {
+        Expression {
+            operator: Token::Function { func },
+            operands: vec_nodes![arg],
+        }
+    }
+
+    pub fn array(elements: Vec<Expression>) -> Expression
+    {
+        Expression {
+            operator: Token::Array { len: elements.len() },
+            operands: elements
+                .into_iter()
+                .map(|element| Rc::new(RefCell::new(element)))
+                .collect(),
+        }
+    }
+
+    // copies every node instead of sharing them like cloning the operand Rcs would
+    pub fn deep_clone(&self) -> Expression
+    {
+        Self::from_postfix_tokens(self.to_postfix_tokens())
+    }
+
+    pub fn contains_variable(&self, name: &str) -> bool
+    {
+        self.to_postfix_tokens().iter().any(|token| token.get_variable() == Some(name))
+    }
+
+    // the value of this node if it's a plain number
+    pub fn number_value(&self) -> Option<f64>
+    {
+        match self.operator
+        {
+            Token::Number { .. } => self.operator.get_number(),
+            _ => None,
+        }
+    }
+
     pub fn from_postfix_tokens(postfix_tokens: Vec<Token>) -> Expression
     {
         let mut val_stack: Vec<Expression> = Vec::new();
@@ -46,8 +152,10 @@ impl Expression
         {
             match token
             {
-                #[rustfmt::skip]
-                Token::Number { .. } => val_stack.push(Expression { operator: token, operands: Vec::new() }),
+                Token::Number { .. } | Token::Variable { .. } =>
+                {
+                    val_stack.push(Expression { operator: token, operands: Vec::new() })
+                },
                 Token::Operator { .. } =>
                 {
                     let num2 = val_stack.pop().expect("tried to pop value off empty stack");
@@ -56,7 +164,17 @@ impl Expression
 
                     val_stack.push(Expression { operator: token, operands: new_children });
                 },
-                Token::LSep | Token::RSep =>
+                Token::Function { .. } | Token::Array { .. } =>
+                {
+                    let args = val_stack.split_off(val_stack.len() - token.arity());
+                    let new_children = args
+                        .into_iter()
+                        .map(|arg| Rc::new(RefCell::new(arg)))
+                        .collect();
+
+                    val_stack.push(Expression { operator: token, operands: new_children });
+                },
+                Token::LSep | Token::RSep | Token::LBracket | Token::RBracket | Token::Comma =>
                 {
                     panic!("unexpected token for postfix expression: {:?}", token)
                 },
@@ -67,34 +185,104 @@ impl Expression
         val_stack.pop().unwrap()
     }
 
-    fn print(&self)
+    pub fn to_postfix_tokens(&self) -> Vec<Token>
+    {
+        // visit nodes root, right, left and reverse at the end to get postfix order
+        let mut tokens = vec![self.operator.clone()];
+        let mut node_stack: Vec<Rc<RefCell<Expression>>> = self.operands.clone();
+
+        while let Some(node) = node_stack.pop()
+        {
+            let node = node.borrow();
+            tokens.push(node.operator.clone());
+            node_stack.extend(node.operands.iter().cloned());
+        }
+
+        tokens.reverse();
+        tokens
+    }
+
+    pub fn print(&self)
     {
         self.print_expression();
         println!();
     }
 
-    fn print_expression(&self)
+    pub fn print_expression(&self)
     {
-        if self.operands.is_empty()
-        {
-            self.print_token();
-        }
-        else
-        {
-            print!("( ");
-            self.operands[0].borrow().print_expression();
-            self.print_token();
-            self.operands[1].borrow().print_expression();
-            print!(") ");
-        }
+        print!("{}", self.expression_string());
     }
 
-    fn print_token(&self)
+    pub fn expression_string(&self) -> String
+    {
+        self.render(Self::expression_pieces)
+    }
+
+    fn expression_pieces(&self) -> Vec<Piece>
     {
         match &self.operator
         {
-            Token::Number { val } => print!("{} ", val),
-            Token::Operator { op } => print!("{} ", op.str()),
+            Token::Number { val } => vec![Piece::Text(format!("{} ", val))],
+            Token::Variable { name } => vec![Piece::Text(format!("{} ", name))],
+            // a negative base keeps its own parentheses, since "-2 ^ 2" would read as -(2^2)
+            Token::Operator { op: Operation::Exponentiation } if self.negative_base() => vec![
+                Piece::Text("( ( ".to_string()),
+                Piece::Node(self.operands[0].clone()),
+                Piece::Text(") ^ ".to_string()),
+                Piece::Node(self.operands[1].clone()),
+                Piece::Text(") ".to_string()),
+            ],
+            Token::Operator { op } => vec![
+                Piece::Text("( ".to_string()),
+                Piece::Node(self.operands[0].clone()),
+                Piece::Text(format!("{} ", op.str())),
+                Piece::Node(self.operands[1].clone()),
+                Piece::Text(") ".to_string()),
+            ],
+            Token::Function { func: Function::Negate } => vec![
+                Piece::Text("( - ".to_string()),
+                Piece::Node(self.operands[0].clone()),
+                Piece::Text(") ".to_string()),
+            ],
+            Token::Function { func } if func.is_postfix() && self.negative_base() => vec![
+                Piece::Text("( ".to_string()),
+                Piece::Node(self.operands[0].clone()),
+                Piece::Text(") ! ".to_string()),
+            ],
+            Token::Function { func } if func.is_postfix() => vec![
+                Piece::Node(self.operands[0].clone()),
+                Piece::Text("! ".to_string()),
+            ],
+            Token::Function { func } =>
+            {
+                let mut pieces = vec![Piece::Text(format!("{}( ", func.name()))];
+                for (i, operand) in self.operands.iter().enumerate()
+                {
+                    if i > 0
+                    {
+                        pieces.push(Piece::Text(", ".to_string()));
+                    }
+                    pieces.push(Piece::Node(operand.clone()));
+                }
+                pieces.push(Piece::Text(") ".to_string()));
+
+                pieces
+            },
+            Token::Array { .. } =>
+            {
+                let mut pieces = vec![Piece::Text("[ ".to_string())];
+                for (i, operand) in self.operands.iter().enumerate()
+                {
+                    if i > 0
+                    {
+                        pieces.push(Piece::Text(", ".to_string()));
+                    }
+                    pieces.push(Piece::Node(operand.clone()));
+                }
+                pieces.push(Piece::Text("] ".to_string()));
+
+                pieces
+            },
             _ => panic!(
                 "unexpected token encountered while printing: {:?}",
                 self.operator
@@ -102,7 +290,15 @@ impl Expression
         }
     }
 
-    fn print_latex(&self)
+    fn negative_base(&self) -> bool
+    {
+        self.operands[0]
+            .borrow()
+            .number_value()
+            .is_some_and(|val| val < 0.)
+    }
+
+    pub fn print_latex(&self)
     {
         //print!("\\[");
         self.print_expression_latex();
@@ -110,53 +306,147 @@ impl Expression
         println!();
     }
 
-    fn print_expression_latex(&self)
+    pub fn print_expression_latex(&self)
     {
+        print!("{}", self.latex_string());
+    }
+
+    pub fn latex_string(&self) -> String
+    {
+        self.render(Self::latex_pieces)
+    }
+
+    fn latex_pieces(&self) -> Vec<Piece>
+    {
+        let text = |text: &str| Piece::Text(text.to_string());
+
         match &self.operator
         {
-            Token::Number { val } => print!("{}", val),
-            Token::Operator { op } => match op
+            Token::Number { val } => vec![text(&val.replace('±', "\\pm "))],
+            Token::Variable { name } => vec![text(name)],
+            Token::Operator { op } =>
             {
-                Operation::Addition =>
+                let (left, middle, right) = match op
                 {
-                    print!("{{");
-                    self.operands[0].borrow().print_expression_latex();
-                    print!("}}+{{");
-                    self.operands[1].borrow().print_expression_latex();
-                    print!("}}");
-                },
-                Operation::Subtraction =>
+                    Operation::Addition => ("{", "}+{", "}"),
+                    Operation::Subtraction => ("{", "}-{", "}"),
+                    Operation::Multiplication => ("{", "}\\cdot{", "}"),
+                    Operation::Division => ("\\frac{", "}{", "}"),
+                    Operation::IntegerDivision =>
+                    {
+                        ("\\left\\lfloor\\frac{", "}{", "}\\right\\rfloor")
+                    },
+                    Operation::Remainder => ("{", "}\\bmod{", "}"),
+                    Operation::Exponentiation => ("{", "}^{", "}"),
+                    Operation::Dot => ("{", "}\\cdot{", "}"),
+                };
+
+                vec![
+                    text(left),
+                    Piece::Node(self.operands[0].clone()),
+                    text(middle),
+                    Piece::Node(self.operands[1].clone()),
+                    text(right),
+                ]
+            },
+            Token::Function { func } if func.binds_variable() =>
+            {
+                let symbol = match func
                 {
-                    print!("{{");
-                    self.operands[0].borrow().print_expression_latex();
-                    print!("}}-{{");
-                    self.operands[1].borrow().print_expression_latex();
-                    print!("}}");
-                },
-                Operation::Multiplication =>
+                    Function::Product => "\\prod_{",
+                    _ => "\\sum_{",
+                };
+
+                vec![
+                    text(symbol),
+                    Piece::Node(self.operands[0].clone()),
+                    text("="),
+                    Piece::Node(self.operands[1].clone()),
+                    text("}^{"),
+                    Piece::Node(self.operands[2].clone()),
+                    text("}\\left("),
+                    Piece::Node(self.operands[3].clone()),
+                    text("\\right)"),
+                ]
+            },
+            Token::Function { func } =>
+            {
+                let atomic = self.operands[0].borrow().operands.is_empty();
+                let (left, right) = match func
                 {
-                    print!("{{");
-                    self.operands[0].borrow().print_expression_latex();
-                    print!("}}\\cdot{{");
-                    self.operands[1].borrow().print_expression_latex();
-                    print!("}}");
-                },
-                Operation::Division =>
+                    Function::Factorial if atomic => ("{", "}!"),
+                    Function::Factorial => ("\\left(", "\\right)!"),
+                    Function::Negate if atomic => ("-{", "}"),
+                    Function::Negate => ("-\\left(", "\\right)"),
+                    Function::Sqrt => ("\\sqrt{", "}"),
+                    Function::Exp => ("\\exp\\left(", "\\right)"),
+                    Function::Ln => ("\\ln\\left(", "\\right)"),
+                    Function::Sin => ("\\sin\\left(", "\\right)"),
+                    Function::Cos => ("\\cos\\left(", "\\right)"),
+                    Function::Tan => ("\\tan\\left(", "\\right)"),
+                    Function::Det => ("\\det\\left(", "\\right)"),
+                    Function::Inverse => ("{\\left(", "\\right)}^{-1}"),
+                    Function::Aggregate(Aggregate::Sum) => ("\\sum\\left(", "\\right)"),
+                    Function::Aggregate(Aggregate::Min) => ("\\min\\left(", "\\right)"),
+                    Function::Aggregate(Aggregate::Max) => ("\\max\\left(", "\\right)"),
+                    Function::Aggregate(Aggregate::Mean) =>
+                    {
+                        ("\\operatorname{mean}\\left(", "\\right)")
+                    },
+                    Function::Aggregate(Aggregate::Median) =>
+                    {
+                        ("\\operatorname{median}\\left(", "\\right)")
+                    },
+                    Function::Aggregate(Aggregate::Stdev) =>
+                    {
+                        ("\\operatorname{stdev}\\left(", "\\right)")
+                    },
+                    Function::Sum | Function::Product => unreachable!(),
+                };
+
+                vec![
+                    text(left),
+                    Piece::Node(self.operands[0].clone()),
+                    text(right),
+                ]
+            },
+            Token::Array { .. } =>
+            {
+                // an array of arrays is a matrix with one of them per row, and anything else is
+                // a vector, which is a column just like in matrix products
+                let is_row = |operand: &Rc<RefCell<Expression>>| {
+                    matches!(operand.borrow().operator, Token::Array { .. })
+                };
+                let is_matrix = !self.operands.is_empty() && self.operands.iter().all(is_row);
+                let rows: Vec<Vec<_>> = match is_matrix
                 {
-                    print!("\\frac{{");
-                    self.operands[0].borrow().print_expression_latex();
-                    print!("}}{{");
-                    self.operands[1].borrow().print_expression_latex();
-                    print!("}}");
-                },
-                Operation::Exponentiation =>
+                    true => self
+                        .operands
+                        .iter()
+                        .map(|row| row.borrow().operands.clone())
+                        .collect(),
+                    false => self.operands.iter().map(|entry| vec![entry.clone()]).collect(),
+                };
+
+                let mut pieces = vec![text("\\begin{bmatrix}")];
+                for (i, row) in rows.into_iter().enumerate()
                 {
-                    print!("{{");
-                    self.operands[0].borrow().print_expression_latex();
-                    print!("}}^{{");
-                    self.operands[1].borrow().print_expression_latex();
-                    print!("}}");
-                },
+                    if i > 0
+                    {
+                        pieces.push(text("\\\\"));
+                    }
+                    for (j, entry) in row.into_iter().enumerate()
+                    {
+                        if j > 0
+                        {
+                            pieces.push(text("&"));
+                        }
+                        pieces.push(Piece::Node(entry));
+                    }
+                }
+                pieces.push(text("\\end{bmatrix}"));
+
+                pieces
             },
             _ => panic!(
                 "unexpected token encountered while printing: {:?}",
@@ -165,44 +455,139 @@ impl Expression
         }
     }
 
+    // builds a string with an explicit stack instead of recursion, so deeply nested expressions
+    // can't overflow the call stack. `pieces` splits a single node into text and child nodes
+    fn render(&self, pieces: fn(&Expression) -> Vec<Piece>) -> String
+    {
+        let mut string = String::new();
+        let mut piece_stack: Vec<Piece> = pieces(self);
+        piece_stack.reverse();
+
+        while let Some(piece) = piece_stack.pop()
+        {
+            match piece
+            {
+                Piece::Text(text) => string.push_str(&text),
+                Piece::Node(node) => piece_stack.extend(pieces(&node.borrow()).into_iter().rev()),
+            }
+        }
+
+        string
+    }
+
     pub fn eval(&self) -> f64
     {
         // let tree = Rc::new(RefCell::new(self));
 
-        Self::eval_helper(self)
+        self.eval_with(&HashMap::new())
+    }
+
+    pub fn eval_with(&self, vars: &HashMap<String, f64>) -> f64
+    {
+        Self::eval_helper(self, vars, &f64::parse_literal)
+            .unwrap_or_else(|err| panic!("error evaluating expression: {:?}", err))
     }
 
-    fn eval_helper(tree_node: &Expression) -> f64
+    // walks the tree with an explicit stack instead of recursion, so deeply nested expressions
+    // can't overflow the call stack
+    pub(crate) fn eval_helper<N: Numeric>(
+        tree_node: &Expression,
+        vars: &HashMap<String, N>,
+        literal: LiteralParser<N>,
+    ) -> Result<N, N::Error>
     {
-        if tree_node.operands.is_empty()
+        let mut frames: Vec<EvalFrame> = Vec::new();
+        let mut values: Vec<N> = Vec::new();
+
+        Self::push_eval_frames(tree_node, vars, literal, &mut frames, &mut values)?;
+        while let Some(frame) = frames.pop()
         {
-            return tree_node
-                .operator
-                .get_number()
-                .expect("error getting number from token");
-        }
+            match frame
+            {
+                EvalFrame::Visit(node) =>
+                {
+                    Self::push_eval_frames(&node.borrow(), vars, literal, &mut frames, &mut values)?
+                },
+                EvalFrame::Apply(op) =>
+                {
+                    let r2 = values.pop().expect("tried to pop value off empty stack");
+                    let r1 = values.pop().expect("tried to pop value off empty stack");
 
-        let children = &tree_node.operands;
+                    values.push(r1.apply(op, &r2)?);
+                },
+                EvalFrame::Call(func) =>
+                {
+                    let val = values
+                        .last_mut()
+                        .expect("tried to pop value off empty stack");
 
-        let c1 = children[0].borrow();
-        let r1 = Self::eval_helper(&c1);
+                    *val = val.call(func)?;
+                },
+                EvalFrame::Collect(len) =>
+                {
+                    let elements = values.split_off(values.len() - len);
+                    values.push(N::array(elements)?);
+                },
+            }
+        }
 
-        let c2 = children[1].borrow();
-        let r2 = Self::eval_helper(&c2);
+        Ok(values
+            .pop()
+            .expect("evaluation left no result on the stack"))
+    }
 
-        let op = tree_node
-            .operator
-            .get_operator()
-            .expect(&format!(
+    fn push_eval_frames<N: Numeric>(
+        tree_node: &Expression,
+        vars: &HashMap<String, N>,
+        literal: LiteralParser<N>,
+        frames: &mut Vec<EvalFrame>,
+        values: &mut Vec<N>,
+    ) -> Result<(), N::Error>
+    {
+        match &tree_node.operator
+        {
+            Token::Number { val } => values.push(literal(val)?),
+            Token::Variable { name } =>
+            {
+                values.push(
+                    vars.get(name)
+                        .unwrap_or_else(|| panic!("no value given for variable: {}", name))
+                        .clone(),
+                );
+            },
+            Token::Operator { op } =>
+            {
+                frames.push(EvalFrame::Apply(*op));
+                frames.push(EvalFrame::Visit(tree_node.operands[1].clone()));
+                frames.push(EvalFrame::Visit(tree_node.operands[0].clone()));
+            },
+            Token::Function { func } if func.binds_variable() =>
+            {
+                values.push(Self::eval_bound(*func, &tree_node.operands, vars, literal)?);
+            },
+            Token::Function { func } =>
+            {
+                frames.push(EvalFrame::Call(*func));
+                frames.push(EvalFrame::Visit(tree_node.operands[0].clone()));
+            },
+            Token::Array { len } =>
+            {
+                frames.push(EvalFrame::Collect(*len));
+                for operand in tree_node.operands.iter().rev()
+                {
+                    frames.push(EvalFrame::Visit(operand.clone()));
+                }
+            },
+            _ => panic!(
                 "error getting operator from token: {:?}",
                 &tree_node.operator
-            ))
-            .clone();
+            ),
+        }
 
-        Self::eval_binary_op(r1, op, r2)
+        Ok(())
     }
 
-    fn eval_binary_op(val1: f64, op: Operation, val2: f64) -> f64
+    pub(crate) fn eval_binary_op(val1: f64, op: Operation, val2: f64) -> f64
     {
         match op
         {
@@ -210,27 +595,75 @@ impl Expression
             Operation::Subtraction => val1 - val2,
             Operation::Multiplication => val1 * val2,
             Operation::Division => val1 / val2,
+            Operation::IntegerDivision => (val1 / val2).floor(),
+            Operation::Remainder => floored_remainder(val1, val2),
             Operation::Exponentiation => val1.powf(val2),
+            // plain numbers are vectors of length one
+            Operation::Dot => val1 * val2,
+        }
+    }
+
+    pub(crate) fn eval_function(func: Function, val: f64) -> f64
+    {
+        match func
+        {
+            Function::Sqrt => val.sqrt(),
+            Function::Exp => val.exp(),
+            Function::Ln => val.ln(),
+            Function::Sin => val.sin(),
+            Function::Cos => val.cos(),
+            Function::Tan => val.tan(),
+            Function::Factorial => factorial(val),
+            Function::Negate => -val,
+            // and square matrices of size one
+            Function::Det => val,
+            Function::Inverse => 1. / val,
+            // and lists of one
+            Function::Aggregate(aggregate) if aggregate.min_len() > 1 => f64::NAN,
+            Function::Aggregate(_) => val,
+            Function::Sum | Function::Product => panic!(
+                "{} binds a variable and can't be evaluated from a single value",
+                func.name()
+            ),
+        }
+    }
+
+    // value of an operator or function node given the values of its operands
+    pub(crate) fn eval_operation(operator: &Token, operand_values: &[f64]) -> f64
+    {
+        match operator
+        {
+            Token::Operator { op } =>
+            {
+                Self::eval_binary_op(operand_values[0], *op, operand_values[1])
+            },
+            Token::Function { func } => Self::eval_function(*func, operand_values[0]),
+            // floats have no vectors or matrices
+            Token::Array { .. } => f64::NAN,
+            _ => panic!("attempted to eval invalid token: {:?}", operator),
         }
     }
 
     pub fn simplify(self) -> f64
     {
-        let tree: Rc<RefCell<Expression>> = Rc::new(RefCell::new(self));
+        // nodes are evaluated once all their operands are numbers, which never happens for the
+        // bound variable of a sum
+        let expression = match self.has_bound_variables()
+        {
+            true => self.expand_ranges(EXPANDED_TERMS),
+            false => self,
+        };
+        let tree: Rc<RefCell<Expression>> = Rc::new(RefCell::new(expression));
         //tree.borrow().print();
 
-        while matches!(&tree.borrow().operator, Token::Operator { .. })
+        while !tree.borrow().operands.is_empty()
         {
             let node_to_eval = Self::find_node(tree.clone());
             Self::evaluate_node(node_to_eval);
             //tree.borrow().print();
         }
 
-        let result = tree
-            .borrow()
-            .operator
-            .get_number()
-            .expect("error getting number from token");
+        let result = tree.borrow().leaf_value();
 
         result
     }
@@ -245,10 +678,9 @@ impl Expression
         {
             let current_node = node_queue.pop_front().unwrap();
 
-            if let Token::Operator { .. } = current_node.borrow().operator
+            if !current_node.borrow().operands.is_empty()
             {
-                node_queue.push_back(current_node.borrow().operands[0].clone());
-                node_queue.push_back(current_node.borrow().operands[1].clone());
+                node_queue.extend(current_node.borrow().operands.iter().cloned());
 
                 selected_node = current_node.clone();
             };
@@ -257,26 +689,70 @@ impl Expression
         selected_node
     }
 
+    // the value of a node without operands, where an empty array is NaN like any other array
+    pub(crate) fn leaf_value(&self) -> f64
+    {
+        match &self.operator
+        {
+            Token::Number { .. } => self.operator.get_number().unwrap(),
+            Token::Array { .. } => f64::NAN,
+            Token::Variable { name } => panic!("no value given for variable: {}", name),
+            operator => panic!("attempted to eval invalid token: {:?}", operator),
+        }
+    }
+
+    pub(crate) fn set_number(&mut self, val: f64)
+    {
+        self.operator = Token::Number { val: val.to_string() };
+        self.operands.clear();
+    }
+
     pub fn evaluate_node(node: Rc<RefCell<Expression>>)
     {
         let mut node = node.borrow_mut();
 
-        let val1 = node.operands[0].borrow().operator.get_number().unwrap();
-        let val2 = node.operands[1].borrow().operator.get_number().unwrap();
+        let operand_values: Vec<f64> = node
+            .operands
+            .iter()
+            .map(|operand| operand.borrow().leaf_value())
+            .collect();
 
-        if let Token::Operator { op } = &node.operator
-        {
-            let val = Self::eval_binary_op(val1, op.clone(), val2).to_string();
-            node.operator = Token::Number { val };
-            node.operands.clear();
-        }
-        else
-        {
-            panic!("attempted to eval invalid token: {:?}", node.operator);
-        }
+        let val = Self::eval_operation(&node.operator, &operand_values);
+        node.set_number(val);
     }
 }
 
+// what's left over from rounding val1 / val2 down, so it has the sign of val2
+pub(crate) fn floored_remainder(val1: f64, val2: f64) -> f64
+{
+    let remainder = val1 % val2;
+
+    match remainder != 0. && (remainder < 0.) != (val2 < 0.)
+    {
+        true => remainder + val2,
+        false => remainder,
+    }
+}
+
+// val! for whole numbers, and NaN for anything else
+pub(crate) fn factorial(val: f64) -> f64
+{
+    if val < 0. || val.fract() != 0.
+    {
+        return f64::NAN;
+    }
+
+    let mut result = 1f64;
+    let mut i = 2.;
+    while i <= val && result.is_finite()
+    {
+        result *= i;
+        i += 1.;
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests
 {
@@ -454,4 +930,98 @@ mod tests
         let root = Rc::new(RefCell::new(expression_tree));
         assert_eq!(Tree::find_node(root.clone()), root.borrow().operands[1]);
     }
+
+    #[test]
+    fn test_eval_with()
+    {
+        let tree = Expression::parse("x^2 + 3*(y - x)");
+        let vars = HashMap::from([("x".to_string(), 4.), ("y".to_string(), 1.5)]);
+
+        assert_eq!(tree.eval_with(&vars), 8.5);
+    }
+
+    #[test]
+    fn test_deep_expression()
+    {
+        let depth = 200_000;
+
+        // right associative, so every ^ is nested one level deeper than the last
+        let expr = "1^".repeat(depth) + "1";
+        let tree = Expression::parse(&expr);
+        assert_eq!(tree.eval(), 1.);
+        assert_eq!(tree.expression_string().len(), 8 * depth + 2);
+        assert_eq!(tree.latex_string().len(), 6 * depth + 1);
+        drop(tree);
+
+        let expr = "(1+".repeat(depth) + "1" + &")".repeat(depth);
+        let tree = Expression::parse(&expr);
+        assert_eq!(tree.eval(), (depth + 1) as f64);
+    }
+
+    #[test]
+    fn test_functions()
+    {
+        let tree = Expression::parse("sqrt(x^2 + 9) * exp(ln(2)) - sin(0) + cos(0) * tan(0)");
+        let vars = HashMap::from([("x".to_string(), 4.)]);
+
+        assert_eq!(tree.eval_with(&vars), 10.);
+        assert_eq!(Expression::parse("sqrt(16) + ln(1)").simplify(), 4.);
+        assert_eq!(
+            Expression::parse("sqrt(x) / ln(y)").latex_string(),
+            "\\frac{\\sqrt{x}}{\\ln\\left(y\\right)}"
+        );
+        assert_eq!(Expression::parse("sin(x)").expression_string(), "sin( x ) ");
+    }
+
+    #[test]
+    fn test_rounding_operators()
+    {
+        assert_eq!(Expression::parse("7 // 2 + 7 % 3 + 5!").eval(), 124.);
+        assert_eq!(Expression::parse("(0-7) // 2").eval(), -4.);
+        assert_eq!(Expression::parse("(0-7) % 2 + 7 % (0-2) + 5.5 % 2").eval(), 1.5);
+        assert!(Expression::parse("2.5!").eval().is_nan());
+
+        let tree = Expression::parse("x // 2 + (x + 1)! % 3!");
+        assert_eq!(
+            tree.expression_string(),
+            "( ( x // 2 ) + ( ( x + 1 ) ! % 3 ! ) ) "
+        );
+        assert_eq!(
+            tree.latex_string(),
+            "{\\left\\lfloor\\frac{x}{2}\\right\\rfloor}+{{\\left({x}+{1}\\right)!}\\bmod{{3}!}}"
+        );
+    }
+
+    #[test]
+    fn test_negation()
+    {
+        // the minus sign binds looser than ^, and i is just a variable outside of complex numbers
+        assert_eq!(Expression::parse("-2^2").eval(), -4.);
+        assert_eq!(Expression::parse("2^-2 + (-2)^2 - -(1+1)^2 * 3").eval(), 16.25);
+        assert_eq!(Expression::parse("sum(i, 1, 3, 2i)").eval(), 12.);
+
+        let tree = Expression::parse("-x^2 + 2i");
+        let vars = HashMap::from([("x".to_string(), 3.), ("i".to_string(), 3.)]);
+        assert_eq!(tree.eval_with(&vars), -3.);
+        assert_eq!(tree.expression_string(), "( ( - ( x ^ 2 ) ) + ( 2 * i ) ) ");
+        assert_eq!(tree.latex_string(), "{-\\left({x}^{2}\\right)}+{{2}\\cdot{i}}");
+
+        // a negative base keeps its parentheses, so the printed expression reads back the same
+        let tree = Expression::binary(
+            Operation::Exponentiation,
+            Expression::number(-2.),
+            Expression::number(2.),
+        );
+        assert_eq!(tree.expression_string(), "( ( -2 ) ^ 2 ) ");
+        assert_eq!(Expression::parse(&tree.expression_string()).eval(), 4.);
+    }
+
+    #[test]
+    fn test_latex_string()
+    {
+        let tree = Expression::parse("(x+2)/3^y*4");
+
+        assert_eq!(tree.latex_string(), "{\\frac{{x}+{2}}{{3}^{y}}}\\cdot{4}");
+        assert_eq!(tree.expression_string(), "( ( ( x + 2 ) / ( 3 ^ y ) ) * 4 ) ");
+    }
 }
@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Token
 {
     Number
@@ -8,13 +8,35 @@ pub enum Token
         val: String,
     },
 
+    Variable
+    {
+        name: String,
+    },
+
     Operator
     {
         op: Operation,
     },
 
+    Function
+    {
+        func: Function,
+    },
+
+    // a vector or matrix built from the `len` values before it, written like "[1, 2, 3]" or
+    // "[[1, 2], [3, 4]]"
+    Array
+    {
+        len: usize,
+    },
+
     LSep,
     RSep,
+    // the brackets around the elements of an array
+    LBracket,
+    RBracket,
+    // separates the arguments of functions that take more than one, and the elements of an array
+    Comma,
 }
 
 impl Token
@@ -34,6 +56,31 @@ impl Token
         }
     }
 
+    pub fn get_variable(&self) -> Option<&str>
+    {
+        match self
+        {
+            Token::Variable { name } => Some(name),
+            _ => None,
+        }
+    }
+
+    // number of operands a node with this token takes
+    pub fn arity(&self) -> usize
+    {
+        match self
+        {
+            Token::Number { .. } | Token::Variable { .. } => 0,
+            Token::Operator { .. } => 2,
+            Token::Function { func } => func.arity(),
+            Token::Array { len } => *len,
+            Token::LSep | Token::RSep | Token::LBracket | Token::RBracket | Token::Comma =>
+            {
+                panic!("separator tokens have no operands: {:?}", self)
+            },
+        }
+    }
+
     pub fn get_operator(&self) -> Option<&Operation>
     {
         match self
@@ -42,6 +89,15 @@ impl Token
             _ => None,
         }
     }
+
+    pub fn get_function(&self) -> Option<&Function>
+    {
+        match self
+        {
+            Token::Function { func } => Some(func),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -51,14 +107,19 @@ pub enum Associativity
     Right,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Operation
 {
     Addition,
     Subtraction,
     Multiplication,
     Division,
+    // division rounded down, and what's left over from it
+    IntegerDivision,
+    Remainder,
     Exponentiation,
+    // the dot product of vectors, written "[1, 2] . [3, 4]"
+    Dot,
 }
 
 impl Operation
@@ -71,7 +132,10 @@ impl Operation
             Self::Subtraction => "-".to_string(),
             Self::Multiplication => "*".to_string(),
             Self::Division => "/".to_string(),
+            Self::IntegerDivision => "//".to_string(),
+            Self::Remainder => "%".to_string(),
             Self::Exponentiation => "^".to_string(),
+            Self::Dot => ".".to_string(),
         }
     }
 
@@ -80,7 +144,8 @@ impl Operation
         match self
         {
             Self::Addition | Self::Subtraction => 2,
-            Self::Multiplication | Self::Division => 3,
+            Self::Multiplication | Self::Division | Self::IntegerDivision | Self::Remainder => 3,
+            Self::Dot => 3,
             Self::Exponentiation => 4,
         }
     }
@@ -91,11 +156,143 @@ impl Operation
         {
             #[rustfmt::skip]
             Self::Addition | Self::Subtraction | Self::Multiplication | Self::Division => Associativity::Left,
+            Self::IntegerDivision | Self::Remainder | Self::Dot => Associativity::Left,
             Self::Exponentiation => Associativity::Right,
         }
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Function
+{
+    Sqrt,
+    Exp,
+    Ln,
+    Sin,
+    Cos,
+    Tan,
+    // sum(k, 1, 100, k^2) and prod(i, 1, 10, i) bind their first argument to every integer from
+    // the second to the third in turn
+    Sum,
+    Product,
+    // written after its operand, like "30!"
+    Factorial,
+    // a minus sign in front of a value, like "-x" or "-2^2", which is -(2^2)
+    Negate,
+    // the determinant and inverse of a square matrix
+    Det,
+    Inverse,
+    // a statistic of the values in a list, like "mean([3, 5, 8])"
+    Aggregate(Aggregate),
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Aggregate
+{
+    // written like the bound sum but with a single argument, "sum([1, 2, 3])"
+    Sum,
+    Mean,
+    Median,
+    // the sample standard deviation, dividing by one less than the number of values
+    Stdev,
+    Min,
+    Max,
+}
+
+impl Aggregate
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            Self::Sum => "sum",
+            Self::Mean => "mean",
+            Self::Median => "median",
+            Self::Stdev => "stdev",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+
+    // the fewest values the statistic is defined for. a sum of nothing is 0
+    pub fn min_len(&self) -> usize
+    {
+        match self
+        {
+            Self::Sum => 0,
+            Self::Stdev => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl Function
+{
+    pub fn from_name(name: &str) -> Option<Function>
+    {
+        match name
+        {
+            "sqrt" => Some(Self::Sqrt),
+            "exp" => Some(Self::Exp),
+            "ln" => Some(Self::Ln),
+            "sin" => Some(Self::Sin),
+            "cos" => Some(Self::Cos),
+            "tan" => Some(Self::Tan),
+            "sum" => Some(Self::Sum),
+            "prod" => Some(Self::Product),
+            "det" => Some(Self::Det),
+            "inv" => Some(Self::Inverse),
+            "mean" => Some(Self::Aggregate(Aggregate::Mean)),
+            "median" => Some(Self::Aggregate(Aggregate::Median)),
+            "stdev" => Some(Self::Aggregate(Aggregate::Stdev)),
+            "min" => Some(Self::Aggregate(Aggregate::Min)),
+            "max" => Some(Self::Aggregate(Aggregate::Max)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            Self::Sqrt => "sqrt",
+            Self::Exp => "exp",
+            Self::Ln => "ln",
+            Self::Sin => "sin",
+            Self::Cos => "cos",
+            Self::Tan => "tan",
+            Self::Sum => "sum",
+            Self::Product => "prod",
+            Self::Factorial => "factorial",
+            Self::Negate => "-",
+            Self::Det => "det",
+            Self::Inverse => "inv",
+            Self::Aggregate(aggregate) => aggregate.name(),
+        }
+    }
+
+    pub fn arity(&self) -> usize
+    {
+        match self
+        {
+            Self::Sum | Self::Product => 4,
+            _ => 1,
+        }
+    }
+
+    // whether the function binds a variable in its arguments instead of taking a plain value
+    pub fn binds_variable(&self) -> bool
+    {
+        matches!(self, Self::Sum | Self::Product)
+    }
+
+    // whether the function comes after its operand without parentheses
+    pub fn is_postfix(&self) -> bool
+    {
+        matches!(self, Self::Factorial)
+    }
+}
+
 pub fn tokenize(string: &str) -> Vec<Token>
 {
     let chars: Vec<char> = string.chars().collect();
@@ -109,15 +306,72 @@ pub fn tokenize(string: &str) -> Vec<Token>
             i += 1;
             continue;
         }
-        else if chars[i].is_numeric() || chars[i] == '.'
+        else if chars[i].is_numeric()
+            || starts_fraction(&chars, i)
+            || is_negative_number(&chars, i, &tokens)
         {
             let mut j = i + 1;
             while j < chars.len() && (chars[j].is_numeric() || chars[j] == '.')
             {
                 j += 1;
             }
-            let token = Token::Number {
-                val: String::from(&string[i..j]),
+            let mut val: String = chars[i..j].iter().collect();
+
+            // a measurement with its uncertainty, like "3.2 ± 0.1", is a single number
+            let mut k = j;
+            while k < chars.len() && chars[k].is_whitespace()
+            {
+                k += 1;
+            }
+            if chars.get(k) == Some(&'±')
+            {
+                k += 1;
+                while k < chars.len() && chars[k].is_whitespace()
+                {
+                    k += 1;
+                }
+                j = k;
+                while j < chars.len() && (chars[j].is_numeric() || chars[j] == '.')
+                {
+                    j += 1;
+                }
+                val.push('±');
+                val.extend(&chars[k..j]);
+            }
+
+            // ^ and ! bind tighter than the minus sign, so "-2^2" is -(2^2) and not (-2)^2
+            if val.starts_with('-')
+                && chars[j..]
+                    .iter()
+                    .find(|c| !c.is_whitespace())
+                    .is_some_and(|c| *c == '^' || *c == '!')
+            {
+                tokens.push(Token::Function { func: Function::Negate });
+                val.remove(0);
+            }
+
+            tokens.push(Token::Number { val });
+            i = j - 1;
+        }
+        else if chars[i].is_alphabetic() || chars[i] == '_'
+        {
+            let mut j = i + 1;
+            while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_')
+            {
+                j += 1;
+            }
+            let name: String = chars[i..j].iter().collect();
+            let called = chars[j..]
+                .iter()
+                .find(|c| !c.is_whitespace())
+                .is_some_and(|c| *c == '(');
+            let token = match Function::from_name(&name)
+            {
+                // statistics have names like min and max that are common for variables and
+                // units too, so they're only functions when called
+                Some(Function::Aggregate(_)) if !called => Token::Variable { name },
+                Some(func) => Token::Function { func },
+                None => Token::Variable { name },
             };
             tokens.push(token);
             i = j - 1;
@@ -130,6 +384,31 @@ pub fn tokenize(string: &str) -> Vec<Token>
         {
             tokens.push(Token::RSep);
         }
+        else if chars[i] == '['
+        {
+            tokens.push(Token::LBracket);
+        }
+        else if chars[i] == ']'
+        {
+            tokens.push(Token::RBracket);
+        }
+        else if chars[i] == ','
+        {
+            tokens.push(Token::Comma);
+        }
+        else if chars[i] == '!'
+        {
+            tokens.push(Token::Function { func: Function::Factorial });
+        }
+        else if chars[i] == '-' && expects_value(&tokens)
+        {
+            tokens.push(Token::Function { func: Function::Negate });
+        }
+        else if chars[i] == '/' && chars.get(i + 1) == Some(&'/')
+        {
+            tokens.push(Token::Operator { op: Operation::IntegerDivision });
+            i += 1;
+        }
         else
         {
             tokens.push(Token::Operator {
@@ -140,6 +419,8 @@ pub fn tokenize(string: &str) -> Vec<Token>
                     '*' => Operation::Multiplication,
                     '/' => Operation::Division,
                     '^' => Operation::Exponentiation,
+                    '%' => Operation::Remainder,
+                    '.' => Operation::Dot,
                     _ => panic!("unknown operator: {}", chars[i]),
                 },
             });
@@ -150,17 +431,108 @@ pub fn tokenize(string: &str) -> Vec<Token>
     tokens
 }
 
+// a number written without its leading zero, like ".5". any other dot is the dot product
+fn starts_fraction(chars: &[char], i: usize) -> bool
+{
+    chars[i] == '.' && chars.get(i + 1).is_some_and(|c| c.is_numeric())
+}
+
+// a minus sign where a value is expected, like at the start of "-4 + x" or in "2^-1", belongs to
+// the number after it
+fn is_negative_number(chars: &[char], i: usize, tokens: &[Token]) -> bool
+{
+    chars[i] == '-'
+        && expects_value(tokens)
+        && chars
+            .get(i + 1)
+            .is_some_and(|c| c.is_numeric() || *c == '.')
+}
+
+// whether the next token starts a value instead of continuing one, so a minus sign there negates
+// instead of subtracting
+fn expects_value(tokens: &[Token]) -> bool
+{
+    match tokens.last()
+    {
+        None | Some(Token::LSep | Token::LBracket | Token::Comma | Token::Operator { .. }) => true,
+        Some(Token::Function { func }) => !func.is_postfix(),
+        Some(Token::Number { .. } | Token::Variable { .. } | Token::Array { .. }) => false,
+        Some(Token::RSep | Token::RBracket) => false,
+    }
+}
+
+// inserts the multiplications left out in things like "2x", "3(x + 1)" or "(x + 1)(x - 1)"
+pub fn insert_implicit_multiplication(tokens: Vec<Token>) -> Vec<Token>
+{
+    let mut result: Vec<Token> = Vec::with_capacity(tokens.len());
+
+    for token in tokens
+    {
+        let ends_value = match result.last()
+        {
+            Some(Token::Function { func }) => func.is_postfix(),
+            last => matches!(
+                last,
+                Some(Token::Number { .. } | Token::Variable { .. } | Token::RSep | Token::RBracket)
+            ),
+        };
+        let starts_value = match &token
+        {
+            Token::Function { func } => !func.is_postfix(),
+            _ => matches!(
+                token,
+                Token::Number { .. } | Token::Variable { .. } | Token::LSep | Token::LBracket
+            ),
+        };
+
+        if ends_value && starts_value
+        {
+            result.push(Token::Operator { op: Operation::Multiplication });
+        }
+        result.push(token);
+    }
+
+    result
+}
+
+// a minus sign, and a function called without parentheses like the sin in "sin x + 1", bind looser
+// than ^ but as tight as everything else
+const PREFIX_PRECEDENCE: i32 = 3;
+
 pub fn shunting_yard(infix_tokens: Vec<Token>) -> Vec<Token>
 {
     let mut postfix_tokens: Vec<Token> = Vec::new();
     let mut op_stack: Vec<Token> = Vec::new();
+    // the number of commas seen so far inside each parenthesis or bracket that's still open, and
+    // whether the last token opened an array, which makes it empty if the next one closes it
+    let mut commas: Vec<usize> = Vec::new();
+    let mut empty_array = false;
 
-    for token in infix_tokens
+    let mut infix_tokens = infix_tokens.into_iter().peekable();
+    while let Some(token) = infix_tokens.next()
     {
+        let opens_array = token == Token::LBracket;
+
         match token
         {
-            Token::Number { .. } => postfix_tokens.push(token),
-            Token::LSep => op_stack.push(token),
+            Token::Number { .. } | Token::Variable { .. } => postfix_tokens.push(token),
+            // binds tighter than any operator, so it applies to whatever came right before it
+            Token::Function { func } if func.is_postfix() => postfix_tokens.push(token),
+            Token::Function { func } =>
+            {
+                // only single arguments can go without parentheses
+                assert!(
+                    func.arity() == 1 || infix_tokens.peek() == Some(&Token::LSep),
+                    "{} needs its arguments in parentheses",
+                    func.name()
+                );
+                op_stack.push(token)
+            },
+            Token::LSep =>
+            {
+                op_stack.push(token);
+                commas.push(0);
+            },
             Token::RSep =>
             {
                 loop
@@ -171,6 +543,34 @@ pub fn shunting_yard(infix_tokens: Vec<Token>) -> Vec<Token>
 
                     if cur_token == Token::LSep
                     {
+                        let arguments = commas.pop().unwrap() + 1;
+
+                        // the parentheses held the arguments of a function call. sum only binds a
+                        // variable when it has all four, and otherwise adds up a list
+                        match op_stack.last()
+                        {
+                            Some(Token::Function { func: Function::Sum }) if arguments == 1 =>
+                            {
+                                op_stack.pop();
+                                postfix_tokens.push(Token::Function {
+                                    func: Function::Aggregate(Aggregate::Sum),
+                                });
+                            },
+                            // a minus sign in front of parentheses waits for what binds tighter
+                            // to it, like the ^ in "-(x)^2"
+                            Some(Token::Function { func }) if *func != Function::Negate =>
+                            {
+                                assert!(
+                                    arguments == func.arity(),
+                                    "{} takes {} arguments but was given {}",
+                                    func.name(),
+                                    func.arity(),
+                                    arguments
+                                );
+                                postfix_tokens.push(op_stack.pop().unwrap())
+                            },
+                            _ => (),
+                        }
                         break;
                     }
                     else
@@ -179,6 +579,48 @@ pub fn shunting_yard(infix_tokens: Vec<Token>) -> Vec<Token>
                     }
                 }
             },
+            Token::LBracket =>
+            {
+                op_stack.push(token);
+                commas.push(0);
+            },
+            Token::RBracket =>
+            {
+                loop
+                {
+                    let cur_token = op_stack
+                        .pop()
+                        .expect("closing bracket without an opening one");
+
+                    if cur_token == Token::LBracket
+                    {
+                        break;
+                    }
+                    postfix_tokens.push(cur_token);
+                }
+
+                let separators = commas.pop().unwrap();
+                let len = match empty_array
+                {
+                    true => 0,
+                    false => separators + 1,
+                };
+                postfix_tokens.push(Token::Array { len });
+            },
+            // finishes off the previous argument or element, leaving the parenthesis or bracket
+            // for the next one
+            Token::Comma =>
+            {
+                while !matches!(op_stack.last(), Some(Token::LSep | Token::LBracket))
+                {
+                    let cur_token = op_stack
+                        .pop()
+                        .expect("comma outside of a function call or array");
+                    postfix_tokens.push(cur_token);
+                }
+
+                *commas.last_mut().unwrap() += 1;
+            },
             Token::Operator { .. } =>
             {
                 let cur_op = match token
@@ -187,13 +629,16 @@ pub fn shunting_yard(infix_tokens: Vec<Token>) -> Vec<Token>
                     _ => panic!("token {:?} is not an operator", token),
                 };
 
-                while !op_stack.is_empty() && op_stack.last().unwrap() != &Token::LSep
+                while !matches!(op_stack.last(), None | Some(Token::LSep | Token::LBracket))
                 {
-                    let stack_op = op_stack.last().unwrap().get_operator().expect(
-                        format!("not an operator: {:?}", op_stack.last().unwrap()).as_str(),
-                    );
-                    if stack_op.precedence() > cur_op.precedence()
-                        || (stack_op.precedence() == cur_op.precedence()
+                    let stack_precedence = match op_stack.last().unwrap()
+                    {
+                        Token::Operator { op } => op.precedence(),
+                        Token::Function { .. } => PREFIX_PRECEDENCE,
+                        token => panic!("not an operator: {:?}", token),
+                    };
+                    if stack_precedence > cur_op.precedence()
+                        || (stack_precedence == cur_op.precedence()
                             && cur_op.associativity() == Associativity::Left)
                     {
                         postfix_tokens.push(op_stack.pop().unwrap())
@@ -205,12 +650,15 @@ pub fn shunting_yard(infix_tokens: Vec<Token>) -> Vec<Token>
                 }
                 op_stack.push(token);
             },
+            Token::Array { .. } => panic!("unexpected token for infix expression: {:?}", token),
         }
+
+        empty_array = opens_array;
     }
 
-    while !op_stack.is_empty()
+    while let Some(token) = op_stack.pop()
     {
-        postfix_tokens.push(op_stack.pop().unwrap());
+        postfix_tokens.push(token);
     }
 
     postfix_tokens
@@ -236,10 +684,25 @@ pub fn make_token(string: &str) -> Token
         "*" => Token::Operator { op: Operation::Multiplication },
         "/" => Token::Operator { op: Operation::Division },
         "^" => Token::Operator { op: Operation::Exponentiation },
+        "//" => Token::Operator { op: Operation::IntegerDivision },
+        "%" => Token::Operator { op: Operation::Remainder },
+        "." => Token::Operator { op: Operation::Dot },
+        "!" => Token::Function { func: Function::Factorial },
 
         "(" => Token::LSep,
         ")" => Token::RSep,
+        "," => Token::Comma,
+        "[" => Token::LBracket,
+        "]" => Token::RBracket,
 
+        name if name.starts_with(|c: char| c.is_alphabetic() || c == '_') =>
+        {
+            match Function::from_name(name)
+            {
+                Some(func) => Token::Function { func },
+                None => Token::Variable { name: name.to_string() },
+            }
+        },
         number => Token::Number { val: number.to_string() },
     }
 }
@@ -285,6 +748,73 @@ mod tests
 
         assert_eq!(test1, tokens);
         assert_eq!(test2, tokens);
+
+        let tokens = create_tokens!["sqrt", "(", "x", ")", "+", "sin", "(", "2", "*", "y", ")"];
+        let test1 = tokenize("sqrt(x)+sin(2*y)");
+        let test2 = tokenize("sqrt  (x) + sin( 2 * y )");
+
+        assert_eq!(test1, tokens);
+        assert_eq!(test2, tokens);
+
+        let tokens = create_tokens!["prod", "(", "i", ",", "1", ",", "10", ",", "i", ")"];
+        assert_eq!(tokenize("prod(i, 1, 10, i)"), tokens);
+
+        let mut tokens = vec![Token::Variable { name: "max".to_string() }];
+        tokens.extend(create_tokens!["-", "min", "(", "x", ")", "*"]);
+        tokens.push(Token::Variable { name: "mean".to_string() });
+        assert_eq!(tokenize("max - min (x) * mean"), tokens);
+
+        let tokens = create_tokens!["x", "^", "2", "+", "rate_1", "*", "(", "y", "-", ".5", ")"];
+        let test1 = tokenize("x^2+rate_1*(y-.5)");
+        let test2 = tokenize("x ^ 2 +   rate_1 * ( y - .5 )");
+
+        assert_eq!(test1, tokens);
+        assert_eq!(test2, tokens);
+
+        let tokens = create_tokens![
+            "-4", "+", "sqrt", "(", "-.5", ")", "*", "2", "^", "-1", "-", "3"
+        ];
+        assert_eq!(tokenize("-4 + sqrt(-.5) * 2^-1 - 3"), tokens);
+
+        // the imaginary unit is an ordinary variable here, and only complex evaluation knows it
+        let tokens = create_tokens![
+            "(", "1", "+", "2", "i", ")", "*", "(", "3", "-", "i", ")", "-", "-0.5", "i"
+        ];
+        assert_eq!(tokenize("(1+2i)*(3-i) - -0.5i"), tokens);
+
+        // ^ and ! bind tighter than a minus sign, which then can't be part of the number
+        let negate = Token::Function { func: Function::Negate };
+        let mut tokens = vec![negate.clone()];
+        tokens.extend(create_tokens!["2", "^", "2", "-"]);
+        tokens.push(negate.clone());
+        tokens.extend(create_tokens!["x", "*"]);
+        tokens.push(negate);
+        tokens.extend(create_tokens!["3", "!"]);
+        assert_eq!(tokenize("-2^2 - -x * -3!"), tokens);
+
+        let tokens = create_tokens!["2", "in", "+", "3", "ix"];
+        assert_eq!(tokenize("2in + 3ix"), tokens);
+
+        let tokens = create_tokens!["(", "3.2±0.1", ")", "*", "x", "-", "-1±.05"];
+        assert_eq!(tokenize("(3.2 ± 0.1) * x - -1±.05"), tokens);
+
+        let tokens = create_tokens![
+            "[", "[", "1", ",", "-2", "]", "]", ".", "[", ".5", ",", "x", "]"
+        ];
+        assert_eq!(tokenize("[[1,-2]] . [.5, x]"), tokens);
+    }
+
+    #[test]
+    fn test_implicit_multiplication()
+    {
+        let tokens = create_tokens!["2", "*", "x", "+", "3", "*", "(", "x", "-", "1", ")", "*", "(", "y", ")"];
+        assert_eq!(insert_implicit_multiplication(tokenize("2x + 3(x - 1)(y)")), tokens);
+
+        let tokens = create_tokens!["x", "*", "sin", "(", "x", ")", "-", "5"];
+        assert_eq!(insert_implicit_multiplication(tokenize("x sin(x) - 5")), tokens);
+
+        let tokens = create_tokens!["3", "!", "*", "x", "*", "(", "y", ")", "!"];
+        assert_eq!(insert_implicit_multiplication(tokenize("3!x(y)!")), tokens);
     }
 
     #[test]
@@ -309,5 +839,77 @@ mod tests
         let infix_tokens = create_tokens!["2", "^", "(", "3", "+", "4", ")"];
         let postfix_tokens = create_tokens!["2", "3", "4", "+", "^"];
         assert_eq!(shunting_yard(infix_tokens), postfix_tokens);
+
+        let infix_tokens = create_tokens!["ln", "(", "x", "+", "1", ")", "*", "exp", "(", "2", ")"];
+        let postfix_tokens = create_tokens!["x", "1", "+", "ln", "2", "exp", "*"];
+        assert_eq!(shunting_yard(infix_tokens), postfix_tokens);
+
+        let infix_tokens = create_tokens!["sum", "(", "k", ",", "1", ",", "n", "+", "1", ",", "k", "^", "2", ")"];
+        let postfix_tokens = create_tokens!["k", "1", "n", "1", "+", "k", "2", "^", "sum"];
+        assert_eq!(shunting_yard(infix_tokens), postfix_tokens);
+
+        let infix_tokens = create_tokens!["x", "*", "(", "y", "+", "2", ")"];
+        let postfix_tokens = create_tokens!["x", "y", "2", "+", "*"];
+        assert_eq!(shunting_yard(infix_tokens), postfix_tokens);
+
+        let infix_tokens = tokenize("2^3! // 4 % (1 + 2)!");
+        let postfix_tokens = create_tokens!["2", "3", "!", "^", "4", "//", "1", "2", "+", "!", "%"];
+        assert_eq!(shunting_yard(infix_tokens), postfix_tokens);
+
+        let infix_tokens = tokenize("[[1, 2 + x], [sum(k, 1, 2, k), 4]] . []");
+        let mut postfix_tokens = create_tokens!["1", "2", "x", "+"];
+        postfix_tokens.push(Token::Array { len: 2 });
+        postfix_tokens.extend(create_tokens!["k", "1", "2", "k", "sum", "4"]);
+        postfix_tokens.push(Token::Array { len: 2 });
+        postfix_tokens.push(Token::Array { len: 2 });
+        postfix_tokens.push(Token::Array { len: 0 });
+        postfix_tokens.push(make_token("."));
+        assert_eq!(shunting_yard(infix_tokens), postfix_tokens);
+
+        // a minus sign applies after ^ but before everything else
+        let negate = Token::Function { func: Function::Negate };
+        let mut postfix_tokens = create_tokens!["2", "2", "^"];
+        postfix_tokens.push(negate.clone());
+        postfix_tokens.extend(create_tokens!["2", "x"]);
+        postfix_tokens.push(negate.clone());
+        postfix_tokens.extend(create_tokens!["^", "3", "*", "x", "2", "^"]);
+        postfix_tokens.push(negate);
+        postfix_tokens.extend(create_tokens!["/", "+"]);
+        assert_eq!(shunting_yard(tokenize("-2^2 + 2^-x*3 / -(x)^2")), postfix_tokens);
+
+        // sum with a single argument adds up a list instead of binding a variable
+        let infix_tokens = tokenize("sum([1, 2]) + sum(k, 1, 2, (k)) * mean([])");
+        let mut postfix_tokens = create_tokens!["1", "2"];
+        postfix_tokens.push(Token::Array { len: 2 });
+        postfix_tokens.push(Token::Function { func: Function::Aggregate(Aggregate::Sum) });
+        postfix_tokens.extend(create_tokens!["k", "1", "2", "k", "sum"]);
+        postfix_tokens.push(Token::Array { len: 0 });
+        postfix_tokens.extend(create_tokens!["mean", "*", "+"]);
+        assert_eq!(shunting_yard(infix_tokens), postfix_tokens);
+    }
+
+    #[test]
+    fn test_call_without_parentheses()
+    {
+        let postfix_tokens =
+            create_tokens!["1", "sin", "1", "+", "x", "2", "^", "cos", "2", "*", "-"];
+        assert_eq!(
+            shunting_yard(tokenize("sin 1 + 1 - cos x^2 * 2")),
+            postfix_tokens
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "sum needs its arguments in parentheses")]
+    fn test_sum_without_parentheses()
+    {
+        shunting_yard(tokenize("sum k + 1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "sum takes 4 arguments but was given 2")]
+    fn test_wrong_argument_count()
+    {
+        shunting_yard(tokenize("sum(k, 3)"));
     }
 }
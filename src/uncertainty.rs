@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::solver::Expression;
+use crate::tokens::*;
+
+#[derive(Debug, PartialEq)]
+pub enum UncertaintyError
+{
+    InvalidLiteral(String),
+    // the measurement whose uncertainty can't be carried through the expression because it has no
+    // slope there, like the one in "( 3 ± 0.1 )!"
+    NoDerivative(String),
+    // a variable with no measurement given for it
+    UnboundVariable(String),
+}
+
+// a value known up to a standard uncertainty, like "3.2 ± 0.1"
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Measurement
+{
+    pub mean: f64,
+    pub uncertainty: f64,
+}
+
+impl Measurement
+{
+    pub fn new(mean: f64, uncertainty: f64) -> Measurement
+    {
+        Measurement { mean, uncertainty }
+    }
+
+    // reads "3.2±0.1", or a plain number that is known exactly
+    pub fn parse(literal: &str) -> Result<Measurement, UncertaintyError>
+    {
+        let invalid = || UncertaintyError::InvalidLiteral(literal.to_string());
+        let (mean, uncertainty) = literal.split_once('±').unwrap_or((literal, "0"));
+
+        Ok(Measurement {
+            mean: mean.parse().map_err(|_| invalid())?,
+            uncertainty: uncertainty.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    // the number of decimal places the measurement is known to. the uncertainty keeps one
+    // significant digit, or two when the first one is a 1
+    fn decimals(&self) -> i32
+    {
+        let decimals_for = |exponent: i32, leading: f64| match leading < 2.
+        {
+            true => 1 - exponent,
+            false => -exponent,
+        };
+
+        let exponent = self.uncertainty.log10().floor() as i32;
+        let decimals = decimals_for(exponent, self.uncertainty / 10f64.powi(exponent));
+
+        // rounding can carry into the next digit, like 0.096 to 0.1
+        match round_to(self.uncertainty, decimals) >= 10f64.powi(exponent + 1)
+        {
+            true => decimals_for(exponent + 1, 1.),
+            false => decimals,
+        }
+    }
+}
+
+fn round_to(val: f64, decimals: i32) -> f64
+{
+    let scale = 10f64.powi(decimals);
+    (val * scale).round() / scale
+}
+
+// halfway cases are rounded away from zero
+fn format_to(val: f64, decimals: i32) -> String
+{
+    // adding zero turns -0 into 0
+    let val = round_to(val, decimals) + 0.;
+
+    format!("{:.*}", decimals.max(0) as usize, val)
+}
+
+// "3.2 ± 0.1", with the mean rounded to the last digit the uncertainty keeps
+impl fmt::Display for Measurement
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        if self.uncertainty == 0. || !self.uncertainty.is_finite()
+        {
+            return write!(f, "{} ± {}", self.mean, self.uncertainty);
+        }
+
+        let decimals = self.decimals();
+        write!(
+            f,
+            "{} ± {}",
+            format_to(self.mean, decimals),
+            format_to(self.uncertainty, decimals)
+        )
+    }
+}
+
+impl Expression
+{
+    pub fn eval_uncertain(&self) -> Result<Measurement, UncertaintyError>
+    {
+        self.eval_uncertain_with(&HashMap::new())
+    }
+
+    // the value of the expression at the means, with an uncertainty from the first order terms of
+    // its taylor series. each variable and each "±" literal is an independent measurement, so a
+    // variable used twice is the same measurement both times
+    pub fn eval_uncertain_with(
+        &self,
+        vars: &HashMap<String, Measurement>,
+    ) -> Result<Measurement, UncertaintyError>
+    {
+        let mut measurements = vars.clone();
+        let mut literals: HashMap<String, String> = HashMap::new();
+
+        // each literal becomes a variable of its own, with a name no variable can have
+        let tokens = self
+            .to_postfix_tokens()
+            .into_iter()
+            .map(|token| match token
+            {
+                Token::Number { val } if val.contains('±') =>
+                {
+                    let name = format!("±{}", literals.len());
+                    measurements.insert(name.clone(), Measurement::parse(&val)?);
+                    literals.insert(name.clone(), val);
+
+                    Ok(Token::Variable { name })
+                },
+                token => Ok(token),
+            })
+            .collect::<Result<Vec<Token>, UncertaintyError>>()?;
+
+        let means = measurements
+            .iter()
+            .map(|(name, measurement)| (name.clone(), measurement.mean))
+            .collect();
+        let (mean, gradient) = Expression::from_postfix_tokens(tokens)
+            .eval_gradient(&means)
+            .map_err(UncertaintyError::UnboundVariable)?;
+
+        let mut variance = 0.;
+        for (name, derivative) in gradient
+        {
+            let uncertainty = measurements[&name].uncertainty;
+            if uncertainty == 0.
+            {
+                continue;
+            }
+            if derivative.is_nan()
+            {
+                let literal = literals.remove(&name);
+                return Err(UncertaintyError::NoDerivative(literal.unwrap_or(name)));
+            }
+
+            variance += (derivative * uncertainty).powi(2);
+        }
+
+        Ok(Measurement::new(mean, variance.sqrt()))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn eval(expr: &str) -> Result<String, UncertaintyError>
+    {
+        Expression::parse(expr)
+            .eval_uncertain()
+            .map(|val| val.to_string())
+    }
+
+    #[test]
+    fn test_eval_uncertain()
+    {
+        assert_eq!(eval("(3.2 ± 0.1) * 2"), Ok("6.4 ± 0.2".to_string()));
+        assert_eq!(eval("5! * (3 ± 0.1)"), Ok("360 ± 12".to_string()));
+        assert_eq!(eval("(10 ± 0.5)^2 / 4"), Ok("25 ± 3".to_string()));
+        assert_eq!(eval("ln(100 ± 1)"), Ok("4.605 ± 0.010".to_string()));
+
+        // two measurements of the same size are independent, but a variable is the same
+        // measurement wherever it appears
+        assert_eq!(
+            eval("(3.2 ± 0.1) - (3.2 ± 0.1)"),
+            Ok("0.00 ± 0.14".to_string())
+        );
+        let vars = HashMap::from([
+            ("x".to_string(), Measurement::new(3., 0.1)),
+            ("y".to_string(), Measurement::new(4., 0.2)),
+        ]);
+        let result = Expression::parse("x - x + sqrt(x^2 + y^2)").eval_uncertain_with(&vars);
+        assert_eq!(
+            result.map(|val| val.to_string()),
+            Ok("5.00 ± 0.17".to_string())
+        );
+
+        assert_eq!(eval("2 + 3"), Ok("5 ± 0".to_string()));
+        let vars = HashMap::from([
+            ("x".to_string(), Measurement::new(2., 0.1)),
+            ("n".to_string(), Measurement::new(3., 0.)),
+        ]);
+        let result = Expression::parse("sum(k, 1, n, x^k)").eval_uncertain_with(&vars);
+        assert_eq!(
+            result.map(|val| val.to_string()),
+            Ok("14.0 ± 1.7".to_string())
+        );
+        assert_eq!(
+            eval("(2 ± 0.1)!"),
+            Err(UncertaintyError::NoDerivative("2±0.1".to_string()))
+        );
+        assert_eq!(
+            eval("1 ± x"),
+            Err(UncertaintyError::InvalidLiteral("1±".to_string()))
+        );
+        assert_eq!(
+            eval("x * (2 ± 0.1)"),
+            Err(UncertaintyError::UnboundVariable("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_measurement_string()
+    {
+        assert_eq!(Measurement::new(12345., 230.).to_string(), "12300 ± 200");
+        assert_eq!(
+            Measurement::new(0.0123456, 0.00096).to_string(),
+            "0.0123 ± 0.0010"
+        );
+        assert_eq!(Measurement::new(-2.25, 0.15).to_string(), "-2.25 ± 0.15");
+        assert_eq!(Measurement::new(-0.001, 0.3).to_string(), "0.0 ± 0.3");
+        assert_eq!(
+            Measurement::parse("3.2±0.1"),
+            Ok(Measurement::new(3.2, 0.1))
+        );
+    }
+
+    #[test]
+    fn test_uncertain_latex()
+    {
+        assert_eq!(
+            Expression::parse("(3.2 ± 0.1) * x").latex_string(),
+            "{3.2\\pm 0.1}\\cdot{x}"
+        );
+    }
+}
@@ -0,0 +1,653 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+
+use crate::numeric::{array_description, Numeric};
+use crate::solver::Expression;
+use crate::tokens::*;
+
+// powers of metres, kilograms, seconds, amperes, kelvin, moles and candelas
+type Dimensions = [i32; 7];
+
+const LENGTH: Dimensions = [1, 0, 0, 0, 0, 0, 0];
+const MASS: Dimensions = [0, 1, 0, 0, 0, 0, 0];
+const TIME: Dimensions = [0, 0, 1, 0, 0, 0, 0];
+const NONE: Dimensions = [0; 7];
+
+// a size as mantissa * 10^exponent, kept apart so that powers of prefixes like the c in "cm^3"
+// stay exact
+type Scale = (f64, i32);
+
+const ONE: Scale = (1., 0);
+
+// name, size in SI base units, dimensions, and whether it takes a prefix like the k in "km"
+#[rustfmt::skip]
+const UNITS: &[(&str, f64, i32, Dimensions, bool)] = &[
+    ("m", 1., 0, LENGTH, true),
+    ("g", 1., -3, MASS, true),
+    ("s", 1., 0, TIME, true),
+    ("A", 1., 0, [0, 0, 0, 1, 0, 0, 0], true),
+    ("K", 1., 0, [0, 0, 0, 0, 1, 0, 0], true),
+    ("mol", 1., 0, [0, 0, 0, 0, 0, 1, 0], true),
+    ("cd", 1., 0, [0, 0, 0, 0, 0, 0, 1], true),
+    ("Hz", 1., 0, [0, 0, -1, 0, 0, 0, 0], true),
+    ("N", 1., 0, [1, 1, -2, 0, 0, 0, 0], true),
+    ("Pa", 1., 0, [-1, 1, -2, 0, 0, 0, 0], true),
+    ("J", 1., 0, [2, 1, -2, 0, 0, 0, 0], true),
+    ("W", 1., 0, [2, 1, -3, 0, 0, 0, 0], true),
+    ("C", 1., 0, [0, 0, 1, 1, 0, 0, 0], true),
+    ("V", 1., 0, [2, 1, -3, -1, 0, 0, 0], true),
+    ("ohm", 1., 0, [2, 1, -3, -2, 0, 0, 0], true),
+    ("L", 1., -3, [3, 0, 0, 0, 0, 0, 0], true),
+    ("min", 60., 0, TIME, false),
+    ("h", 3600., 0, TIME, false),
+    ("day", 86400., 0, TIME, false),
+    ("inch", 0.0254, 0, LENGTH, false),
+    ("ft", 0.3048, 0, LENGTH, false),
+    ("yd", 0.9144, 0, LENGTH, false),
+    ("mi", 1609.344, 0, LENGTH, false),
+    ("mph", 0.44704, 0, [1, 0, -1, 0, 0, 0, 0], false),
+    ("lb", 0.45359237, 0, MASS, false),
+    ("rad", 1., 0, NONE, false),
+    ("deg", PI / 180., 0, NONE, false),
+];
+
+// name and power of ten
+const PREFIXES: &[(&str, i32)] = &[
+    ("G", 9),
+    ("M", 6),
+    ("k", 3),
+    ("c", -2),
+    ("m", -3),
+    ("u", -6),
+    ("µ", -6),
+    ("n", -9),
+];
+
+// the size and dimensions of a unit like "km", looking for the whole name before a prefix so
+// "min" is minutes and not milli-inches
+pub fn lookup(name: &str) -> Option<(Scale, Dimensions)>
+{
+    let unit = |name: &str| UNITS.iter().find(|unit| unit.0 == name);
+
+    if let Some(&(_, mantissa, exponent, dimensions, _)) = unit(name)
+    {
+        return Some(((mantissa, exponent), dimensions));
+    }
+
+    PREFIXES
+        .iter()
+        .find_map(|&(prefix, power)| match unit(name.strip_prefix(prefix)?)
+        {
+            Some(&(_, mantissa, exponent, dimensions, true)) =>
+            {
+                Some(((mantissa, exponent + power), dimensions))
+            },
+            _ => None,
+        })
+}
+
+// how many of `to` make up one `from`
+fn ratio(from: Scale, to: Scale) -> f64
+{
+    from.0 / to.0 * 10f64.powi(from.1 - to.1)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UnitError
+{
+    InvalidLiteral(String),
+    // the operation whose operands have different dimensions, like "3 m + 2 s"
+    Dimension(String),
+    // the operation that would need a unit to a fractional power, like "sqrt( 2 m )"
+    FractionalPower(String),
+    // a statistic that needs more than the one quantity it was given, like "stdev( 3 m )"
+    NotEnoughValues(String),
+    // the expression whose "in" or "to" is missing a side, like "5 m in", or isn't the last step,
+    // like "(60 mph in m/s) * 2" or "10 s in min in h"
+    Conversion(String),
+    // a vector or matrix of quantities like "[ 1 m, 2 m ]", which has no units
+    Unsupported(String),
+    // the sum or product whose bounds aren't whole numbers, like "sum( k, 1, 2.5 )"
+    Undefined(String),
+    // a name that is neither a unit nor one of the variables given, like "furlongs"
+    UnknownUnit(String),
+}
+
+// a product of named units to whole powers, like km h^-1, in the order they first appeared
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Unit
+{
+    powers: Vec<(String, i32)>,
+}
+
+impl Unit
+{
+    fn named(name: &str) -> Unit
+    {
+        Unit { powers: vec![(name.to_string(), 1)] }
+    }
+
+    fn scale(&self) -> Scale
+    {
+        self.powers
+            .iter()
+            .fold(ONE, |(mantissa, exponent), (name, power)| {
+                let ((unit_mantissa, unit_exponent), _) = lookup(name).unwrap();
+                (
+                    mantissa * unit_mantissa.powi(*power),
+                    exponent + unit_exponent * power,
+                )
+            })
+    }
+
+    fn dimensions(&self) -> Dimensions
+    {
+        let mut dimensions = NONE;
+        for (name, power) in &self.powers
+        {
+            let (_, unit) = lookup(name).unwrap();
+            dimensions
+                .iter_mut()
+                .zip(unit)
+                .for_each(|(total, d)| *total += power * d);
+        }
+
+        dimensions
+    }
+
+    // the product with `other` to the power `sign`, and the factor the magnitude changes by when
+    // units of `other` are converted to ones of the same kind in `self`, like m to km
+    fn combined(&self, other: &Unit, sign: i32) -> (f64, Unit)
+    {
+        let mut factor = ONE;
+        let mut powers = self.powers.clone();
+
+        for (name, power) in &other.powers
+        {
+            let (scale, dimensions) = lookup(name).unwrap();
+            let matching = powers
+                .iter()
+                .position(|(existing, _)| lookup(existing).unwrap().1 == dimensions);
+
+            match matching
+            {
+                Some(i) =>
+                {
+                    let (existing, _) = lookup(&powers[i].0).unwrap();
+                    factor.0 *= (scale.0 / existing.0).powi(sign * power);
+                    factor.1 += (scale.1 - existing.1) * sign * power;
+                    powers[i].1 += sign * power;
+                },
+                None => powers.push((name.clone(), sign * power)),
+            }
+        }
+        powers.retain(|(_, power)| *power != 0);
+
+        (ratio(factor, ONE), Unit { powers })
+    }
+}
+
+// "km/h", "m/s^2" or "kg*m^2/(s^2*A)"
+impl fmt::Display for Unit
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let join = |positive: bool| {
+            self.powers
+                .iter()
+                .filter(|(_, power)| (*power > 0) == positive)
+                .map(|(name, power)| match power.abs()
+                {
+                    1 => name.clone(),
+                    power => format!("{}^{}", name, power),
+                })
+                .collect::<Vec<_>>()
+        };
+        let (numerator, denominator) = (join(true), join(false));
+
+        let numerator = match numerator.is_empty()
+        {
+            true => "1".to_string(),
+            false => numerator.join("*"),
+        };
+        match denominator.len()
+        {
+            0 => write!(f, "{}", numerator),
+            1 => write!(f, "{}/{}", numerator, denominator[0]),
+            _ => write!(f, "{}/({})", numerator, denominator.join("*")),
+        }
+    }
+}
+
+// a number of some unit. results stay in the units they were written in, and the right operand
+// of a sum is converted to the units of the left one
+#[derive(Debug, PartialEq, Clone)]
+pub struct Quantity
+{
+    magnitude: f64,
+    unit: Unit,
+}
+
+impl Quantity
+{
+    pub fn new(magnitude: f64) -> Quantity
+    {
+        Quantity { magnitude, unit: Unit::default() }
+    }
+
+    // one of a unit from the registry, like "km" or "mph"
+    pub fn unit(name: &str) -> Option<Quantity>
+    {
+        lookup(name).map(|_| Quantity { magnitude: 1., unit: Unit::named(name) })
+    }
+
+    pub fn magnitude(&self) -> f64
+    {
+        self.magnitude
+    }
+
+    pub fn unit_string(&self) -> String
+    {
+        self.unit.to_string()
+    }
+
+    // the same amount in the units of `target`, like 60 mph in m/s
+    pub fn convert(&self, target: &Quantity) -> Result<Quantity, UnitError>
+    {
+        if self.unit.dimensions() != target.unit.dimensions()
+        {
+            return Err(UnitError::Dimension(format!(
+                "{} in {}",
+                self,
+                target.unit_string()
+            )));
+        }
+
+        Ok(Quantity {
+            magnitude: self.magnitude * ratio(self.unit.scale(), target.unit.scale()),
+            unit: target.unit.clone(),
+        })
+    }
+
+    // the plain number, for quantities like 2 km/m or 90 deg whose units cancel out
+    fn number(&self) -> Option<f64>
+    {
+        (self.unit.dimensions() == NONE).then(|| self.magnitude * ratio(self.unit.scale(), ONE))
+    }
+
+    fn number_or(&self, description: impl FnOnce() -> String) -> Result<f64, UnitError>
+    {
+        self.number()
+            .ok_or_else(|| UnitError::Dimension(description()))
+    }
+
+    // `other` in the units of self, for operations that need them to match
+    fn matching(&self, other: &Quantity, op: Operation) -> Result<f64, UnitError>
+    {
+        match self.unit.dimensions() == other.unit.dimensions()
+        {
+            true => Ok(other.magnitude * ratio(other.unit.scale(), self.unit.scale())),
+            false => Err(UnitError::Dimension(format!(
+                "{} {} {}",
+                self,
+                op.str(),
+                other
+            ))),
+        }
+    }
+
+    fn product(&self, other: &Quantity, sign: i32) -> Quantity
+    {
+        let (factor, unit) = self.unit.combined(&other.unit, sign);
+        let magnitude = match sign
+        {
+            1 => self.magnitude * other.magnitude * factor,
+            _ => self.magnitude / other.magnitude * factor,
+        };
+
+        // units that cancel down to a plain number leave just the number, but angles keep theirs
+        let cancelled = unit
+            .powers
+            .iter()
+            .all(|(name, _)| lookup(name).unwrap().1 != NONE);
+        match unit.dimensions() == NONE && cancelled
+        {
+            true => Quantity::new(magnitude * ratio(unit.scale(), ONE)),
+            false => Quantity { magnitude, unit },
+        }
+    }
+}
+
+// "2.5 km/h"
+impl fmt::Display for Quantity
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.unit.powers.is_empty()
+        {
+            true => write!(f, "{}", self.magnitude),
+            false => write!(f, "{} {}", self.magnitude, self.unit),
+        }
+    }
+}
+
+impl Numeric for Quantity
+{
+    type Error = UnitError;
+
+    fn parse_literal(literal: &str) -> Result<Self, Self::Error>
+    {
+        literal
+            .parse()
+            .map(Quantity::new)
+            .map_err(|_| UnitError::InvalidLiteral(literal.to_string()))
+    }
+
+    fn to_f64(&self) -> f64
+    {
+        self.number().unwrap_or(f64::NAN)
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let other = self.matching(other, Operation::Addition)?;
+        Ok(Quantity { magnitude: self.magnitude + other, unit: self.unit.clone() })
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let other = self.matching(other, Operation::Subtraction)?;
+        Ok(Quantity { magnitude: self.magnitude - other, unit: self.unit.clone() })
+    }
+
+    fn mul(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self.product(other, 1))
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        Ok(self.product(other, -1))
+    }
+
+    fn int_div(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let other = self.matching(other, Operation::IntegerDivision)?;
+        Ok(Quantity::new((self.magnitude / other).floor()))
+    }
+
+    fn rem(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let other = self.matching(other, Operation::Remainder)?;
+        let magnitude = self.magnitude - other * (self.magnitude / other).floor();
+
+        Ok(Quantity { magnitude, unit: self.unit.clone() })
+    }
+
+    // units can only be raised to whole powers
+    fn pow(&self, other: &Self) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{} ^ {}", self, other);
+        let exponent = other.number_or(description)?;
+
+        if let Some(base) = self.number()
+        {
+            return Ok(Quantity::new(base.powf(exponent)));
+        }
+        if exponent.fract() != 0.
+        {
+            return Err(UnitError::FractionalPower(description()));
+        }
+
+        let powers = self
+            .unit
+            .powers
+            .iter()
+            .map(|(name, power)| (name.clone(), power * exponent as i32))
+            .collect();
+        Ok(Quantity {
+            magnitude: self.magnitude.powf(exponent),
+            unit: Unit { powers },
+        })
+    }
+
+    // sqrt halves the powers of the units, negation keeps them, det and inv treat the quantity like
+    // a matrix of size one and statistics like a list of one, and everything else needs a plain
+    // number
+    fn call(&self, func: Function) -> Result<Self, Self::Error>
+    {
+        let description = || format!("{}( {} )", func.name(), self);
+
+        match func
+        {
+            Function::Negate => return Quantity::new(-1.).mul(self),
+            Function::Det => return Ok(self.clone()),
+            Function::Inverse => return Quantity::new(1.).div(self),
+            Function::Aggregate(aggregate) if aggregate.min_len() > 1 =>
+            {
+                return Err(UnitError::NotEnoughValues(description()))
+            },
+            Function::Aggregate(_) => return Ok(self.clone()),
+            _ => (),
+        }
+
+        if func == Function::Sqrt && self.number().is_none()
+        {
+            if self.unit.powers.iter().any(|(_, power)| power % 2 != 0)
+            {
+                return Err(UnitError::FractionalPower(description()));
+            }
+
+            let powers = self
+                .unit
+                .powers
+                .iter()
+                .map(|(name, power)| (name.clone(), power / 2))
+                .collect();
+            return Ok(Quantity { magnitude: self.magnitude.sqrt(), unit: Unit { powers } });
+        }
+
+        let val = self.number_or(description)?;
+        Ok(Quantity::new(Expression::eval_function(func, val)))
+    }
+
+    fn array(elements: Vec<Self>) -> Result<Self, Self::Error>
+    {
+        Err(UnitError::Unsupported(array_description(&elements)))
+    }
+
+    fn invalid_bounds(description: String) -> Result<Self, Self::Error>
+    {
+        Err(UnitError::Undefined(description))
+    }
+}
+
+// evaluates expressions with units, like "5 km / 2 h" or "60 mph in m/s". a number followed by a
+// unit is a single value, and "in" or "to" converts everything before it to the units after it.
+// variables in `vars` hide units with the same name
+pub fn evaluate_units(expr: &str, vars: &HashMap<String, Quantity>) -> Result<Quantity, UnitError>
+{
+    let is_unit = |name: &str| !vars.contains_key(name) && lookup(name).is_some();
+
+    let tokens = tokenize(expr);
+    let invalid = || UnitError::Conversion(expr.to_string());
+
+    // only a conversion outside of all parentheses applies to the whole expression
+    let mut depth = 0;
+    let mut split = None;
+    for (i, token) in tokens.iter().enumerate()
+    {
+        match token
+        {
+            Token::LSep | Token::LBracket => depth += 1,
+            Token::RSep | Token::RBracket => depth -= 1,
+            Token::Variable { name } if name == "in" || name == "to" =>
+            {
+                if depth > 0 || split.is_some()
+                {
+                    return Err(invalid());
+                }
+                split = Some(i);
+            },
+            _ => (),
+        }
+    }
+
+    let (tokens, target) = match split
+    {
+        Some(i) if i == 0 || i == tokens.len() - 1 => return Err(invalid()),
+        Some(i) => (tokens[..i].to_vec(), Some(tokens[i + 1..].to_vec())),
+        None => (tokens, None),
+    };
+
+    let eval = |tokens: Vec<Token>| {
+        // "5 km" is read as "(5 km)", so "5 km / 2 h" divides by the whole of "2 h",
+        let mut grouped = Vec::with_capacity(tokens.len());
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next()
+        {
+            match (&token, tokens.peek())
+            {
+                (Token::Number { .. }, Some(Token::Variable { name })) if is_unit(name) =>
+                {
+                    let unit = tokens.next().unwrap();
+                    grouped.extend([
+                        Token::LSep,
+                        token,
+                        Token::Operator { op: Operation::Multiplication },
+                        unit,
+                    ]);
+
+                    // and "16 m^2" as "(16 m^2)"
+                    if tokens.peek() == Some(&Token::Operator { op: Operation::Exponentiation })
+                    {
+                        grouped.extend(tokens.next());
+                        grouped
+                            .extend(tokens.next_if(|token| matches!(token, Token::Number { .. })));
+                    }
+                    grouped.push(Token::RSep);
+                },
+                _ => grouped.push(token),
+            }
+        }
+
+        let tree =
+            Expression::from_postfix_tokens(shunting_yard(insert_implicit_multiplication(grouped)));
+        let mut scope = vars.clone();
+        for name in tree.free_variables()
+        {
+            if !vars.contains_key(&name)
+            {
+                let unit =
+                    Quantity::unit(&name).ok_or_else(|| UnitError::UnknownUnit(name.clone()))?;
+                scope.insert(name, unit);
+            }
+        }
+
+        tree.eval_numeric_with(&scope)
+    };
+
+    let result = eval(tokens)?;
+    match target
+    {
+        Some(target) => result.convert(&eval(target)?),
+        None => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn eval(expr: &str) -> Result<String, UnitError>
+    {
+        evaluate_units(expr, &HashMap::new()).map(|val| val.to_string())
+    }
+
+    #[test]
+    fn test_evaluate_units()
+    {
+        assert_eq!(eval("5 km / 2 h"), Ok("2.5 km/h".to_string()));
+        assert_eq!(eval("1 km + 500 m"), Ok("1.5 km".to_string()));
+        assert_eq!(eval("3 m * 2 m * 50 cm"), Ok("3 m^3".to_string()));
+        assert_eq!(eval("2 kg * 9.8 m/s^2"), Ok("19.6 kg*m/s^2".to_string()));
+        assert_eq!(eval("6 J / (2 s * 3 A)"), Ok("1 J/(s*A)".to_string()));
+        assert_eq!(eval("2 km / 500 m + 1"), Ok("5".to_string()));
+        assert_eq!(eval("sqrt(16 m^2) + 50 cm"), Ok("4.5 m".to_string()));
+        assert_eq!(
+            eval("sin(90 deg) + 17 m // 5 m + 17 m % 5 m / 1 m"),
+            Ok("6".to_string())
+        );
+
+        let vars = HashMap::from([("d".to_string(), Quantity::unit("mi").unwrap())]);
+        let result = evaluate_units("d / 30 min in km/h", &vars).unwrap();
+        assert!((result.magnitude() - 3.218688).abs() < 1e-12);
+        assert_eq!(result.unit_string(), "km/h");
+    }
+
+    #[test]
+    fn test_unit_conversion()
+    {
+        assert_eq!(eval("60 mph in m/s"), Ok("26.8224 m/s".to_string()));
+        assert_eq!(eval("1.5 h to min"), Ok("90 min".to_string()));
+        assert_eq!(eval("10 min + min(30 s)"), Ok("10.5 min".to_string()));
+        assert_eq!(eval("(1 km + 500 m) in m"), Ok("1500 m".to_string()));
+        assert_eq!(eval("1 L in cm^3"), Ok("1000 cm^3".to_string()));
+        assert_eq!(
+            eval("60 mph in kg"),
+            Err(UnitError::Dimension("60 mph in kg".to_string()))
+        );
+        assert_eq!(
+            eval("5 furlongs + 1 m"),
+            Err(UnitError::UnknownUnit("furlongs".to_string()))
+        );
+        assert_eq!(
+            eval("5 m * x in cm"),
+            Err(UnitError::UnknownUnit("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_unit_errors()
+    {
+        assert_eq!(
+            eval("3 m + 2 s"),
+            Err(UnitError::Dimension("3 m + 2 s".to_string()))
+        );
+        assert_eq!(
+            eval("2^(3 m)"),
+            Err(UnitError::Dimension("2 ^ 3 m".to_string()))
+        );
+        assert_eq!(
+            eval("sqrt(2 m)"),
+            Err(UnitError::FractionalPower("sqrt( 2 m )".to_string()))
+        );
+        assert_eq!(
+            eval("ln(5 km / 2 h)"),
+            Err(UnitError::Dimension("ln( 2.5 km/h )".to_string()))
+        );
+        assert_eq!(
+            eval("stdev(3 m)"),
+            Err(UnitError::NotEnoughValues("stdev( 3 m )".to_string()))
+        );
+
+        let conversion = |expr: &str| Err(UnitError::Conversion(expr.to_string()));
+        for expr in ["5 m in", "to m", "(60 mph in m/s) * 2", "10 s in min in h"]
+        {
+            assert_eq!(eval(expr), conversion(expr));
+        }
+    }
+
+    #[test]
+    fn test_lookup()
+    {
+        assert_eq!(lookup("km"), Some(((1., 3), LENGTH)));
+        assert_eq!(lookup("kg"), Some(((1., 0), MASS)));
+        assert_eq!(lookup("min"), Some(((60., 0), TIME)));
+        assert_eq!(lookup("µs"), Some(((1., -6), TIME)));
+        assert_eq!(lookup("kmin"), None);
+        assert_eq!(lookup("x"), None);
+    }
+}
@@ -1,8 +1,32 @@
-use math_evaluator::evaluate;
+use math_evaluator::{evaluate, evaluate_parallel};
 
 #[test]
 fn test_eval()
 {
     assert_eq!(evaluate("2+2"), 4.);
     assert_eq!(evaluate("2*4+6"), 14.);
+    assert_eq!(evaluate("sin 1 + 1"), 1f64.sin() + 1.);
+}
+
+#[test]
+fn test_eval_parallel()
+{
+    assert_eq!(evaluate_parallel("2+2"), 4.);
+    assert_eq!(
+        evaluate_parallel("(4*2-(8-3))/(5^.4*2) + 5^(.2-.8/3)"),
+        evaluate("(4*2-(8-3))/(5^.4*2) + 5^(.2-.8/3)")
+    );
+}
+
+#[test]
+fn test_eval_arrays()
+{
+    assert_eq!(evaluate("[1,2,3] . [4,5,6]"), 32.);
+    assert_eq!(evaluate("mean([3,5,8]) * 3"), 16.);
+    assert_eq!(evaluate("det([[1, 2], [3, 4]])"), -2.);
+
+    // vectors and matrices themselves have no single value, and neither do mistakes
+    assert!(evaluate("[1, 2] + [3, 4]").is_nan());
+    assert!(evaluate("[1, 2] + [3, 4, 5]").is_nan());
+    assert!(evaluate("0/0").is_nan());
 }